@@ -0,0 +1,2 @@
+from ._xingque import *  # noqa: F401,F403
+from ._xingque import __all__  # noqa: F401
@@ -0,0 +1,1150 @@
+import inspect
+import os
+from types import EllipsisType
+from typing import Callable, Literal, Iterable, Iterator, Protocol, Self, TypeVar, overload
+
+VERSION: str
+STARLARK_RUST_VERSION: str
+
+# starlark::codemap
+
+class CodeMap:
+    def __init__(self, filename: str, source: str) -> None: ...
+    # TODO: empty_static
+    def full_span(self) -> Span: ...
+    def file_span(self, span: Span) -> FileSpan: ...
+    @property
+    def filename(self) -> str: ...
+    def byte_at(self, pos: Pos) -> int: ...
+    def find_line(self, pos: Pos) -> int: ...
+    @property
+    def source(self) -> str: ...
+    def source_span(self, span: Span) -> str: ...
+    def line_span(self, line: int) -> Span: ...
+    def line_span_opt(self, line: int) -> Span | None: ...
+    def resolve_span(self, span: Span) -> ResolvedSpan: ...
+    def source_line(self, line: int) -> str: ...
+    def source_line_at_pos(self, pos: Pos) -> str: ...
+
+class FileSpan:
+    def __init__(self, filename: str, source: str) -> None: ...
+    @property
+    def file(self) -> CodeMap: ...
+    @property
+    def span(self) -> Span: ...
+    @property
+    def filename(self) -> str: ...
+    @property
+    def source_span(self) -> str: ...
+    def resolve_span(self) -> ResolvedSpan: ...
+    def resolve(self) -> ResolvedFileSpan: ...
+
+class Pos:
+    def __init__(self, x: int) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    def get(self) -> int: ...
+    def __int__(self) -> int: ...
+    def __add__(self, other: int) -> Self: ...
+    def __iadd__(self, other: int) -> None: ...
+
+class ResolvedFileLine:
+    file: str
+    line: int
+    def __init__(self, file: str, line: int) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+
+class ResolvedFileSpan:
+    file: str
+    span: ResolvedSpan
+    def __init__(self, file: str, span: ResolvedSpan) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    def begin_file_line(self) -> ResolvedFileLine: ...
+
+class ResolvedPos:
+    def __init__(self, line: int, column: int) -> None: ...
+    @property
+    def line(self) -> int: ...
+    @property
+    def column(self) -> int: ...
+
+class ResolvedSpan:
+    def __init__(self, begin: ResolvedPos, end: ResolvedPos) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    @property
+    def begin(self) -> ResolvedPos: ...
+    @property
+    def end(self) -> ResolvedPos: ...
+    def __contains__(self, pos: ResolvedPos) -> bool: ...
+    def contains(self, pos: ResolvedPos) -> bool: ...
+
+class Span:
+    def __init__(self, begin: Pos, end: Pos) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    @property
+    def begin(self) -> Pos: ...
+    @property
+    def end(self) -> Pos: ...
+    def merge(self, other: Self) -> Self: ...
+    # TODO: merge_all
+    def end_span(self) -> Self: ...
+    def __contains__(self, pos: Pos | int) -> bool: ...
+    def contains(self, pos: Pos | int) -> bool: ...
+
+# starlark::docs
+
+class DocString:
+    @property
+    def summary(self) -> str: ...
+    @property
+    def details(self) -> str | None: ...
+
+class DocParamKind:
+    ARG: DocParamKind
+    NO_ARGS: DocParamKind
+    ONLY_POS_BEFORE: DocParamKind
+    ARGS: DocParamKind
+    KWARGS: DocParamKind
+
+class DocParam:
+    @property
+    def kind(self) -> DocParamKind: ...
+    @property
+    def name(self) -> str | None: ...
+    @property
+    def docs(self) -> DocString | None: ...
+    @property
+    def type(self) -> str | None: ...
+    @property
+    def default_value(self) -> str | None: ...
+
+class DocReturn:
+    @property
+    def docs(self) -> DocString | None: ...
+    @property
+    def type(self) -> str: ...
+
+class DocFunction:
+    @property
+    def docs(self) -> DocString | None: ...
+    @property
+    def params(self) -> list[DocParam]: ...
+    @property
+    def ret(self) -> DocReturn: ...
+    @property
+    def as_type(self) -> str | None: ...
+    def to_json(self) -> str: ...
+
+class DocProperty:
+    @property
+    def docs(self) -> DocString | None: ...
+    @property
+    def type(self) -> str: ...
+    def to_json(self) -> str: ...
+
+class DocObject:
+    @property
+    def docs(self) -> DocString | None: ...
+    @property
+    def members(self) -> dict[str, DocFunction | DocProperty]: ...
+    def to_json(self) -> str: ...
+
+class DocModule:
+    @property
+    def docs(self) -> DocString | None: ...
+    @property
+    def members(self) -> dict[str, DocItem]: ...
+    def to_json(self) -> str: ...
+    def render(
+        self,
+        format: Literal["markdown", "html"] = "markdown",
+        *,
+        title: str | None = None,
+        links: dict[str, str] | None = None,
+    ) -> str: ...
+
+DocItem = DocModule | DocObject | DocFunction | DocProperty
+
+# starlark::environment
+
+class FrozenModule:
+    @staticmethod
+    def from_globals(globals: Globals) -> FrozenModule: ...
+    def get_option(self, name: str) -> object | None: ...
+    def get(self, name: str) -> object | None: ...
+    def names(self) -> Iterator[str]: ...
+    def describe(self) -> str: ...
+    def documentation(self) -> DocModule: ...
+    def render_docs(
+        self,
+        format: Literal["markdown", "html"] = "markdown",
+        *,
+        title: str | None = None,
+        links: dict[str, str] | None = None,
+    ) -> str: ...
+    # TODO: aggregated_heap_profile_info
+    @property
+    def extra_value(self) -> object | None: ...
+
+class Globals:
+    def __init__(self) -> None: ...
+    @staticmethod
+    def standard() -> Globals: ...
+    @staticmethod
+    def extended_by(extensions: Iterable[LibraryExtension]) -> Globals: ...
+    def names(self) -> Iterator[str]: ...
+    def __iter__(self) -> Iterator[tuple[str, object]]: ...
+    def describe(self) -> str: ...
+    @property
+    def docstring(self) -> str | None: ...
+    def documentation(self) -> DocModule: ...
+    def render_docs(
+        self,
+        format: Literal["markdown", "html"] = "markdown",
+        *,
+        title: str | None = None,
+        links: dict[str, str] | None = None,
+    ) -> str: ...
+
+class GlobalsBuilder:
+    def __init__(self) -> None: ...
+    @staticmethod
+    def standard() -> GlobalsBuilder: ...
+    @staticmethod
+    def extended_by(extensions: Iterable[LibraryExtension]) -> GlobalsBuilder: ...
+    def struct(self, name: str, f: Callable[[_SubGlobalsBuilder], None]) -> None: ...
+    def with_(self, f: Callable[[_SubGlobalsBuilder], None]) -> Self: ...
+    def with_struct(
+        self, name: str, f: Callable[[_SubGlobalsBuilder], None]
+    ) -> Self: ...
+    def build(self) -> Globals: ...
+    def set(self, name: str, value: object) -> None: ...
+    def set_function(
+        self,
+        name: str,
+        callable: Callable[..., object],
+        *,
+        signature: inspect.Signature | None = None,
+        docstring: str | None = None,
+        parameter_types: dict[str, str] | None = None,
+        return_type: str | None = None,
+    ) -> None: ...
+    @overload
+    def function(
+        self,
+        f: Callable[..., object],
+        /,
+        *,
+        name: str | None = None,
+        docstring: str | None = None,
+    ) -> StarlarkFunction: ...
+    @overload
+    def function(
+        self,
+        /,
+        *,
+        name: str | None = None,
+        docstring: str | None = None,
+    ) -> Callable[[Callable[..., object]], StarlarkFunction]: ...
+
+class _SubGlobalsBuilder:
+    def struct(self, name: str, f: Callable[[_SubGlobalsBuilder], None]) -> None: ...
+    def with_(self, f: Callable[[_SubGlobalsBuilder], None]) -> Self: ...
+    def with_struct(
+        self, name: str, f: Callable[[_SubGlobalsBuilder], None]
+    ) -> Self: ...
+    def set(self, name: str, value: object) -> None: ...
+    def set_function(
+        self,
+        name: str,
+        callable: Callable[..., object],
+        *,
+        signature: inspect.Signature | None = None,
+        docstring: str | None = None,
+        parameter_types: dict[str, str] | None = None,
+        return_type: str | None = None,
+    ) -> None: ...
+    @overload
+    def function(
+        self,
+        f: Callable[..., object],
+        /,
+        *,
+        name: str | None = None,
+        docstring: str | None = None,
+    ) -> StarlarkFunction: ...
+    @overload
+    def function(
+        self,
+        /,
+        *,
+        name: str | None = None,
+        docstring: str | None = None,
+    ) -> Callable[[Callable[..., object]], StarlarkFunction]: ...
+
+class StarlarkFunction:
+    def __call__(self, *args: object, **kwargs: object) -> object: ...
+    def __get__(self, obj: object, objtype: type | None = None) -> object: ...
+    @property
+    def __wrapped__(self) -> Callable[..., object]: ...
+    @property
+    def name(self) -> str: ...
+    @property
+    def docstring(self) -> str | None: ...
+    @property
+    def parameter_types(self) -> dict[str, str]: ...
+    @property
+    def return_type(self) -> str | None: ...
+
+@overload
+def starlark_function(
+    f: Callable[..., object],
+    /,
+    *,
+    name: str | None = None,
+    docstring: str | None = None,
+) -> StarlarkFunction: ...
+@overload
+def starlark_function(
+    *,
+    name: str | None = None,
+    docstring: str | None = None,
+) -> Callable[[Callable[..., object]], StarlarkFunction]: ...
+
+class LibraryExtension:
+    STRUCT_TYPE: LibraryExtension
+    """Definitions to support the `struct` type, the `struct()` constructor."""
+
+    RECORD_TYPE: LibraryExtension
+    """Definitions to support the `record` type, the `record()` constructor and `field()` function."""
+
+    ENUM_TYPE: LibraryExtension
+    """Definitions to support the `enum` type, the `enum()` constructor."""
+
+    MAP: LibraryExtension
+    """A function `map(f, xs)` which applies `f` to each element of `xs` and returns the result."""
+
+    FILTER: LibraryExtension
+    """A function `filter(f, xs)` which applies `f` to each element of `xs` and returns those for which `f` returns `True`.
+    As a special case, `filter(None, xs)` removes all `None` values.
+    """
+
+    PARTIAL: LibraryExtension
+    """Partially apply a function, `partial(f, *args, **kwargs)` will create a function where those `args` `kwargs`
+    are already applied to `f`.
+    """
+
+    DEBUG: LibraryExtension
+    """Add a function `debug(x)` which shows the Rust `Debug` representation of a value.
+    Useful when debugging, but the output should not be considered stable.
+    """
+
+    PRINT: LibraryExtension
+    """Add a function `print(x)` which prints to stderr."""
+
+    PPRINT: LibraryExtension
+    """Add a function `pprint(x)` which pretty-prints to stderr."""
+
+    BREAKPOINT: LibraryExtension
+    """Add a function `breakpoint()` which will drop into a console-module evaluation prompt."""
+
+    JSON: LibraryExtension
+    """Add a function `json()` which will generate JSON for a module."""
+
+    TYPING: LibraryExtension
+    """Provides `typing.All`, `typing.Callable` etc.
+    Usually used in conjunction with `Dialect.enable_types`."""
+
+    INTERNAL: LibraryExtension
+    """Utilities exposing starlark-rust internals.
+    These are not for production use."""
+
+    CALL_STACK: LibraryExtension
+    """Add a function `call_stack()` which returns a string representation of
+    the current call stack."""
+
+class Module:
+    extra_value: object | None = None
+    def __init__(self) -> None: ...
+    def names(self) -> Iterator[str]: ...
+    def get(self, name: str) -> object: ...
+    def set(self, name: str, value: object) -> None: ...
+    def freeze(self) -> FrozenModule: ...
+
+# starlark::errors
+
+class EvalSeverity:
+    ERROR: EvalSeverity
+    """An error while the program was being parsed."""
+
+    WARNING: EvalSeverity
+    """The program parsed, but might not execute without error."""
+
+    ADVICE: EvalSeverity
+    """This could be changed, but is not a blocking problem."""
+
+    DISABLED: EvalSeverity
+    """This should be ignored"""
+
+class Frame:
+    @property
+    def name(self) -> str: ...
+    @property
+    def location(self) -> FileSpan | None: ...
+
+class Lint:
+    @property
+    def location(self) -> FileSpan: ...
+    @property
+    def short_name(self) -> str: ...
+    @property
+    def severity(self) -> EvalSeverity: ...
+    @property
+    def problem(self) -> str: ...
+    @property
+    def original(self) -> str: ...
+
+class Diagnostic:
+    def __init__(
+        self,
+        message: str,
+        span: FileSpan | None = None,
+        *,
+        severity: EvalSeverity = EvalSeverity.ERROR,
+        name: str | None = None,
+        label: str | None = None,
+        secondary_labels: list[tuple[FileSpan, str]] = [],
+        notes: list[str] = [],
+    ) -> None: ...
+    @staticmethod
+    def from_error(e: BaseException) -> Diagnostic: ...
+    @staticmethod
+    def from_lint(lint: Lint) -> Diagnostic: ...
+    @property
+    def message(self) -> str: ...
+    @property
+    def span(self) -> FileSpan | None: ...
+    @property
+    def severity(self) -> EvalSeverity: ...
+    @property
+    def name(self) -> str | None: ...
+    @property
+    def label(self) -> str | None: ...
+    @property
+    def secondary_labels(self) -> list[tuple[FileSpan, str]]: ...
+    @property
+    def notes(self) -> list[str]: ...
+    def render(self, color: bool = False) -> str: ...
+
+class StarlarkError(Exception):
+    message: str
+    span: FileSpan | None
+    call_stack: list[Frame] | None
+
+class StarlarkParseError(StarlarkError): ...
+class StarlarkTypeCheckError(StarlarkError): ...
+class StarlarkFailError(StarlarkError): ...
+class StarlarkValueError(StarlarkError): ...
+class StarlarkFunctionError(StarlarkError): ...
+class StarlarkScopeError(StarlarkError): ...
+class StarlarkNativeError(StarlarkError): ...
+class StarlarkInternalError(StarlarkError): ...
+
+# starlark::eval
+
+class _FileLoader(Protocol):
+    def load(self, path: str) -> FrozenModule: ...
+
+class DictFileLoader:
+    def __init__(self, modules: dict[str, FrozenModule]) -> None: ...
+    def load(self, path: str) -> FrozenModule: ...
+
+class FilesystemLoader:
+    def __init__(
+        self,
+        root: str | os.PathLike[str],
+        globals: Globals | None = None,
+        dialect: Dialect = Dialect.STANDARD,
+        prefixes: dict[str, str | os.PathLike[str]] | None = None,
+    ) -> None: ...
+    @property
+    def root(self) -> str: ...
+    def resolve(
+        self, path: str, current_file: str | os.PathLike[str] | None = None
+    ) -> str: ...
+    def load(self, path: str) -> FrozenModule: ...
+    def clear_cache(self) -> None: ...
+
+class Evaluator:
+    def __init__(self, module: Module | None = None) -> None: ...
+    # TODO: disable_gc
+    def eval_statements(self, statements: AstModule) -> object: ...
+    def local_variables(self) -> dict[str, object]: ...
+    def verbose_gc(self) -> None: ...
+    def enable_static_typechecking(self, enable: bool) -> None: ...
+    def set_loader(self, loader: _FileLoader) -> None: ...
+    def enable_profile(self, mode: ProfileMode) -> None: ...
+    def write_profile(self, filename: str) -> None: ...
+    def gen_profile(self) -> ProfileData: ...
+    def coverage(self) -> list[ResolvedFileSpan]: ...
+    def enable_terminal_breakpoint_console(self) -> None: ...
+    def call_stack(self) -> list[Frame]: ...
+    def call_stack_top_frame(self) -> Frame | None: ...
+    def call_stack_count(self) -> int: ...
+    def call_stack_top_location(self) -> FileSpan | None: ...
+    def set_print_handler(self, handler: Callable[[str], object] | None) -> None: ...
+    # TODO: heap
+    @property
+    def module(self) -> Module: ...
+    # TODO: frozen_heap
+    # TODO: set_module_variable_at_some_point (is this okay to expose?)
+    def set_max_callstack_size(self, stack_size: int) -> None: ...
+    def eval_module(self, ast: AstModule, globals: Globals) -> object: ...
+    def eval_function(self, function: object, *args, **kwargs) -> object: ...
+
+class CoverageReport:
+    def __init__(self) -> None: ...
+    def add_coverage(self, spans: Iterable[ResolvedFileSpan]) -> None: ...
+    def add_module(self, module: AstModule) -> None: ...
+    @property
+    def files(self) -> list[str]: ...
+    def executed_spans(self, file: str) -> list[ResolvedFileSpan]: ...
+    def executed_lines(self, file: str) -> list[int]: ...
+    def lines(self, file: str) -> list[int]: ...
+    def to_lcov(self) -> str: ...
+    def to_cobertura(self) -> str: ...
+
+class ProfileMode:
+    HEAP_SUMMARY_ALLOCATED: ProfileMode
+    """The heap profile mode provides information about the time spent in each function and allocations
+    performed by each function. Enabling this mode the side effect of disabling garbage-collection.
+    This profiling mode is the recommended one."""
+
+    HEAP_SUMMARY_RETAINED: ProfileMode
+    """Like heap summary, but information about retained memory after module is frozen."""
+
+    HEAP_FLAME_ALLOCATED: ProfileMode
+    """Like heap profile, but writes output comparible with
+    [flamegraph.pl](https://github.com/brendangregg/FlameGraph/blob/master/flamegraph.pl)."""
+
+    HEAP_FLAME_RETAINED: ProfileMode
+    """Like heap flame, but information about retained memory after module is frozen."""
+
+    STATEMENT: ProfileMode
+    """The statement profile mode provides information about time spent in each statement."""
+
+    COVERAGE: ProfileMode
+    """Code coverage."""
+
+    BYTECODE: ProfileMode
+    """The bytecode profile mode provides information about bytecode instructions."""
+
+    BYTECODE_PAIRS: ProfileMode
+    """The bytecode profile mode provides information about bytecode instruction pairs."""
+
+    TIME_FLAME: ProfileMode
+    """Provide output compatible with
+    [flamegraph.pl](https://github.com/brendangregg/FlameGraph/blob/master/flamegraph.pl)."""
+
+    TYPECHECK: ProfileMode
+    """Profile runtime typechecking."""
+
+class ProfileData:
+    @property
+    def profile_mode(self) -> ProfileMode: ...
+    def gen(self) -> str: ...
+    def write(self, path: str) -> None: ...
+    def rows(self) -> list[dict[str, str]] | list[tuple[list[str], int]]: ...
+    @staticmethod
+    def merge(profiles: list[ProfileData]) -> ProfileData: ...
+
+# xingque.lsp
+
+class _LspContext(Protocol):
+    def resolve_load(self, module: str, current_file: str) -> str: ...
+    # optional members:
+    # def get_load_contents(self, path: str) -> str | None: ...
+    # def get_globals(self, current_file: str) -> Globals: ...
+    # dialect: Dialect
+
+class _BinaryReader(Protocol):
+    def readline(self) -> bytes: ...
+    def read(self, n: int) -> bytes: ...
+
+class _BinaryWriter(Protocol):
+    def write(self, data: bytes) -> object: ...
+    def flush(self) -> None: ...
+
+class _LspModule:
+    @staticmethod
+    def serve(
+        stdin: _BinaryReader, stdout: _BinaryWriter, context: _LspContext
+    ) -> None: ...
+
+lsp: _LspModule
+
+# starlark::syntax
+
+class DialectTypes:
+    DISABLE: DialectTypes
+    PARSE_ONLY: DialectTypes
+    ENABLE: DialectTypes
+
+class Dialect:
+    enable_def: bool
+    enable_lambda: bool
+    enable_load: bool
+    enable_keyword_only_arguments: bool
+    enable_types: DialectTypes
+    enable_load_reexport: bool
+    enable_top_level_stmt: bool
+    enable_f_strings: bool
+    def __init__(
+        self,
+        enable_def: bool = False,
+        enable_lambda: bool = False,
+        enable_load: bool = False,
+        enable_keyword_only_arguments: bool = False,
+        enable_types: DialectTypes = DialectTypes.DISABLE,
+        enable_load_reexport: bool = False,
+        enable_top_level_stmt: bool = False,
+        enable_f_strings: bool = False,
+    ) -> None: ...
+    EXTENDED: Dialect
+    STANDARD: Dialect
+
+class AstLoad:
+    @property
+    def span(self) -> FileSpan: ...
+    @property
+    def module_id(self) -> str: ...
+    @property
+    def symbols(self) -> dict[str, str]: ...
+
+class AstModule:
+    @staticmethod
+    def parse_file(path: str, dialect: Dialect = Dialect.STANDARD) -> AstModule: ...
+    @staticmethod
+    def parse(
+        filename: str,
+        content: str,
+        dialect: Dialect = Dialect.STANDARD,
+    ) -> AstModule: ...
+    @property
+    def loads(self) -> list[AstLoad]: ...
+    def file_span(self, x: Span) -> FileSpan: ...
+    @property
+    def stmt_locations(self) -> list[FileSpan]: ...
+    @property
+    def statements(self) -> list[Stmt]: ...
+    def unparse(self, statements: list[Stmt] | None = None) -> str: ...
+    def format(self) -> str: ...
+    def replace_binary_operators(self, replace: dict[str, str]) -> None: ...
+    def documentation(self) -> DocModule: ...
+    def lint(self, globals: Globals | Iterable[str] | None = None) -> list[Lint]: ...
+    def typecheck(
+        self,
+        globals: Globals,
+        loads: dict[str, Interface] | None = None,
+    ) -> tuple[list[StarlarkTypeCheckError], Interface]: ...
+
+def format_source(text: str, dialect: Dialect = Dialect.STANDARD) -> str: ...
+
+# starlark::syntax::ast
+
+class ArgumentKind:
+    POSITIONAL: ArgumentKind
+    NAMED: ArgumentKind
+    ARGS: ArgumentKind
+    KW_ARGS: ArgumentKind
+
+class ParameterKind:
+    NORMAL: ParameterKind
+    WITH_DEFAULT_VALUE: ParameterKind
+    NO_ARGS: ParameterKind
+    ARGS: ParameterKind
+    KW_ARGS: ParameterKind
+
+class AstNode:
+    _fields: tuple[str, ...]
+    @property
+    def span(self) -> Span: ...
+    def children(self) -> list[AstNode]: ...
+
+class NodeVisitor:
+    def __init__(self) -> None: ...
+    @overload
+    def visit(self, node: AstNode) -> object: ...
+    @overload
+    def visit(self, node: list[Stmt]) -> list[object]: ...
+    def generic_visit(self, node: AstNode) -> object: ...
+
+class NodeTransformer(NodeVisitor):
+    def generic_visit(self, node: AstNode) -> AstNode: ...
+
+def unparse(node: AstNode | list[Stmt]) -> str: ...
+
+class Pass(AstNode):
+    def __init__(self, *, span: Span | None = None) -> None: ...
+
+class Break(AstNode):
+    def __init__(self, *, span: Span | None = None) -> None: ...
+
+class Continue(AstNode):
+    def __init__(self, *, span: Span | None = None) -> None: ...
+
+class Return(AstNode):
+    def __init__(
+        self,
+        value: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    value: Expr | None
+
+class Expression(AstNode):
+    def __init__(
+        self,
+        value: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    value: Expr
+
+class Assign(AstNode):
+    def __init__(
+        self,
+        target: AssignTarget | None = None,
+        annotation: Expr | None = None,
+        value: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    target: AssignTarget
+    annotation: Expr | None
+    value: Expr
+
+class AssignModify(AstNode):
+    def __init__(
+        self,
+        target: AssignTarget | None = None,
+        op: str | None = None,
+        value: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    target: AssignTarget
+    op: str
+    value: Expr
+
+class If(AstNode):
+    def __init__(
+        self,
+        test: Expr | None = None,
+        body: list[Stmt] | None = None,
+        orelse: list[Stmt] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    test: Expr
+    body: list[Stmt]
+    orelse: list[Stmt]
+
+class For(AstNode):
+    def __init__(
+        self,
+        target: AssignTarget | None = None,
+        iter: Expr | None = None,
+        body: list[Stmt] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    target: AssignTarget
+    iter: Expr
+    body: list[Stmt]
+
+class Def(AstNode):
+    def __init__(
+        self,
+        name: str | None = None,
+        params: list[Parameter] | None = None,
+        return_type: Expr | None = None,
+        body: list[Stmt] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    name: str
+    params: list[Parameter]
+    return_type: Expr | None
+    body: list[Stmt]
+
+class Load(AstNode):
+    def __init__(
+        self,
+        module: str | None = None,
+        args: list[LoadArg] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    module: str
+    args: list[LoadArg]
+
+class LoadArg(AstNode):
+    def __init__(
+        self,
+        local: str | None = None,
+        their: str | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    local: str
+    their: str
+
+class Tuple(AstNode):
+    def __init__(
+        self,
+        elts: list[Expr] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    elts: list[Expr]
+
+class Dot(AstNode):
+    def __init__(
+        self,
+        value: Expr | None = None,
+        attr: str | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    value: Expr
+    attr: str
+
+class Call(AstNode):
+    def __init__(
+        self,
+        func: Expr | None = None,
+        args: list[Argument] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    func: Expr
+    args: list[Argument]
+
+class Argument(AstNode):
+    def __init__(
+        self,
+        kind: ArgumentKind | None = None,
+        name: str | None = None,
+        value: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    kind: ArgumentKind
+    name: str | None
+    value: Expr
+
+class Index(AstNode):
+    def __init__(
+        self,
+        value: Expr | None = None,
+        index: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    value: Expr
+    index: Expr
+
+class Index2(AstNode):
+    def __init__(
+        self,
+        value: Expr | None = None,
+        index1: Expr | None = None,
+        index2: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    value: Expr
+    index1: Expr
+    index2: Expr
+
+class Slice(AstNode):
+    def __init__(
+        self,
+        value: Expr | None = None,
+        start: Expr | None = None,
+        stop: Expr | None = None,
+        step: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    value: Expr
+    start: Expr | None
+    stop: Expr | None
+    step: Expr | None
+
+class Identifier(AstNode):
+    def __init__(
+        self,
+        name: str | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    name: str
+
+class Lambda(AstNode):
+    def __init__(
+        self,
+        params: list[Parameter] | None = None,
+        body: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    params: list[Parameter]
+    body: Expr
+
+class Parameter(AstNode):
+    def __init__(
+        self,
+        kind: ParameterKind | None = None,
+        name: str | None = None,
+        annotation: Expr | None = None,
+        default: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    kind: ParameterKind
+    name: str | None
+    annotation: Expr | None
+    default: Expr | None
+
+class Literal(AstNode):
+    def __init__(
+        self,
+        value: int | float | str | EllipsisType | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    value: int | float | str | EllipsisType
+
+class Not(AstNode):
+    def __init__(
+        self,
+        operand: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    operand: Expr
+
+class Minus(AstNode):
+    def __init__(
+        self,
+        operand: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    operand: Expr
+
+class Plus(AstNode):
+    def __init__(
+        self,
+        operand: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    operand: Expr
+
+class BitNot(AstNode):
+    def __init__(
+        self,
+        operand: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    operand: Expr
+
+class Op(AstNode):
+    def __init__(
+        self,
+        left: Expr | None = None,
+        op: str | None = None,
+        right: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    left: Expr
+    op: str
+    right: Expr
+
+class IfExpr(AstNode):
+    def __init__(
+        self,
+        test: Expr | None = None,
+        body: Expr | None = None,
+        orelse: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    test: Expr
+    body: Expr
+    orelse: Expr
+
+class List(AstNode):
+    def __init__(
+        self,
+        elts: list[Expr] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    elts: list[Expr]
+
+class Dict(AstNode):
+    def __init__(
+        self,
+        keys: list[Expr] | None = None,
+        values: list[Expr] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    keys: list[Expr]
+    values: list[Expr]
+
+class ListComprehension(AstNode):
+    def __init__(
+        self,
+        elt: Expr | None = None,
+        clauses: list[ForClause | IfClause] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    elt: Expr
+    clauses: list[ForClause | IfClause]
+
+class DictComprehension(AstNode):
+    def __init__(
+        self,
+        key: Expr | None = None,
+        value: Expr | None = None,
+        clauses: list[ForClause | IfClause] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    key: Expr
+    value: Expr
+    clauses: list[ForClause | IfClause]
+
+class ForClause(AstNode):
+    def __init__(
+        self,
+        target: AssignTarget | None = None,
+        iter: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    target: AssignTarget
+    iter: Expr
+
+class IfClause(AstNode):
+    def __init__(
+        self,
+        test: Expr | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    test: Expr
+
+class FString(AstNode):
+    def __init__(
+        self,
+        format: str | None = None,
+        expressions: list[Expr] | None = None,
+        *,
+        span: Span | None = None,
+    ) -> None: ...
+    format: str
+    expressions: list[Expr]
+
+
+
+Stmt = (
+    Pass
+    | Break
+    | Continue
+    | Return
+    | Expression
+    | Assign
+    | AssignModify
+    | If
+    | For
+    | Def
+    | Load
+)
+Expr = (
+    Tuple
+    | Dot
+    | Call
+    | Index
+    | Index2
+    | Slice
+    | Identifier
+    | Lambda
+    | Literal
+    | Not
+    | Minus
+    | Plus
+    | BitNot
+    | Op
+    | IfExpr
+    | List
+    | Dict
+    | ListComprehension
+    | DictComprehension
+    | FString
+)
+AssignTarget = Tuple | Index | Dot | Identifier
+
+# starlark::typing
+
+class Interface:
+    def __init__(self) -> None: ...
+    def __len__(self) -> int: ...
+    def __contains__(self, name: str) -> bool: ...
+    def names(self) -> list[str]: ...
+    def get(self, name: str) -> str | None: ...
+    @property
+    def types(self) -> dict[str, str]: ...
+
+_TClass = TypeVar("_TClass", bound=type)
+_T = TypeVar("_T")
+
+def register_type(
+    cls: _TClass,
+    *,
+    name: str | None = None,
+    attrs: dict[str, object] | None = None,
+) -> _TClass: ...
+def register_converter(
+    cls: type[_T], to_starlark: Callable[[_T], object] | None
+) -> None: ...
+
+# starlark::values
+
+class FrozenValue:
+    pass
+
+class HeapSummary:
+    def summary(self) -> dict[str, tuple[int, int]]: ...
+    @property
+    def total_allocated_bytes(self) -> int: ...
+
+class Heap:
+    def __init__(self) -> None: ...
+    @property
+    def allocated_bytes(self) -> int: ...
+    @property
+    def peak_allocated_bytes(self) -> int: ...
+    @property
+    def available_bytes(self) -> int: ...
+    def allocated_summary(self) -> HeapSummary: ...
+
+class Value:
+    pass
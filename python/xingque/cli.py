@@ -0,0 +1,250 @@
+"""The ``xingque`` command-line tool, also available as ``python -m xingque``.
+
+Subcommands:
+
+* ``run`` evaluates a Starlark file and prints its result;
+* ``check`` parses, typechecks and lints Starlark files;
+* ``fmt`` formats Starlark files in place;
+* ``repl``, the default, starts an interactive console.
+"""
+
+import argparse
+import os
+import sys
+from typing import List, Optional, Sequence
+
+from ._xingque import (
+    STARLARK_RUST_VERSION,
+    VERSION,
+    AstModule,
+    Diagnostic,
+    Dialect,
+    EvalSeverity,
+    Evaluator,
+    FilesystemLoader,
+    Globals,
+    LibraryExtension,
+    Lint,
+    Module,
+    StarlarkError,
+    format_source,
+)
+from .repl import Repl
+
+DIALECTS = {
+    "standard": Dialect.STANDARD,
+    "extended": Dialect.EXTENDED,
+}
+
+SEVERITIES = {
+    EvalSeverity.ERROR: "error",
+    EvalSeverity.WARNING: "warning",
+    EvalSeverity.ADVICE: "advice",
+}
+
+
+def library_extensions() -> List[str]:
+    """Names of all the `LibraryExtension`s, in lower case."""
+    return sorted(
+        name.lower()
+        for name in dir(LibraryExtension)
+        if name.isupper()
+        and isinstance(getattr(LibraryExtension, name), LibraryExtension)
+    )
+
+
+def parse_extensions(spec: str) -> List[LibraryExtension]:
+    """Parses a comma-separated list of `LibraryExtension` names.
+
+    The names are case-insensitive, and the special name ``all`` stands for
+    every extension.
+    """
+    names: List[str] = []
+    for name in spec.split(","):
+        name = name.strip().lower()
+        if not name:
+            continue
+        if name == "all":
+            names.extend(library_extensions())
+        elif name in library_extensions():
+            names.append(name)
+        else:
+            raise argparse.ArgumentTypeError(f"unknown library extension: {name}")
+    return [getattr(LibraryExtension, name.upper()) for name in names]
+
+
+def format_lint(lint: Lint) -> str:
+    """Formats `lint` as a single ``file:line:column: severity`` line."""
+    location = lint.location.resolve()
+    begin = location.span.begin
+    return (
+        f"{location.file}:{begin.line + 1}:{begin.column + 1}: "
+        f"{SEVERITIES[lint.severity]}: {lint.problem} [{lint.short_name}]"
+    )
+
+
+def add_dialect_argument(parser: argparse.ArgumentParser) -> None:
+    """Adds the ``--dialect`` option to `parser`."""
+    parser.add_argument(
+        "--dialect",
+        choices=sorted(DIALECTS),
+        default="extended",
+        help="the Starlark dialect to accept (default: %(default)s)",
+    )
+
+
+def add_ext_argument(parser: argparse.ArgumentParser) -> None:
+    """Adds the ``--ext`` option to `parser`."""
+    parser.add_argument(
+        "--ext",
+        metavar="NAME[,NAME...]",
+        type=parse_extensions,
+        default="all",
+        help=(
+            "comma-separated library extensions to add to the standard globals, "
+            f"out of {', '.join(library_extensions())} or all (default: %(default)s)"
+        ),
+    )
+
+
+def add_common_arguments(parser: argparse.ArgumentParser) -> None:
+    """Adds the ``--dialect`` and ``--ext`` options to `parser`."""
+    add_dialect_argument(parser)
+    add_ext_argument(parser)
+
+
+def report(e: BaseException) -> None:
+    """Prints `e` to stderr, with a source snippet if it comes from Starlark."""
+    diagnostic = Diagnostic.from_error(e)
+    print(diagnostic.render(color=sys.stderr.isatty()).rstrip(), file=sys.stderr)
+
+
+def cmd_run(args: argparse.Namespace) -> int:
+    dialect = DIALECTS[args.dialect]
+    globals = Globals.extended_by(args.ext)
+    root = args.root if args.root is not None else os.path.dirname(args.file)
+    evaluator = Evaluator(Module())
+    evaluator.set_loader(FilesystemLoader(root, globals, dialect))
+    try:
+        ast = AstModule.parse_file(args.file, dialect)
+        result = evaluator.eval_module(ast, globals)
+    except (StarlarkError, OSError) as e:
+        report(e)
+        return 1
+    if result is not None:
+        print(repr(result))
+    return 0
+
+
+def cmd_check(args: argparse.Namespace) -> int:
+    dialect = DIALECTS[args.dialect]
+    globals = Globals.extended_by(args.ext)
+    failed = False
+    for file in args.files:
+        try:
+            ast = AstModule.parse_file(file, dialect)
+        except (StarlarkError, OSError) as e:
+            report(e)
+            failed = True
+            continue
+        lints = [x for x in ast.lint(globals) if x.severity in SEVERITIES]
+        lints.sort(key=lambda x: int(x.location.span.begin))
+        for lint in lints:
+            print(format_lint(lint))
+            failed = failed or lint.severity != EvalSeverity.ADVICE
+        errors, _ = ast.typecheck(globals)
+        for error in errors:
+            diagnostic = Diagnostic.from_error(error)
+            print(diagnostic.render(color=sys.stdout.isatty()).rstrip())
+            failed = True
+    return 1 if failed else 0
+
+
+def cmd_fmt(args: argparse.Namespace) -> int:
+    dialect = DIALECTS[args.dialect]
+    failed = False
+    for file in args.files:
+        try:
+            if file == "-":
+                sys.stdout.write(format_source(sys.stdin.read(), dialect))
+                continue
+            with open(file, encoding="utf-8") as f:
+                text = f.read()
+            formatted = format_source(text, dialect)
+        except (StarlarkError, OSError) as e:
+            report(e)
+            failed = True
+            continue
+        if formatted == text:
+            continue
+        if args.check:
+            print(f"would reformat {file}")
+            failed = True
+        else:
+            with open(file, "w", encoding="utf-8") as f:
+                f.write(formatted)
+            print(f"reformatted {file}")
+    return 1 if failed else 0
+
+
+def cmd_repl(args: argparse.Namespace) -> int:
+    repl = Repl(Globals.extended_by(args.ext), DIALECTS[args.dialect])
+    repl.interact(
+        f"xingque {VERSION} (starlark-rust {STARLARK_RUST_VERSION})\n"
+        "Enter an empty line to finish a multi-line statement, EOF to exit."
+    )
+    return 0
+
+
+def make_parser() -> argparse.ArgumentParser:
+    parser = argparse.ArgumentParser(
+        prog="xingque",
+        description="Run, check and format Starlark code.",
+    )
+    parser.add_argument(
+        "--version",
+        action="version",
+        version=f"xingque {VERSION} (starlark-rust {STARLARK_RUST_VERSION})",
+    )
+    subparsers = parser.add_subparsers(dest="command", metavar="COMMAND")
+
+    run = subparsers.add_parser("run", help="evaluate a Starlark file")
+    add_common_arguments(run)
+    run.add_argument(
+        "--root",
+        help="the directory to load modules from (default: that of FILE)",
+    )
+    run.add_argument("file", metavar="FILE")
+    run.set_defaults(func=cmd_run)
+
+    check = subparsers.add_parser("check", help="typecheck and lint Starlark files")
+    add_common_arguments(check)
+    check.add_argument("files", metavar="FILE", nargs="+")
+    check.set_defaults(func=cmd_check)
+
+    fmt = subparsers.add_parser("fmt", help="format Starlark files in place")
+    add_dialect_argument(fmt)
+    fmt.add_argument(
+        "--check",
+        action="store_true",
+        help="only report the files that would be reformatted",
+    )
+    fmt.add_argument(
+        "files", metavar="FILE", nargs="+", help="a file to format, or - for stdin"
+    )
+    fmt.set_defaults(func=cmd_fmt)
+
+    repl = subparsers.add_parser("repl", help="start an interactive console")
+    add_common_arguments(repl)
+    repl.set_defaults(func=cmd_repl)
+
+    return parser
+
+
+def main(argv: Optional[Sequence[str]] = None) -> int:
+    """Entry point of the ``xingque`` command."""
+    parser = make_parser()
+    args = parser.parse_args(argv)
+    if args.command is None:
+        args = parser.parse_args(["repl"])
+    return args.func(args)
@@ -0,0 +1,203 @@
+"""An interactive Starlark console, started by ``python -m xingque``.
+
+The console keeps a single `Module` alive across inputs, so definitions made
+in one input are visible to the later ones. Results of expressions other than
+``None`` are echoed back, and compound statements such as ``def`` may span
+multiple lines, ending with an empty line.
+"""
+
+import sys
+from typing import List, Optional, TextIO
+
+from ._xingque import (
+    AstModule,
+    Dialect,
+    Evaluator,
+    Globals,
+    Module,
+    StarlarkError,
+    StarlarkParseError,
+)
+
+# Reserved words of the Starlark language, offered as completions too.
+KEYWORDS = [
+    "and",
+    "break",
+    "continue",
+    "def",
+    "elif",
+    "else",
+    "for",
+    "if",
+    "in",
+    "lambda",
+    "load",
+    "not",
+    "or",
+    "pass",
+    "return",
+]
+
+
+def is_unfinished(source: str) -> bool:
+    """Whether `source` ends inside brackets, a triple-quoted string or after a
+    line continuation, so that more lines are needed to complete it.
+    """
+    depth = 0
+    i = 0
+    while i < len(source):
+        c = source[i]
+        if c == "#":
+            end = source.find("\n", i)
+            i = len(source) if end < 0 else end
+            continue
+        if c in "'\"":
+            quote = source[i : i + 3] if source[i : i + 3] in ('"""', "'''") else c
+            i += len(quote)
+            while i < len(source) and not source.startswith(quote, i):
+                if source[i] == "\\":
+                    i += 1
+                elif source[i] == "\n" and len(quote) == 1:
+                    break
+                i += 1
+            if i >= len(source):
+                return len(quote) == 3
+            i += len(quote)
+            continue
+        if c in "([{":
+            depth += 1
+        elif c in ")]}":
+            depth -= 1
+        i += 1
+    return depth > 0 or source.rstrip("\n").endswith("\\")
+
+
+def strip_comment(line: str) -> str:
+    """`line` without its trailing comment, if any."""
+    quote = ""
+    i = 0
+    while i < len(line):
+        c = line[i]
+        if quote:
+            if c == "\\":
+                i += 1
+            elif line.startswith(quote, i):
+                i += len(quote) - 1
+                quote = ""
+        elif c == "#":
+            return line[:i]
+        elif c in "'\"":
+            quote = line[i : i + 3] if line[i : i + 3] in ('"""', "'''") else c
+            i += len(quote) - 1
+        i += 1
+    return line
+
+
+class Repl:
+    """An interactive Starlark session.
+
+    Inputs are given line by line to `push`, which evaluates them once they
+    make up a complete statement, writing results to `stdout` and errors to
+    `stderr`.
+    """
+
+    def __init__(
+        self,
+        globals: Optional[Globals] = None,
+        dialect: Dialect = Dialect.EXTENDED,
+        *,
+        stdout: Optional[TextIO] = None,
+        stderr: Optional[TextIO] = None,
+    ) -> None:
+        self.globals = globals if globals is not None else Globals.standard()
+        self.dialect = dialect
+        self.module = Module()
+        self.evaluator = Evaluator(self.module)
+        self.stdout = stdout if stdout is not None else sys.stdout
+        self.stderr = stderr if stderr is not None else sys.stderr
+        self._buffer: List[str] = []
+
+    def push(self, line: str) -> bool:
+        """Adds a line of input, returning whether more lines are needed."""
+        self._buffer.append(line)
+        source = "\n".join(self._buffer)
+        in_block = strip_comment(self._buffer[0]).rstrip().endswith(":")
+        if is_unfinished(source) or (in_block and line.strip()):
+            return True
+        try:
+            ast = AstModule.parse("<repl>", source, self.dialect)
+        except StarlarkParseError as e:
+            if not in_block and line.strip() and "unexpected end of file" in str(e):
+                return True
+            self.reset()
+            self._report(e)
+            return False
+        self.reset()
+        # eval_statements would fit better, but panics outside a function
+        # call in starlark 0.12; eval_module keeps the same Module anyway
+        try:
+            result = self.evaluator.eval_module(ast, self.globals)
+        except StarlarkError as e:
+            self._report(e)
+            return False
+        if result is not None:
+            print(repr(result), file=self.stdout)
+        return False
+
+    def reset(self) -> None:
+        """Discards any incomplete input."""
+        self._buffer = []
+
+    def names(self) -> List[str]:
+        """Names that can be used in the session."""
+        names = set(KEYWORDS)
+        names.update(self.module.names())
+        names.update(self.globals.names())
+        return sorted(names)
+
+    def complete(self, text: str) -> List[str]:
+        """Names completing the prefix `text`."""
+        return [name for name in self.names() if name.startswith(text)]
+
+    def interact(self, banner: Optional[str] = None) -> None:
+        """Reads and evaluates input until end of file."""
+        self._install_completer()
+        if banner is not None:
+            print(banner, file=self.stderr)
+        more = False
+        while True:
+            try:
+                line = input("... " if more else ">>> ")
+            except EOFError:
+                print(file=self.stderr)
+                return
+            except KeyboardInterrupt:
+                print("\nKeyboardInterrupt", file=self.stderr)
+                self.reset()
+                more = False
+                continue
+            more = self.push(line)
+
+    def _report(self, e: Exception) -> None:
+        print(str(e).rstrip(), file=self.stderr)
+
+    def _install_completer(self) -> None:
+        try:
+            import readline
+        except ImportError:
+            return
+
+        matches: List[str] = []
+
+        def completer(text: str, state: int) -> Optional[str]:
+            if state == 0:
+                matches[:] = self.complete(text)
+            return matches[state] if state < len(matches) else None
+
+        readline.set_completer(completer)
+        readline.set_completer_delims(" \t\n()[]{},:;=+-*/%<>!&|^~.'\"")
+        if "libedit" in (readline.__doc__ or ""):
+            readline.parse_and_bind("bind ^I rl_complete")
+        else:
+            readline.parse_and_bind("tab: complete")
+
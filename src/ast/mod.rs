@@ -0,0 +1,518 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyString, PyTuple};
+use starlark::codemap::Span;
+use starlark_syntax::lexer::TokenInt;
+use starlark_syntax::syntax::ast::{
+    ArgumentP, AssignTargetP, AstArgument, AstAssignTarget, AstExpr, AstLiteral, AstParameter,
+    AstStmt, Clause, ClauseP, ExprP, ForClause, ParameterP, StmtP,
+};
+
+use crate::codemap::PySpan;
+
+mod unparser;
+pub(crate) use unparser::{unparse, Unparser};
+mod visitor;
+pub(crate) use visitor::{PyNodeTransformer, PyNodeVisitor};
+
+// NOTE: keep this in sync with starlark_syntax::syntax::ast::ArgumentP
+#[pyclass(
+    module = "xingque",
+    name = "ArgumentKind",
+    rename_all = "SCREAMING_SNAKE_CASE",
+    frozen,
+    eq,
+    hash
+)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum PyArgumentKind {
+    Positional,
+    Named,
+    Args,
+    KwArgs,
+}
+
+// NOTE: keep this in sync with starlark_syntax::syntax::ast::ParameterP
+#[pyclass(
+    module = "xingque",
+    name = "ParameterKind",
+    rename_all = "SCREAMING_SNAKE_CASE",
+    frozen,
+    eq,
+    hash
+)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum PyParameterKind {
+    Normal,
+    WithDefaultValue,
+    NoArgs,
+    Args,
+    KwArgs,
+}
+
+/// Base class of all nodes in the Python view of a Starlark AST.
+///
+/// Nodes created from Python have an empty span at the start of the file.
+#[pyclass(module = "xingque", name = "AstNode", subclass)]
+pub(crate) struct PyAstNode {
+    span: Span,
+}
+
+#[pymethods]
+impl PyAstNode {
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        let class_name = slf.get_type().qualname()?;
+        let mut fields = Vec::new();
+        for name in slf.getattr("_fields")?.iter()? {
+            let name = name?;
+            let value = slf.getattr(name.downcast::<PyString>()?)?;
+            fields.push(format!("{}={}", name, value.repr()?));
+        }
+        Ok(format!("{}({})", class_name, fields.join(", ")))
+    }
+
+    #[getter]
+    fn span(&self) -> PySpan {
+        self.span.into()
+    }
+
+    /// The direct child nodes of this node, in source order.
+    fn children(slf: &Bound<'_, Self>) -> PyResult<Vec<PyObject>> {
+        let mut result = Vec::new();
+        let mut push = |x: Bound<'_, PyAny>| {
+            if x.is_instance_of::<PyAstNode>() {
+                result.push(x.unbind());
+            }
+        };
+        for name in slf.getattr("_fields")?.iter()? {
+            let value = slf.getattr(name?.downcast::<PyString>()?)?;
+            if value.is_instance_of::<PyList>() {
+                for x in value.iter()? {
+                    push(x?);
+                }
+            } else {
+                push(value);
+            }
+        }
+        Ok(result)
+    }
+}
+
+macro_rules! ast_node_class {
+    // the constructor, only taking `py` if there are fields to default
+    (@new $rust_name:ident, []) => {
+        #[pymethods]
+        impl $rust_name {
+            #[new]
+            #[pyo3(signature = (*, span = None))]
+            fn py_new(span: Option<PyRef<'_, PySpan>>) -> PyClassInitializer<Self> {
+                let span = span.map_or_else(Span::default, |x| x.0);
+                PyClassInitializer::from(PyAstNode { span }).add_subclass(Self {})
+            }
+        }
+    };
+    (@new $rust_name:ident, [$($field:ident),+]) => {
+        #[pymethods]
+        impl $rust_name {
+            #[new]
+            #[pyo3(signature = ($($field = None,)* *, span = None))]
+            fn py_new(
+                py: Python<'_>,
+                $($field: Option<PyObject>,)*
+                span: Option<PyRef<'_, PySpan>>,
+            ) -> PyClassInitializer<Self> {
+                let span = span.map_or_else(Span::default, |x| x.0);
+                PyClassInitializer::from(PyAstNode { span })
+                    .add_subclass(Self { $($field: $field.unwrap_or_else(|| py.None()),)* })
+            }
+        }
+    };
+    ($(#[doc = $doc:literal])* $rust_name:ident, $name:literal, [$($field:ident),*]) => {
+        $(#[doc = $doc])*
+        #[pyclass(module = "xingque", name = $name, extends = PyAstNode)]
+        pub(crate) struct $rust_name {
+            $(
+                #[pyo3(get, set)]
+                $field: PyObject,
+            )*
+        }
+
+        impl $rust_name {
+            fn new_node(py: Python<'_>, span: Span, $($field: PyObject),*) -> PyResult<PyObject> {
+                let init = PyClassInitializer::from(PyAstNode { span })
+                    .add_subclass(Self { $($field,)* });
+                Ok(Py::new(py, init)?.into_any())
+            }
+        }
+
+        ast_node_class!(@new $rust_name, [$($field),*]);
+
+        #[pymethods]
+        impl $rust_name {
+            /// Names of the fields of this node type.
+            #[classattr]
+            fn _fields(py: Python<'_>) -> Py<PyTuple> {
+                let fields: &[&str] = &[$(stringify!($field)),*];
+                PyTuple::new_bound(py, fields).unbind()
+            }
+        }
+    };
+}
+
+// statements
+ast_node_class!(PyPass, "Pass", []);
+ast_node_class!(PyBreak, "Break", []);
+ast_node_class!(PyContinue, "Continue", []);
+ast_node_class!(PyReturn, "Return", [value]);
+ast_node_class!(PyExpression, "Expression", [value]);
+ast_node_class!(PyAssign, "Assign", [target, annotation, value]);
+ast_node_class!(PyAssignModify, "AssignModify", [target, op, value]);
+ast_node_class!(PyIf, "If", [test, body, orelse]);
+ast_node_class!(PyFor, "For", [target, iter, body]);
+ast_node_class!(PyDef, "Def", [name, params, return_type, body]);
+ast_node_class!(PyLoad, "Load", [module, args]);
+ast_node_class!(
+    /// A `local="their"` pair in a `load` statement.
+    PyLoadArg,
+    "LoadArg",
+    [local, their]
+);
+
+// expressions
+ast_node_class!(PyTupleExpr, "Tuple", [elts]);
+ast_node_class!(PyDot, "Dot", [value, attr]);
+ast_node_class!(PyCall, "Call", [func, args]);
+ast_node_class!(PyArgument, "Argument", [kind, name, value]);
+ast_node_class!(PyIndex, "Index", [value, index]);
+ast_node_class!(PyIndex2, "Index2", [value, index1, index2]);
+ast_node_class!(PySlice, "Slice", [value, start, stop, step]);
+ast_node_class!(PyIdentifier, "Identifier", [name]);
+ast_node_class!(PyLambda, "Lambda", [params, body]);
+ast_node_class!(PyParameter, "Parameter", [kind, name, annotation, default]);
+ast_node_class!(PyLiteral, "Literal", [value]);
+ast_node_class!(PyNot, "Not", [operand]);
+ast_node_class!(PyMinus, "Minus", [operand]);
+ast_node_class!(PyPlus, "Plus", [operand]);
+ast_node_class!(PyBitNot, "BitNot", [operand]);
+ast_node_class!(PyOp, "Op", [left, op, right]);
+ast_node_class!(PyIfExpr, "IfExpr", [test, body, orelse]);
+ast_node_class!(PyListExpr, "List", [elts]);
+ast_node_class!(PyDictExpr, "Dict", [keys, values]);
+ast_node_class!(PyListComprehension, "ListComprehension", [elt, clauses]);
+ast_node_class!(
+    PyDictComprehension,
+    "DictComprehension",
+    [key, value, clauses]
+);
+ast_node_class!(PyForClause, "ForClause", [target, iter]);
+ast_node_class!(PyIfClause, "IfClause", [test]);
+ast_node_class!(PyFString, "FString", [format, expressions]);
+
+/// Converts a statement into a list of statement nodes, flattening nested
+/// statement lists.
+pub(crate) fn stmts_to_py(py: Python<'_>, stmt: &AstStmt) -> PyResult<PyObject> {
+    fn f(py: Python<'_>, stmt: &AstStmt, result: &mut Vec<PyObject>) -> PyResult<()> {
+        match &stmt.node {
+            StmtP::Statements(xs) => {
+                for x in xs {
+                    f(py, x, result)?;
+                }
+            }
+            _ => result.push(stmt_to_py(py, stmt)?),
+        }
+        Ok(())
+    }
+
+    let mut result = Vec::new();
+    f(py, stmt, &mut result)?;
+    Ok(PyList::new_bound(py, result).into_any().unbind())
+}
+
+fn stmt_to_py(py: Python<'_>, stmt: &AstStmt) -> PyResult<PyObject> {
+    let span = stmt.span;
+    match &stmt.node {
+        StmtP::Break => PyBreak::new_node(py, span),
+        StmtP::Continue => PyContinue::new_node(py, span),
+        StmtP::Pass => PyPass::new_node(py, span),
+        StmtP::Return(x) => PyReturn::new_node(py, span, opt_expr_to_py(py, x.as_ref())?),
+        StmtP::Expression(x) => PyExpression::new_node(py, span, expr_to_py(py, x)?),
+        StmtP::Assign(x) => PyAssign::new_node(
+            py,
+            span,
+            target_to_py(py, &x.lhs)?,
+            opt_expr_to_py(py, x.ty.as_ref().map(|x| &x.expr))?,
+            expr_to_py(py, &x.rhs)?,
+        ),
+        StmtP::AssignModify(lhs, op, rhs) => PyAssignModify::new_node(
+            py,
+            span,
+            target_to_py(py, lhs)?,
+            op.to_string().trim().into_py(py),
+            expr_to_py(py, rhs)?,
+        ),
+        StmtP::Statements(_) => unreachable!("flattened by stmts_to_py"),
+        StmtP::If(test, body) => PyIf::new_node(
+            py,
+            span,
+            expr_to_py(py, test)?,
+            stmts_to_py(py, body)?,
+            PyList::empty_bound(py).into_any().unbind(),
+        ),
+        StmtP::IfElse(test, branches) => PyIf::new_node(
+            py,
+            span,
+            expr_to_py(py, test)?,
+            stmts_to_py(py, &branches.0)?,
+            stmts_to_py(py, &branches.1)?,
+        ),
+        StmtP::For(x) => PyFor::new_node(
+            py,
+            span,
+            target_to_py(py, &x.var)?,
+            expr_to_py(py, &x.over)?,
+            stmts_to_py(py, &x.body)?,
+        ),
+        StmtP::Def(x) => PyDef::new_node(
+            py,
+            span,
+            x.name.ident.clone().into_py(py),
+            params_to_py(py, &x.params)?,
+            opt_expr_to_py(py, x.return_type.as_ref().map(|x| &x.expr))?,
+            stmts_to_py(py, &x.body)?,
+        ),
+        StmtP::Load(x) => {
+            let args = x
+                .args
+                .iter()
+                .map(|arg| {
+                    PyLoadArg::new_node(
+                        py,
+                        arg.span(),
+                        arg.local.ident.clone().into_py(py),
+                        arg.their.node.clone().into_py(py),
+                    )
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            PyLoad::new_node(
+                py,
+                span,
+                x.module.node.clone().into_py(py),
+                PyList::new_bound(py, args).into_any().unbind(),
+            )
+        }
+    }
+}
+
+fn opt_expr_to_py(py: Python<'_>, expr: Option<&AstExpr>) -> PyResult<PyObject> {
+    match expr {
+        Some(x) => expr_to_py(py, x),
+        None => Ok(py.None()),
+    }
+}
+
+fn exprs_to_py<'a>(
+    py: Python<'_>,
+    exprs: impl IntoIterator<Item = &'a AstExpr>,
+) -> PyResult<PyObject> {
+    let result = exprs
+        .into_iter()
+        .map(|x| expr_to_py(py, x))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(PyList::new_bound(py, result).into_any().unbind())
+}
+
+fn expr_to_py(py: Python<'_>, expr: &AstExpr) -> PyResult<PyObject> {
+    let span = expr.span;
+    match &expr.node {
+        ExprP::Tuple(xs) => PyTupleExpr::new_node(py, span, exprs_to_py(py, xs)?),
+        ExprP::Dot(value, attr) => PyDot::new_node(
+            py,
+            span,
+            expr_to_py(py, value)?,
+            attr.node.clone().into_py(py),
+        ),
+        ExprP::Call(func, args) => {
+            let args = args
+                .iter()
+                .map(|x| argument_to_py(py, x))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyCall::new_node(
+                py,
+                span,
+                expr_to_py(py, func)?,
+                PyList::new_bound(py, args).into_any().unbind(),
+            )
+        }
+        ExprP::Index(x) => {
+            PyIndex::new_node(py, span, expr_to_py(py, &x.0)?, expr_to_py(py, &x.1)?)
+        }
+        ExprP::Index2(x) => PyIndex2::new_node(
+            py,
+            span,
+            expr_to_py(py, &x.0)?,
+            expr_to_py(py, &x.1)?,
+            expr_to_py(py, &x.2)?,
+        ),
+        ExprP::Slice(value, start, stop, step) => PySlice::new_node(
+            py,
+            span,
+            expr_to_py(py, value)?,
+            opt_expr_to_py(py, start.as_deref())?,
+            opt_expr_to_py(py, stop.as_deref())?,
+            opt_expr_to_py(py, step.as_deref())?,
+        ),
+        ExprP::Identifier(x) => PyIdentifier::new_node(py, span, x.node.ident.clone().into_py(py)),
+        ExprP::Lambda(x) => PyLambda::new_node(
+            py,
+            span,
+            params_to_py(py, &x.params)?,
+            expr_to_py(py, &x.body)?,
+        ),
+        ExprP::Literal(x) => {
+            let value = match x {
+                AstLiteral::Int(x) => match &x.node {
+                    TokenInt::I32(x) => x.into_py(py),
+                    TokenInt::BigInt(x) => x.clone().into_py(py),
+                },
+                AstLiteral::Float(x) => x.node.into_py(py),
+                AstLiteral::String(x) => x.node.clone().into_py(py),
+                AstLiteral::Ellipsis => py.Ellipsis(),
+            };
+            PyLiteral::new_node(py, span, value)
+        }
+        ExprP::Not(x) => PyNot::new_node(py, span, expr_to_py(py, x)?),
+        ExprP::Minus(x) => PyMinus::new_node(py, span, expr_to_py(py, x)?),
+        ExprP::Plus(x) => PyPlus::new_node(py, span, expr_to_py(py, x)?),
+        ExprP::BitNot(x) => PyBitNot::new_node(py, span, expr_to_py(py, x)?),
+        ExprP::Op(lhs, op, rhs) => PyOp::new_node(
+            py,
+            span,
+            expr_to_py(py, lhs)?,
+            op.to_string().trim().into_py(py),
+            expr_to_py(py, rhs)?,
+        ),
+        // Order: condition, v1, v2 <=> v1 if condition else v2
+        ExprP::If(x) => PyIfExpr::new_node(
+            py,
+            span,
+            expr_to_py(py, &x.0)?,
+            expr_to_py(py, &x.1)?,
+            expr_to_py(py, &x.2)?,
+        ),
+        ExprP::List(xs) => PyListExpr::new_node(py, span, exprs_to_py(py, xs)?),
+        ExprP::Dict(xs) => PyDictExpr::new_node(
+            py,
+            span,
+            exprs_to_py(py, xs.iter().map(|x| &x.0))?,
+            exprs_to_py(py, xs.iter().map(|x| &x.1))?,
+        ),
+        ExprP::ListComprehension(elt, first, rest) => PyListComprehension::new_node(
+            py,
+            span,
+            expr_to_py(py, elt)?,
+            clauses_to_py(py, first, rest)?,
+        ),
+        ExprP::DictComprehension(kv, first, rest) => PyDictComprehension::new_node(
+            py,
+            span,
+            expr_to_py(py, &kv.0)?,
+            expr_to_py(py, &kv.1)?,
+            clauses_to_py(py, first, rest)?,
+        ),
+        ExprP::FString(x) => PyFString::new_node(
+            py,
+            span,
+            x.node.format.node.clone().into_py(py),
+            exprs_to_py(py, &x.node.expressions)?,
+        ),
+    }
+}
+
+fn target_to_py(py: Python<'_>, target: &AstAssignTarget) -> PyResult<PyObject> {
+    let span = target.span;
+    match &target.node {
+        AssignTargetP::Tuple(xs) => {
+            let elts = xs
+                .iter()
+                .map(|x| target_to_py(py, x))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyTupleExpr::new_node(py, span, PyList::new_bound(py, elts).into_any().unbind())
+        }
+        AssignTargetP::Index(x) => {
+            PyIndex::new_node(py, span, expr_to_py(py, &x.0)?, expr_to_py(py, &x.1)?)
+        }
+        AssignTargetP::Dot(value, attr) => PyDot::new_node(
+            py,
+            span,
+            expr_to_py(py, value)?,
+            attr.node.clone().into_py(py),
+        ),
+        AssignTargetP::Identifier(x) => {
+            PyIdentifier::new_node(py, span, x.node.ident.clone().into_py(py))
+        }
+    }
+}
+
+fn argument_to_py(py: Python<'_>, arg: &AstArgument) -> PyResult<PyObject> {
+    let (kind, name, value) = match &arg.node {
+        ArgumentP::Positional(x) => (PyArgumentKind::Positional, None, x),
+        ArgumentP::Named(name, x) => (PyArgumentKind::Named, Some(name.node.clone()), x),
+        ArgumentP::Args(x) => (PyArgumentKind::Args, None, x),
+        ArgumentP::KwArgs(x) => (PyArgumentKind::KwArgs, None, x),
+    };
+    PyArgument::new_node(
+        py,
+        arg.span,
+        kind.into_py(py),
+        name.into_py(py),
+        expr_to_py(py, value)?,
+    )
+}
+
+fn params_to_py(py: Python<'_>, params: &[AstParameter]) -> PyResult<PyObject> {
+    let result = params
+        .iter()
+        .map(|x| param_to_py(py, x))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(PyList::new_bound(py, result).into_any().unbind())
+}
+
+fn param_to_py(py: Python<'_>, param: &AstParameter) -> PyResult<PyObject> {
+    let (kind, ty, default) = match &param.node {
+        ParameterP::Normal(_, ty) => (PyParameterKind::Normal, ty, None),
+        ParameterP::WithDefaultValue(_, ty, default) => {
+            (PyParameterKind::WithDefaultValue, ty, Some(&**default))
+        }
+        ParameterP::NoArgs => (PyParameterKind::NoArgs, &None, None),
+        ParameterP::Args(_, ty) => (PyParameterKind::Args, ty, None),
+        ParameterP::KwArgs(_, ty) => (PyParameterKind::KwArgs, ty, None),
+    };
+    PyParameter::new_node(
+        py,
+        param.span,
+        kind.into_py(py),
+        param.node.ident().map(|x| x.node.ident.clone()).into_py(py),
+        opt_expr_to_py(py, ty.as_ref().map(|x| &x.expr))?,
+        opt_expr_to_py(py, default)?,
+    )
+}
+
+fn clauses_to_py(py: Python<'_>, first: &ForClause, rest: &[Clause]) -> PyResult<PyObject> {
+    let for_clause = |x: &ForClause| {
+        // clauses have no span of their own
+        let span = x.var.span.merge(x.over.span);
+        PyForClause::new_node(
+            py,
+            span,
+            target_to_py(py, &x.var)?,
+            expr_to_py(py, &x.over)?,
+        )
+    };
+    let mut result = vec![for_clause(first)?];
+    for x in rest {
+        result.push(match x {
+            ClauseP::For(x) => for_clause(x)?,
+            ClauseP::If(x) => PyIfClause::new_node(py, x.span, expr_to_py(py, x)?)?,
+        });
+    }
+    Ok(PyList::new_bound(py, result).into_any().unbind())
+}
@@ -0,0 +1,1221 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyFloat, PyLong, PyString};
+use starlark::codemap::{CodeMap, Pos, Span};
+use starlark::syntax::Dialect;
+use starlark_syntax::lexer::{Lexer, Token, TokenInt};
+
+use super::*;
+
+/// Lines longer than this are wrapped where possible.
+const MAX_LINE_LENGTH: usize = 79;
+
+// Operator precedence, from loosest to tightest binding
+const PREC_LAMBDA: u8 = 0;
+const PREC_IF: u8 = 1;
+const PREC_OR: u8 = 2;
+const PREC_AND: u8 = 3;
+const PREC_NOT: u8 = 4;
+const PREC_COMPARE: u8 = 5;
+const PREC_BIT_OR: u8 = 6;
+const PREC_BIT_XOR: u8 = 7;
+const PREC_BIT_AND: u8 = 8;
+const PREC_SHIFT: u8 = 9;
+const PREC_ADD: u8 = 10;
+const PREC_MUL: u8 = 11;
+const PREC_UNARY: u8 = 12;
+const PREC_POSTFIX: u8 = 13;
+
+fn binary_op_prec(op: &str) -> PyResult<u8> {
+    Ok(match op {
+        "or" => PREC_OR,
+        "and" => PREC_AND,
+        "==" | "!=" | "<" | ">" | "<=" | ">=" | "in" | "not in" => PREC_COMPARE,
+        "|" => PREC_BIT_OR,
+        "^" => PREC_BIT_XOR,
+        "&" => PREC_BIT_AND,
+        "<<" | ">>" => PREC_SHIFT,
+        "+" | "-" => PREC_ADD,
+        "*" | "/" | "//" | "%" => PREC_MUL,
+        _ => {
+            return Err(PyTypeError::new_err(format!(
+                "unknown binary operator '{}'",
+                op
+            )))
+        }
+    })
+}
+
+/// Renders a string as a double-quoted Starlark string literal.
+pub(crate) fn quote_str(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() && c.is_ascii() => result.push_str(&format!("\\x{:02x}", c as u32)),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn is_stmt(node: &Bound<'_, PyAny>) -> bool {
+    node.is_instance_of::<PyPass>()
+        || node.is_instance_of::<PyBreak>()
+        || node.is_instance_of::<PyContinue>()
+        || node.is_instance_of::<PyReturn>()
+        || node.is_instance_of::<PyExpression>()
+        || node.is_instance_of::<PyAssign>()
+        || node.is_instance_of::<PyAssignModify>()
+        || node.is_instance_of::<PyIf>()
+        || node.is_instance_of::<PyFor>()
+        || node.is_instance_of::<PyDef>()
+        || node.is_instance_of::<PyLoad>()
+}
+
+/// Gets a list field of a node, treating `None` as an empty list.
+fn list_field<'py>(node: &Bound<'py, PyAny>, name: &str) -> PyResult<Vec<Bound<'py, PyAny>>> {
+    let value = node.getattr(name)?;
+    if value.is_none() {
+        return Ok(Vec::new());
+    }
+    value.iter()?.collect()
+}
+
+fn is_number_literal(node: &Bound<'_, PyAny>) -> PyResult<bool> {
+    if !node.is_instance_of::<PyLiteral>() {
+        return Ok(false);
+    }
+    let value = node.getattr("value")?;
+    Ok(value.is_instance_of::<PyLong>() || value.is_instance_of::<PyFloat>())
+}
+
+fn node_span(node: &Bound<'_, PyAny>) -> PyResult<Span> {
+    Ok(node.downcast::<PyAstNode>()?.borrow().span)
+}
+
+fn unexpected_node(node: &Bound<'_, PyAny>) -> PyErr {
+    PyTypeError::new_err(format!(
+        "expected a Starlark AST node, got {}",
+        node.get_type()
+            .qualname()
+            .map_or_else(|_| "?".to_owned(), |x| x.to_string())
+    ))
+}
+
+struct Comment {
+    begin: usize,
+    line: usize,
+    column: usize,
+    // without the leading `#`
+    text: String,
+    // whether the comment is on a line of its own
+    standalone: bool,
+}
+
+/// The comments of the source, and the spans of the contents of its
+/// parentheses, from the first token after `(` to the last before `)`.
+fn lex_source(codemap: &CodeMap) -> (Vec<Comment>, HashSet<(usize, usize)>) {
+    let source = codemap.source();
+    let mut result = Vec::new();
+    let mut parens = HashSet::new();
+    // where the contents of each open parenthesis begin, once known
+    let mut open: Vec<Option<usize>> = Vec::new();
+    let mut last_end = 0;
+    for x in Lexer::new(source, &Dialect::Extended, codemap.clone()) {
+        // the source is already parsed, so this doesn't fail
+        let Ok((begin, token, end)) = x else {
+            break;
+        };
+        if !matches!(token, Token::Comment(_)) {
+            if let Token::ClosingRound = token {
+                if let Some(Some(first)) = open.pop() {
+                    parens.insert((first, last_end));
+                }
+            } else if let Some(first @ None) = open.last_mut() {
+                *first = Some(begin);
+            }
+            if let Token::OpeningRound = token {
+                open.push(None);
+            }
+            last_end = end;
+        }
+        if let Token::Comment(text) = token {
+            let line = codemap.find_line(Pos::new(begin as u32));
+            let line_begin = codemap.line_span(line).begin().get() as usize;
+            result.push(Comment {
+                begin,
+                line,
+                column: begin - line_begin,
+                text: text.trim_end().to_owned(),
+                standalone: source[line_begin..begin].trim().is_empty(),
+            });
+        }
+    }
+    (result, parens)
+}
+
+/// An item of a bracketed, comma-separated sequence.
+enum SeqItem<'py> {
+    Node(Bound<'py, PyAny>),
+    Entry(Bound<'py, PyAny>, Bound<'py, PyAny>),
+    Text(String),
+}
+
+/// Turns the Python view of a Starlark AST back into source text.
+///
+/// When the nodes come from a parsed module, its source is used for keeping
+/// comments, blank lines and the spelling of some literals.
+pub(crate) struct Unparser<'a> {
+    out: String,
+    indent: usize,
+    codemap: Option<&'a CodeMap>,
+    comments: Vec<Comment>,
+    next_comment: usize,
+    // see `lex_source`
+    parens: Rc<HashSet<(usize, usize)>>,
+    // source line of the last statement or comment written
+    last_line: Option<usize>,
+    // whether nothing is written yet in the current block
+    block_start: bool,
+    // write everything on one line, for measuring
+    flat: bool,
+}
+
+impl<'a> Unparser<'a> {
+    pub(crate) fn new(codemap: Option<&'a CodeMap>) -> Self {
+        let (comments, parens) = codemap.map(lex_source).unwrap_or_default();
+        Self {
+            out: String::new(),
+            indent: 0,
+            codemap,
+            comments,
+            next_comment: 0,
+            parens: Rc::new(parens),
+            last_line: None,
+            block_start: true,
+            flat: false,
+        }
+    }
+
+    /// An unparser writing everything on one line, for measuring. Comments
+    /// are left to `self`, so the source is not lexed again.
+    fn new_flat(&self) -> Self {
+        Self {
+            out: String::new(),
+            indent: 0,
+            codemap: self.codemap,
+            comments: Vec::new(),
+            next_comment: 0,
+            parens: self.parens.clone(),
+            last_line: None,
+            block_start: true,
+            flat: true,
+        }
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.out
+    }
+
+    fn write(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn line(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn column(&self) -> usize {
+        let line_begin = self.out.rfind('\n').map_or(0, |x| x + 1);
+        self.out[line_begin..].chars().count()
+    }
+
+    /// The span of the node, if it came from the source.
+    fn real_span(&self, node: &Bound<'_, PyAny>) -> PyResult<Option<Span>> {
+        let Some(codemap) = self.codemap else {
+            return Ok(None);
+        };
+        let span = node_span(node)?;
+        if span.begin() == span.end() || span.end().get() as usize > codemap.source().len() {
+            return Ok(None);
+        }
+        Ok(Some(span))
+    }
+
+    fn line_of(&self, pos: usize) -> usize {
+        self.codemap
+            .map_or(0, |x| x.find_line(Pos::new(pos as u32)))
+    }
+
+    fn is_multiline(&self, span: Span) -> bool {
+        self.line_of(span.begin().get() as usize) != self.line_of(span.end().get() as usize - 1)
+    }
+
+    /// Whether `span` is directly within parentheses in the source.
+    fn is_parenthesized(&self, span: Span) -> bool {
+        self.parens
+            .contains(&(span.begin().get() as usize, span.end().get() as usize))
+    }
+
+    fn has_comments_in(&self, span: Span) -> bool {
+        self.comments[self.next_comment..]
+            .iter()
+            .take_while(|x| x.begin < span.end().get() as usize)
+            .any(|x| x.begin >= span.begin().get() as usize)
+    }
+
+    fn next_comment_before(&self, pos: usize) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .is_some_and(|x| x.begin < pos)
+    }
+
+    /// Writes the next comment on a line of its own.
+    fn comment_line(&mut self, keep_blank_line: bool) {
+        let comment = &self.comments[self.next_comment];
+        let (line, text) = (comment.line, format!("#{}\n", comment.text));
+        self.next_comment += 1;
+        if keep_blank_line {
+            self.separate(Some(line), false);
+        }
+        self.line();
+        self.write(&text);
+        self.last_line = Some(line);
+        self.block_start = false;
+    }
+
+    /// Writes the next comment at the end of the current line, if it's on the
+    /// source line `line` and before `limit`.
+    fn trailing_comment(&mut self, line: usize, limit: usize) {
+        if let Some(comment) = self.comments.get(self.next_comment) {
+            if !comment.standalone && comment.line == line && comment.begin < limit {
+                let text = format!("  #{}", comment.text);
+                self.next_comment += 1;
+                self.write(&text);
+            }
+        }
+    }
+
+    /// Writes a blank line if there is one in the source before `line`, or
+    /// if `fallback` when that isn't known.
+    fn separate(&mut self, line: Option<usize>, fallback: bool) {
+        if self.block_start {
+            return;
+        }
+        let blank = match (self.last_line, line) {
+            (Some(last), Some(line)) => line > last + 1,
+            _ => fallback,
+        };
+        if blank {
+            self.write("\n");
+        }
+    }
+
+    /// The first and last lines of the source spanned by the statement, if it
+    /// came from the source.
+    fn source_lines(&self, node: &Bound<'_, PyAny>) -> PyResult<Option<(usize, usize)>> {
+        let Some(span) = self.real_span(node)? else {
+            return Ok(None);
+        };
+        let begin = self.line_of(span.begin().get() as usize);
+        // the spans of compound statements include the blank lines following
+        // them, so look at their last nested statement instead
+        let last = if node.is_instance_of::<PyIf>() {
+            let orelse = list_field(node, "orelse")?;
+            if orelse.is_empty() {
+                list_field(node, "body")?.pop()
+            } else {
+                orelse.last().cloned()
+            }
+        } else if node.is_instance_of::<PyFor>() || node.is_instance_of::<PyDef>() {
+            list_field(node, "body")?.pop()
+        } else {
+            None
+        };
+        let end = match last {
+            Some(x) => match self.source_lines(&x)? {
+                Some((_, end)) => end,
+                None => return Ok(None),
+            },
+            None => self.line_of(span.end().get() as usize - 1),
+        };
+        Ok(Some((begin, end)))
+    }
+
+    pub(crate) fn stmts(&mut self, stmts: &[Bound<'_, PyAny>], top_level: bool) -> PyResult<()> {
+        let mut prev: Option<&Bound<'_, PyAny>> = None;
+        for stmt in stmts {
+            if let Some(span) = self.real_span(stmt)? {
+                while self.next_comment_before(span.begin().get() as usize) {
+                    self.comment_line(true);
+                }
+            }
+            let fallback = top_level
+                && prev.is_some_and(|prev| {
+                    prev.is_instance_of::<PyDef>()
+                        || stmt.is_instance_of::<PyDef>()
+                        || prev.is_instance_of::<PyLoad>() != stmt.is_instance_of::<PyLoad>()
+                });
+            let lines = self.source_lines(stmt)?;
+            self.separate(lines.map(|x| x.0), fallback);
+            self.stmt(stmt)?;
+            self.last_line = match (lines, self.last_line) {
+                (Some((_, end)), Some(last)) => Some(end.max(last)),
+                (Some((_, end)), None) => Some(end),
+                (None, _) => None,
+            };
+            self.block_start = false;
+            prev = Some(stmt);
+        }
+        if top_level {
+            while self.next_comment < self.comments.len() {
+                self.comment_line(true);
+            }
+        } else if stmts.is_empty() {
+            self.line();
+            self.write("pass\n");
+        }
+        Ok(())
+    }
+
+    /// Writes the body of a compound statement, whose source ends at `end`.
+    fn block(&mut self, stmts: &[Bound<'_, PyAny>], end: Option<usize>) -> PyResult<()> {
+        self.write(":");
+        let first = match stmts.first() {
+            Some(x) => self.real_span(x)?,
+            None => None,
+        };
+        let first_begin = first.map(|x| x.begin().get() as usize).or(end);
+        // a comment on the line of the header
+        if let (Some(comment), Some(limit)) = (self.comments.get(self.next_comment), first_begin) {
+            if !comment.standalone && comment.begin < limit {
+                let text = format!("  #{}", comment.text);
+                self.next_comment += 1;
+                self.write(&text);
+            }
+        }
+        self.write("\n");
+        self.indent += 1;
+        self.block_start = true;
+        self.stmts(stmts, false)?;
+        // comments after the last statement, indented like the block
+        if let (Some(first), Some(end)) = (first, end) {
+            let column = first.begin().get() as usize
+                - self.codemap.map_or(0, |x| {
+                    x.line_span(self.line_of(first.begin().get() as usize))
+                        .begin()
+                        .get() as usize
+                });
+            while let Some(comment) = self.comments.get(self.next_comment) {
+                if !(comment.standalone && comment.begin < end && comment.column >= column) {
+                    break;
+                }
+                self.comment_line(true);
+            }
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    pub(crate) fn stmt(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.line();
+        self.stmt_inline(node)?;
+        if node.is_instance_of::<PyIf>()
+            || node.is_instance_of::<PyFor>()
+            || node.is_instance_of::<PyDef>()
+        {
+            return Ok(());
+        }
+        let Some(span) = self.real_span(node)? else {
+            self.write("\n");
+            return Ok(());
+        };
+        let end = span.end().get() as usize;
+        // comments within the statement that had no place in the output
+        let mut leftover = Vec::new();
+        while self.next_comment_before(end) {
+            leftover.push(self.next_comment);
+            self.next_comment += 1;
+        }
+        self.trailing_comment(self.line_of(end - 1), usize::MAX);
+        self.write("\n");
+        for i in leftover {
+            let text = format!("#{}\n", self.comments[i].text);
+            self.line();
+            self.write(&text);
+        }
+        Ok(())
+    }
+
+    fn stmt_inline(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        if node.is_instance_of::<PyPass>() {
+            self.write("pass");
+        } else if node.is_instance_of::<PyBreak>() {
+            self.write("break");
+        } else if node.is_instance_of::<PyContinue>() {
+            self.write("continue");
+        } else if node.is_instance_of::<PyReturn>() {
+            self.write("return");
+            let value = node.getattr("value")?;
+            if !value.is_none() {
+                self.write(" ");
+                self.value(&value)?;
+            }
+        } else if node.is_instance_of::<PyExpression>() {
+            self.value(&node.getattr("value")?)?;
+        } else if node.is_instance_of::<PyAssign>() {
+            self.target(&node.getattr("target")?)?;
+            let annotation = node.getattr("annotation")?;
+            if !annotation.is_none() {
+                self.write(": ");
+                self.expr(&annotation, PREC_IF)?;
+            }
+            self.write(" = ");
+            self.value(&node.getattr("value")?)?;
+        } else if node.is_instance_of::<PyAssignModify>() {
+            self.target(&node.getattr("target")?)?;
+            self.write(" ");
+            self.write(&node.getattr("op")?.extract::<String>()?);
+            self.write(" ");
+            self.value(&node.getattr("value")?)?;
+        } else if node.is_instance_of::<PyIf>() {
+            self.write("if ");
+            let end = self.real_span(node)?.map(|x| x.end().get() as usize);
+            self.if_stmt(node, end)?;
+        } else if node.is_instance_of::<PyFor>() {
+            self.write("for ");
+            self.target(&node.getattr("target")?)?;
+            self.write(" in ");
+            self.expr(&node.getattr("iter")?, PREC_OR)?;
+            let end = self.real_span(node)?.map(|x| x.end().get() as usize);
+            self.block(&list_field(node, "body")?, end)?;
+        } else if node.is_instance_of::<PyDef>() {
+            self.write("def ");
+            self.write(&node.getattr("name")?.extract::<String>()?);
+            let params = list_field(node, "params")?;
+            let return_type = node.getattr("return_type")?;
+            // the span of the def includes the body
+            let mut header = None;
+            for x in params
+                .iter()
+                .chain(Some(&return_type).filter(|x| !x.is_none()))
+            {
+                if let (Some(span), Some(def)) = (self.real_span(x)?, self.real_span(node)?) {
+                    header = Some(Span::new(def.begin(), span.end()));
+                }
+            }
+            let params = params.into_iter().map(SeqItem::Node).collect::<Vec<_>>();
+            self.seq(header, "(", &params, ")", false, Self::param)?;
+            if !return_type.is_none() {
+                self.write(" -> ");
+                self.expr(&return_type, PREC_IF)?;
+            }
+            let end = self.real_span(node)?.map(|x| x.end().get() as usize);
+            self.block(&list_field(node, "body")?, end)?;
+        } else if node.is_instance_of::<PyLoad>() {
+            self.write("load");
+            let module = quote_str(&node.getattr("module")?.extract::<String>()?);
+            let items = Some(SeqItem::Text(module))
+                .into_iter()
+                .chain(list_field(node, "args")?.into_iter().map(SeqItem::Node))
+                .collect::<Vec<_>>();
+            self.seq(
+                self.real_span(node)?,
+                "(",
+                &items,
+                ")",
+                false,
+                Self::load_arg,
+            )?;
+        } else {
+            return Err(unexpected_node(node));
+        }
+        Ok(())
+    }
+
+    fn if_stmt(&mut self, node: &Bound<'_, PyAny>, end: Option<usize>) -> PyResult<()> {
+        self.expr(&node.getattr("test")?, PREC_IF)?;
+        let orelse = list_field(node, "orelse")?;
+        let body_end = match orelse.first() {
+            Some(x) => self.real_span(x)?.map(|x| x.begin().get() as usize),
+            None => end,
+        };
+        self.block(&list_field(node, "body")?, body_end)?;
+        match orelse.as_slice() {
+            [] => Ok(()),
+            [x] if x.is_instance_of::<PyIf>() => {
+                self.line();
+                self.write("elif ");
+                self.if_stmt(x, end)
+            }
+            _ => {
+                self.line();
+                self.write("else");
+                self.block(&orelse, end)
+            }
+        }
+    }
+
+    fn load_arg(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        if !node.is_instance_of::<PyLoadArg>() {
+            return Err(unexpected_node(node));
+        }
+        let local: String = node.getattr("local")?.extract()?;
+        let their: String = node.getattr("their")?.extract()?;
+        if local != their {
+            self.write(&local);
+            self.write(" = ");
+        }
+        self.write(&quote_str(&their));
+        Ok(())
+    }
+
+    /// Writes the value of a statement, leaving out the parentheses of tuples
+    /// that had none in the source and still fit on the line.
+    fn value(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        if node.is_instance_of::<PyTupleExpr>() && !self.flat {
+            let span = self.real_span(node)?.filter(|&x| {
+                !(self.is_parenthesized(x) || self.is_multiline(x) || self.has_comments_in(x))
+            });
+            let elts = list_field(node, "elts")?;
+            if span.is_some() && !elts.is_empty() {
+                let mut flat = self.new_flat();
+                flat.comma_separated(&elts, Self::expr_item)?;
+                if elts.len() == 1 {
+                    flat.write(",");
+                }
+                let flat = flat.finish();
+                if self.column() + flat.chars().count() <= MAX_LINE_LENGTH {
+                    self.write(&flat);
+                    return Ok(());
+                }
+            }
+        }
+        self.expr(node, PREC_LAMBDA)
+    }
+
+    /// Writes an assignment target, leaving out the parentheses of tuples
+    /// unless they are in the source.
+    fn target(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        let parenthesized = self
+            .real_span(node)?
+            .is_some_and(|x| self.is_parenthesized(x));
+        if node.is_instance_of::<PyTupleExpr>() && !parenthesized {
+            let elts = list_field(node, "elts")?;
+            self.comma_separated(&elts, |me, x| me.expr(x, PREC_BIT_OR))?;
+            if elts.len() == 1 {
+                self.write(",");
+            }
+            Ok(())
+        } else {
+            self.expr(node, PREC_BIT_OR)
+        }
+    }
+
+    fn comma_separated(
+        &mut self,
+        items: &[Bound<'_, PyAny>],
+        mut f: impl FnMut(&mut Self, &Bound<'_, PyAny>) -> PyResult<()>,
+    ) -> PyResult<()> {
+        for (i, x) in items.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            f(self, x)?;
+        }
+        Ok(())
+    }
+
+    fn params(&mut self, params: &[Bound<'_, PyAny>]) -> PyResult<()> {
+        self.comma_separated(params, |me, x| me.param(x))
+    }
+
+    fn seq_item(
+        &mut self,
+        item: &SeqItem<'_>,
+        f: fn(&mut Self, &Bound<'_, PyAny>) -> PyResult<()>,
+    ) -> PyResult<()> {
+        match item {
+            SeqItem::Node(x) => f(self, x),
+            SeqItem::Entry(k, v) => {
+                self.expr(k, PREC_IF)?;
+                self.write(": ");
+                self.expr(v, PREC_IF)
+            }
+            SeqItem::Text(x) => {
+                self.write(x);
+                Ok(())
+            }
+        }
+    }
+
+    fn seq_item_span(&self, item: &SeqItem<'_>) -> PyResult<Option<Span>> {
+        Ok(match item {
+            SeqItem::Node(x) => self.real_span(x)?,
+            SeqItem::Entry(k, v) => match (self.real_span(k)?, self.real_span(v)?) {
+                (Some(k), Some(v)) => Some(k.merge(v)),
+                _ => None,
+            },
+            SeqItem::Text(_) => None,
+        })
+    }
+
+    /// Writes a bracketed, comma-separated sequence, with one item per line
+    /// if it doesn't fit on the line, contains comments or spans multiple
+    /// lines in the source.
+    fn seq(
+        &mut self,
+        span: Option<Span>,
+        open: &str,
+        items: &[SeqItem<'_>],
+        close: &str,
+        single_comma: bool,
+        f: fn(&mut Self, &Bound<'_, PyAny>) -> PyResult<()>,
+    ) -> PyResult<()> {
+        let mut flat = self.new_flat();
+        flat.write(open);
+        for (i, x) in items.iter().enumerate() {
+            if i > 0 {
+                flat.write(", ");
+            }
+            flat.seq_item(x, f)?;
+        }
+        if single_comma && items.len() == 1 {
+            flat.write(",");
+        }
+        flat.write(close);
+        let flat = flat.finish();
+
+        let wrap = !self.flat
+            && !items.is_empty()
+            && (span.is_some_and(|x| self.has_comments_in(x) || self.is_multiline(x))
+                || self.column() + flat.lines().next().map_or(0, |x| x.chars().count())
+                    > MAX_LINE_LENGTH);
+        if !wrap {
+            self.write(&flat);
+            return Ok(());
+        }
+
+        self.write(open);
+        self.write("\n");
+        self.indent += 1;
+        for (i, item) in items.iter().enumerate() {
+            let item_span = self.seq_item_span(item)?;
+            if let Some(x) = item_span {
+                while self.next_comment_before(x.begin().get() as usize) {
+                    self.comment_line(false);
+                }
+            }
+            self.line();
+            self.seq_item(item, f)?;
+            self.write(",");
+            if let Some(x) = item_span {
+                let limit = match items.get(i + 1) {
+                    Some(next) => self.seq_item_span(next)?.map(|x| x.begin().get() as usize),
+                    None => span.map(|x| x.end().get() as usize),
+                };
+                let line = self.line_of(x.end().get() as usize - 1);
+                self.trailing_comment(line, limit.unwrap_or(usize::MAX));
+            }
+            self.write("\n");
+        }
+        if let Some(x) = span {
+            while self.next_comment_before(x.end().get() as usize) {
+                self.comment_line(false);
+            }
+        }
+        self.indent -= 1;
+        self.line();
+        self.write(close);
+        Ok(())
+    }
+
+    fn param(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        if !node.is_instance_of::<PyParameter>() {
+            return Err(unexpected_node(node));
+        }
+        let kind: PyParameterKind = node.getattr("kind")?.extract()?;
+        match kind {
+            PyParameterKind::NoArgs => {
+                self.write("*");
+                return Ok(());
+            }
+            PyParameterKind::Args => self.write("*"),
+            PyParameterKind::KwArgs => self.write("**"),
+            PyParameterKind::Normal | PyParameterKind::WithDefaultValue => {}
+        }
+        self.write(&node.getattr("name")?.extract::<String>()?);
+        let annotation = node.getattr("annotation")?;
+        if !annotation.is_none() {
+            self.write(": ");
+            self.expr(&annotation, PREC_IF)?;
+        }
+        let default = node.getattr("default")?;
+        if !default.is_none() {
+            self.write(" = ");
+            self.expr(&default, PREC_IF)?;
+        }
+        Ok(())
+    }
+
+    fn argument(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        if !node.is_instance_of::<PyArgument>() {
+            return Err(unexpected_node(node));
+        }
+        let kind: PyArgumentKind = node.getattr("kind")?.extract()?;
+        match kind {
+            PyArgumentKind::Positional => {}
+            PyArgumentKind::Named => {
+                self.write(&node.getattr("name")?.extract::<String>()?);
+                self.write(" = ");
+            }
+            PyArgumentKind::Args => self.write("*"),
+            PyArgumentKind::KwArgs => self.write("**"),
+        }
+        self.expr(&node.getattr("value")?, PREC_IF)
+    }
+
+    fn clause(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        if node.is_instance_of::<PyForClause>() {
+            self.write(" for ");
+            self.target(&node.getattr("target")?)?;
+            self.write(" in ");
+            self.expr(&node.getattr("iter")?, PREC_OR)
+        } else if node.is_instance_of::<PyIfClause>() {
+            self.write(" if ");
+            self.expr(&node.getattr("test")?, PREC_OR)
+        } else {
+            Err(unexpected_node(node))
+        }
+    }
+
+    /// The source text of a literal, if it should be kept as written.
+    fn literal_source(&self, node: &Bound<'_, PyAny>) -> PyResult<Option<String>> {
+        let (Some(span), Some(codemap)) = (self.real_span(node)?, self.codemap) else {
+            return Ok(None);
+        };
+        let text = codemap.source_span(span);
+        let value = node.getattr("value")?;
+        // keep the spelling of numbers, and of strings that would otherwise
+        // lose their line breaks or raw-ness
+        if value.is_instance_of::<PyString>()
+            && !(text.starts_with("\"\"\"")
+                || text.starts_with("'''")
+                || text.starts_with(['r', 'R']))
+        {
+            return Ok(None);
+        }
+        // the node may have been changed since it was parsed
+        let lexer_codemap = CodeMap::new(String::new(), text.to_owned());
+        let same = match Lexer::new(text, &Dialect::Extended, lexer_codemap).next() {
+            Some(Ok((_, Token::String(x), end))) if end == text.len() => value
+                .downcast::<PyString>()
+                .map_or(Ok(false), |v| v.to_cow().map(|v| v == x))?,
+            Some(Ok((_, Token::Int(x), end))) if end == text.len() => {
+                value.is_exact_instance_of::<PyLong>()
+                    && match x {
+                        TokenInt::I32(x) => value.eq(x)?,
+                        TokenInt::BigInt(x) => value.eq(x)?,
+                    }
+            }
+            Some(Ok((_, Token::Float(x), end))) if end == text.len() => {
+                value.is_exact_instance_of::<PyFloat>() && value.eq(x)?
+            }
+            _ => false,
+        };
+        Ok(same.then(|| text.to_owned()))
+    }
+
+    fn literal(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        if let Some(x) = self.literal_source(node)? {
+            self.write(&x);
+            return Ok(());
+        }
+        let value = node.getattr("value")?;
+        let value = &value;
+        if let Ok(x) = value.downcast::<PyString>() {
+            self.write(&quote_str(&x.to_cow()?));
+        } else if value.is(&value.py().Ellipsis()) {
+            self.write("...");
+        } else if value.is_none() || value.is_instance_of::<PyLong>() {
+            self.write(&value.str()?.to_cow()?);
+        } else if let Ok(x) = value.downcast::<PyFloat>() {
+            if !x.value().is_finite() {
+                return Err(PyValueError::new_err(format!(
+                    "cannot write {} as a Starlark literal",
+                    value.repr()?
+                )));
+            }
+            self.write(&value.repr()?.to_cow()?);
+        } else {
+            return Err(PyTypeError::new_err(format!(
+                "cannot write a {} as a Starlark literal",
+                value.get_type().qualname()?
+            )));
+        }
+        Ok(())
+    }
+
+    fn fstring(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        let format: String = node.getattr("format")?.extract()?;
+        let mut expressions = list_field(node, "expressions")?.into_iter();
+        let mut rest = format.as_str();
+        let mut result = String::new();
+        // the format string has a `{}` marker for each expression, and literal
+        // braces are still escaped
+        while !rest.is_empty() {
+            if let Some(x) = rest.strip_prefix("{{") {
+                result.push_str("{{");
+                rest = x;
+            } else if let Some(x) = rest.strip_prefix("}}") {
+                result.push_str("}}");
+                rest = x;
+            } else if let Some(x) = rest.strip_prefix("{}") {
+                let expr = expressions.next().ok_or_else(|| {
+                    PyTypeError::new_err("too few expressions for the f-string format")
+                })?;
+                let mut inner = Unparser::new(None);
+                inner.expr(&expr, PREC_LAMBDA)?;
+                result.push('{');
+                result.push_str(&inner.finish());
+                result.push('}');
+                rest = x;
+            } else {
+                let c = rest.chars().next().unwrap();
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        self.write("f");
+        // escape everything but the braces, which are already as intended
+        self.write(&quote_str(&result));
+        Ok(())
+    }
+
+    /// Writes a unary operator and its operand. An operand starting with a
+    /// unary operator too is parenthesized, as in `-(-1)`, unless it had no
+    /// parentheses in the source, where it's kept apart by a space if needed.
+    fn unary(&mut self, op: &str, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.write(op);
+        let operand = node.getattr("operand")?;
+        let operand_op = if operand.is_instance_of::<PyMinus>() {
+            "-"
+        } else if operand.is_instance_of::<PyPlus>() {
+            "+"
+        } else if operand.is_instance_of::<PyBitNot>() {
+            "~"
+        } else if is_number_literal(&operand)?
+            && operand.getattr("value")?.repr()?.to_cow()?.starts_with('-')
+        {
+            "-"
+        } else {
+            return self.expr(&operand, PREC_UNARY);
+        };
+        match self.real_span(&operand)? {
+            Some(x) if !self.is_parenthesized(x) => {
+                if op == operand_op {
+                    self.write(" ");
+                }
+                self.expr(&operand, PREC_UNARY)
+            }
+            _ => self.expr(&operand, PREC_POSTFIX),
+        }
+    }
+
+    fn expr_item(&mut self, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.expr(node, PREC_IF)
+    }
+
+    pub(crate) fn expr(&mut self, node: &Bound<'_, PyAny>, min_prec: u8) -> PyResult<()> {
+        let prec = if node.is_instance_of::<PyLambda>() {
+            PREC_LAMBDA
+        } else if node.is_instance_of::<PyIfExpr>() {
+            PREC_IF
+        } else if node.is_instance_of::<PyNot>() {
+            PREC_NOT
+        } else if node.is_instance_of::<PyOp>() {
+            binary_op_prec(&node.getattr("op")?.extract::<String>()?)?
+        } else if node.is_instance_of::<PyMinus>()
+            || node.is_instance_of::<PyPlus>()
+            || node.is_instance_of::<PyBitNot>()
+            // `1.real` would lex as a float, and `-1[0]` negates the item
+            || is_number_literal(node)?
+        {
+            PREC_UNARY
+        } else {
+            PREC_POSTFIX
+        };
+        // commented operator chains are parenthesized anyway
+        let parenthesize = prec < min_prec && !self.commented_op(node)?;
+        if parenthesize {
+            self.write("(");
+        }
+        self.expr_inner(node, prec)?;
+        if parenthesize {
+            self.write(")");
+        }
+        Ok(())
+    }
+
+    /// Whether `node` is a binary operation containing comments, which can
+    /// only be kept by breaking it into lines.
+    fn commented_op(&self, node: &Bound<'_, PyAny>) -> PyResult<bool> {
+        if self.flat || !node.is_instance_of::<PyOp>() {
+            return Ok(false);
+        }
+        Ok(self
+            .real_span(node)?
+            .is_some_and(|x| self.has_comments_in(x)))
+    }
+
+    /// Writes a chain of binary operations of the same precedence within
+    /// parentheses, with one operand per line and the comments in between.
+    ///
+    /// Comments within other multi-line expressions outside brackets, such as
+    /// conditional expressions, are moved after the statement instead.
+    fn op_chain(&mut self, node: &Bound<'_, PyAny>, prec: u8) -> PyResult<()> {
+        // operands from right to left, with the operators before them
+        let mut operands = Vec::new();
+        let mut node = node.clone();
+        loop {
+            operands.push((
+                Some(node.getattr("op")?.extract::<String>()?),
+                node.getattr("right")?,
+            ));
+            let left = node.getattr("left")?;
+            let same_prec = left.is_instance_of::<PyOp>()
+                && binary_op_prec(&left.getattr("op")?.extract::<String>()?)? == prec;
+            if !same_prec || prec == PREC_COMPARE {
+                operands.push((None, left));
+                break;
+            }
+            node = left;
+        }
+        operands.reverse();
+
+        self.write("(\n");
+        self.indent += 1;
+        for (i, (op, x)) in operands.iter().enumerate() {
+            let x_span = self.real_span(x)?;
+            if let Some(x) = x_span {
+                while self.next_comment_before(x.begin().get() as usize) {
+                    self.comment_line(false);
+                }
+            }
+            self.line();
+            if let Some(op) = op {
+                self.write(op);
+                self.write(" ");
+            }
+            // comparisons don't chain in Starlark
+            let operand_prec = if i == 0 && prec != PREC_COMPARE {
+                prec
+            } else {
+                prec + 1
+            };
+            self.expr(x, operand_prec)?;
+            if let Some(x) = x_span {
+                let limit = match operands.get(i + 1) {
+                    Some((_, next)) => self.real_span(next)?.map(|x| x.begin().get() as usize),
+                    None => None,
+                };
+                let line = self.line_of(x.end().get() as usize - 1);
+                self.trailing_comment(line, limit.unwrap_or(usize::MAX));
+            }
+            self.write("\n");
+        }
+        self.indent -= 1;
+        self.line();
+        self.write(")");
+        Ok(())
+    }
+
+    fn expr_inner(&mut self, node: &Bound<'_, PyAny>, prec: u8) -> PyResult<()> {
+        if node.is_instance_of::<PyTupleExpr>() {
+            let elts = list_field(node, "elts")?;
+            let elts = elts.into_iter().map(SeqItem::Node).collect::<Vec<_>>();
+            self.seq(
+                self.real_span(node)?,
+                "(",
+                &elts,
+                ")",
+                true,
+                Self::expr_item,
+            )?;
+        } else if node.is_instance_of::<PyDot>() {
+            self.expr(&node.getattr("value")?, PREC_POSTFIX)?;
+            self.write(".");
+            self.write(&node.getattr("attr")?.extract::<String>()?);
+        } else if node.is_instance_of::<PyCall>() {
+            self.expr(&node.getattr("func")?, PREC_POSTFIX)?;
+            let args = list_field(node, "args")?;
+            let args = args.into_iter().map(SeqItem::Node).collect::<Vec<_>>();
+            self.seq(
+                self.real_span(node)?,
+                "(",
+                &args,
+                ")",
+                false,
+                Self::argument,
+            )?;
+        } else if node.is_instance_of::<PyIndex>() {
+            self.expr(&node.getattr("value")?, PREC_POSTFIX)?;
+            self.write("[");
+            self.expr(&node.getattr("index")?, PREC_IF)?;
+            self.write("]");
+        } else if node.is_instance_of::<PyIndex2>() {
+            self.expr(&node.getattr("value")?, PREC_POSTFIX)?;
+            self.write("[");
+            self.expr(&node.getattr("index1")?, PREC_IF)?;
+            self.write(", ");
+            self.expr(&node.getattr("index2")?, PREC_IF)?;
+            self.write("]");
+        } else if node.is_instance_of::<PySlice>() {
+            self.expr(&node.getattr("value")?, PREC_POSTFIX)?;
+            self.write("[");
+            for (i, name) in ["start", "stop", "step"].into_iter().enumerate() {
+                let x = node.getattr(name)?;
+                if i == 2 && x.is_none() {
+                    break;
+                }
+                if i > 0 {
+                    self.write(":");
+                }
+                if !x.is_none() {
+                    self.expr(&x, PREC_IF)?;
+                }
+            }
+            self.write("]");
+        } else if node.is_instance_of::<PyIdentifier>() {
+            self.write(&node.getattr("name")?.extract::<String>()?);
+        } else if node.is_instance_of::<PyLambda>() {
+            self.write("lambda");
+            let params = list_field(node, "params")?;
+            if !params.is_empty() {
+                self.write(" ");
+                self.params(&params)?;
+            }
+            self.write(": ");
+            self.expr(&node.getattr("body")?, PREC_LAMBDA)?;
+        } else if node.is_instance_of::<PyLiteral>() {
+            self.literal(node)?;
+        } else if node.is_instance_of::<PyNot>() {
+            self.write("not ");
+            self.expr(&node.getattr("operand")?, PREC_NOT)?;
+        } else if node.is_instance_of::<PyMinus>() {
+            self.unary("-", node)?;
+        } else if node.is_instance_of::<PyPlus>() {
+            self.unary("+", node)?;
+        } else if node.is_instance_of::<PyBitNot>() {
+            self.unary("~", node)?;
+        } else if self.commented_op(node)? {
+            self.op_chain(node, prec)?;
+        } else if node.is_instance_of::<PyOp>() {
+            // comparisons don't chain in Starlark
+            let left_prec = if prec == PREC_COMPARE { prec + 1 } else { prec };
+            self.expr(&node.getattr("left")?, left_prec)?;
+            self.write(" ");
+            self.write(&node.getattr("op")?.extract::<String>()?);
+            self.write(" ");
+            self.expr(&node.getattr("right")?, prec + 1)?;
+        } else if node.is_instance_of::<PyIfExpr>() {
+            self.expr(&node.getattr("body")?, PREC_OR)?;
+            self.write(" if ");
+            self.expr(&node.getattr("test")?, PREC_OR)?;
+            self.write(" else ");
+            self.expr(&node.getattr("orelse")?, PREC_IF)?;
+        } else if node.is_instance_of::<PyListExpr>() {
+            let elts = list_field(node, "elts")?;
+            let elts = elts.into_iter().map(SeqItem::Node).collect::<Vec<_>>();
+            self.seq(
+                self.real_span(node)?,
+                "[",
+                &elts,
+                "]",
+                false,
+                Self::expr_item,
+            )?;
+        } else if node.is_instance_of::<PyDictExpr>() {
+            let keys = list_field(node, "keys")?;
+            let values = list_field(node, "values")?;
+            if keys.len() != values.len() {
+                return Err(PyTypeError::new_err(
+                    "keys and values of a Dict must have the same length",
+                ));
+            }
+            let items = keys
+                .into_iter()
+                .zip(values)
+                .map(|(k, v)| SeqItem::Entry(k, v))
+                .collect::<Vec<_>>();
+            self.seq(
+                self.real_span(node)?,
+                "{",
+                &items,
+                "}",
+                false,
+                Self::expr_item,
+            )?;
+        } else if node.is_instance_of::<PyListComprehension>() {
+            self.write("[");
+            self.expr(&node.getattr("elt")?, PREC_IF)?;
+            for x in list_field(node, "clauses")? {
+                self.clause(&x)?;
+            }
+            self.write("]");
+        } else if node.is_instance_of::<PyDictComprehension>() {
+            self.write("{");
+            self.expr(&node.getattr("key")?, PREC_IF)?;
+            self.write(": ");
+            self.expr(&node.getattr("value")?, PREC_IF)?;
+            for x in list_field(node, "clauses")? {
+                self.clause(&x)?;
+            }
+            self.write("}");
+        } else if node.is_instance_of::<PyFString>() {
+            self.fstring(node)?;
+        } else {
+            return Err(unexpected_node(node));
+        }
+        Ok(())
+    }
+}
+
+/// Turns an AST node, or a list of statements, back into Starlark source.
+///
+/// Raises `TypeError` for `Literal` values that have no Starlark literal form,
+/// and `ValueError` for non-finite floats.
+#[pyfunction]
+pub(crate) fn unparse(node: &Bound<'_, PyAny>) -> PyResult<String> {
+    let mut unparser = Unparser::new(None);
+    if is_stmt(node) {
+        unparser.stmt(node)?;
+    } else if node.is_instance_of::<PyArgument>() {
+        unparser.argument(node)?;
+    } else if node.is_instance_of::<PyParameter>() {
+        unparser.param(node)?;
+    } else if node.is_instance_of::<PyLoadArg>() {
+        unparser.load_arg(node)?;
+    } else if node.is_instance_of::<PyForClause>() || node.is_instance_of::<PyIfClause>() {
+        unparser.clause(node)?;
+        return Ok(unparser.finish().trim_start().to_owned());
+    } else if node.is_instance_of::<PyAstNode>() {
+        unparser.expr(node, PREC_LAMBDA)?;
+    } else {
+        unparser.stmts(&node.iter()?.collect::<PyResult<Vec<_>>>()?, true)?;
+    }
+    Ok(unparser.finish())
+}
@@ -0,0 +1,103 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyString};
+
+use super::PyAstNode;
+
+/// Walks an AST, calling the `visit_<ClassName>` method for every node if
+/// defined, or `generic_visit` otherwise, similar to `ast.NodeVisitor`.
+#[pyclass(module = "xingque", name = "NodeVisitor", subclass)]
+pub(crate) struct PyNodeVisitor;
+
+#[pymethods]
+impl PyNodeVisitor {
+    #[new]
+    fn py_new() -> Self {
+        Self
+    }
+
+    /// Visits a node, or each node of a list of statements.
+    ///
+    /// For lists, the results are collected into a new list, leaving out
+    /// `None` and flattening lists.
+    fn visit(slf: &Bound<'_, Self>, node: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        if let Ok(nodes) = node.downcast::<PyList>() {
+            return Ok(visit_list(slf.as_any(), nodes)?.into_any().unbind());
+        }
+        let node = node.downcast::<PyAstNode>()?;
+        let method = format!("visit_{}", node.get_type().name()?);
+        if slf.hasattr(method.as_str())? {
+            slf.call_method1(method.as_str(), (node,))
+        } else {
+            slf.call_method1("generic_visit", (node,))
+        }
+        .map(Bound::unbind)
+    }
+
+    /// Visits all children of the node, for use when there is no specific
+    /// visitor method.
+    fn generic_visit(slf: &Bound<'_, Self>, node: &Bound<'_, PyAstNode>) -> PyResult<()> {
+        for child in PyAstNode::children(node)? {
+            slf.call_method1("visit", (child,))?;
+        }
+        Ok(())
+    }
+}
+
+/// A `NodeVisitor` that replaces each visited node with the return value of
+/// its visitor method, similar to `ast.NodeTransformer`.
+///
+/// Returning `None` removes the node, and returning a list of nodes in place
+/// of a statement inserts all of them.
+#[pyclass(module = "xingque", name = "NodeTransformer", extends = PyNodeVisitor, subclass)]
+pub(crate) struct PyNodeTransformer;
+
+#[pymethods]
+impl PyNodeTransformer {
+    #[new]
+    fn py_new() -> (Self, PyNodeVisitor) {
+        (Self, PyNodeVisitor)
+    }
+
+    fn generic_visit<'py>(
+        slf: &Bound<'py, Self>,
+        node: &Bound<'py, PyAstNode>,
+    ) -> PyResult<Bound<'py, PyAstNode>> {
+        for name in node.getattr("_fields")?.iter()? {
+            let name = name?;
+            let name = name.downcast::<PyString>()?;
+            let value = node.getattr(name)?;
+            if let Ok(values) = value.downcast::<PyList>() {
+                node.setattr(name, visit_list(slf.as_any(), values)?)?;
+            } else if value.is_instance_of::<PyAstNode>() {
+                node.setattr(name, slf.call_method1("visit", (value,))?)?;
+            }
+        }
+        Ok(node.clone())
+    }
+}
+
+fn visit_list<'py>(
+    visitor: &Bound<'py, PyAny>,
+    nodes: &Bound<'py, PyList>,
+) -> PyResult<Bound<'py, PyList>> {
+    let result = PyList::empty_bound(visitor.py());
+    for x in nodes.iter() {
+        if !x.is_instance_of::<PyAstNode>() {
+            result.append(x)?;
+            continue;
+        }
+        let x = visitor.call_method1("visit", (x,))?;
+        if x.is_none() {
+            continue;
+        }
+        match x.downcast::<PyList>() {
+            Ok(xs) => {
+                for x in xs.iter() {
+                    result.append(x)?;
+                }
+            }
+            Err(_) => result.append(x)?,
+        }
+    }
+    Ok(result)
+}
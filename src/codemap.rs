@@ -322,7 +322,7 @@ impl PyCodeMap {
 
 #[pyclass(module = "xingque", name = "FileSpan", frozen)]
 #[derive(Clone)]
-pub(crate) struct PyFileSpan(FileSpan);
+pub(crate) struct PyFileSpan(pub(crate) FileSpan);
 
 impl From<FileSpan> for PyFileSpan {
     fn from(value: FileSpan) -> Self {
@@ -420,7 +420,7 @@ impl PyResolvedFileLine {
 }
 
 #[pyclass(module = "xingque", name = "ResolvedFileSpan")]
-pub(crate) struct PyResolvedFileSpan(ResolvedFileSpan);
+pub(crate) struct PyResolvedFileSpan(pub(crate) ResolvedFileSpan);
 
 impl From<ResolvedFileSpan> for PyResolvedFileSpan {
     fn from(value: ResolvedFileSpan) -> Self {
@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+use starlark::codemap::{ResolvedFileSpan, ResolvedPos, ResolvedSpan};
+
+use crate::codemap::PyResolvedFileSpan;
+use crate::syntax::PyAstModule;
+
+#[derive(Default)]
+struct FileCoverage {
+    executed: BTreeSet<ResolvedSpanKey>,
+    executable_lines: BTreeSet<usize>,
+}
+
+impl FileCoverage {
+    fn executed_lines(&self) -> BTreeSet<usize> {
+        self.executed.iter().map(|x| x.0).collect()
+    }
+
+    // lines that ran are always executable, even if the module was not added
+    fn lines(&self) -> BTreeSet<usize> {
+        let mut result = self.executed_lines();
+        result.extend(&self.executable_lines);
+        result
+    }
+}
+
+// ResolvedSpan doesn't implement Ord
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct ResolvedSpanKey(usize, usize, usize, usize);
+
+impl From<ResolvedSpan> for ResolvedSpanKey {
+    fn from(value: ResolvedSpan) -> Self {
+        Self(
+            value.begin.line,
+            value.begin.column,
+            value.end.line,
+            value.end.column,
+        )
+    }
+}
+
+impl From<ResolvedSpanKey> for ResolvedSpan {
+    fn from(value: ResolvedSpanKey) -> Self {
+        ResolvedSpan {
+            begin: ResolvedPos {
+                line: value.0,
+                column: value.1,
+            },
+            end: ResolvedPos {
+                line: value.2,
+                column: value.3,
+            },
+        }
+    }
+}
+
+/// Line coverage of Starlark files, aggregated from possibly many evaluations.
+///
+/// Line numbers are 0-based, like everywhere else, but are written out 1-based
+/// in the exported reports.
+#[pyclass(module = "xingque", name = "CoverageReport")]
+#[derive(Default)]
+pub(crate) struct PyCoverageReport(BTreeMap<String, FileCoverage>);
+
+impl PyCoverageReport {
+    fn ratio(covered: usize, valid: usize) -> f64 {
+        match valid {
+            0 => 1.0,
+            _ => covered as f64 / valid as f64,
+        }
+    }
+}
+
+#[pymethods]
+impl PyCoverageReport {
+    #[new]
+    fn py_new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the statements executed in an evaluation, as obtained from
+    /// `Evaluator.coverage`.
+    fn add_coverage(&mut self, spans: &Bound<'_, PyAny>) -> PyResult<()> {
+        for x in spans.iter()? {
+            let x = x?;
+            let x = x.downcast::<PyResolvedFileSpan>()?.borrow();
+            self.0
+                .entry(x.0.file.clone())
+                .or_default()
+                .executed
+                .insert(x.0.span.into());
+        }
+        Ok(())
+    }
+
+    /// Records the statements of a module as executable, so that the lines
+    /// never executed are reported as uncovered.
+    ///
+    /// This has to be done before evaluating the module, which consumes it.
+    fn add_module(&mut self, module: &Bound<'_, PyAstModule>) -> PyResult<()> {
+        for x in module.borrow().inner()?.stmt_locations() {
+            let x = x.resolve();
+            self.0
+                .entry(x.file)
+                .or_default()
+                .executable_lines
+                .insert(x.span.begin.line);
+        }
+        Ok(())
+    }
+
+    /// The files known to this report, sorted.
+    #[getter]
+    fn files(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+
+    /// The spans of the statements executed in `file`, sorted.
+    fn executed_spans(&self, file: &str) -> Vec<PyResolvedFileSpan> {
+        match self.0.get(file) {
+            Some(x) => x
+                .executed
+                .iter()
+                .map(|span| {
+                    ResolvedFileSpan {
+                        file: file.to_owned(),
+                        span: (*span).into(),
+                    }
+                    .into()
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The lines in `file` where executed statements begin, sorted.
+    fn executed_lines(&self, file: &str) -> Vec<usize> {
+        match self.0.get(file) {
+            Some(x) => x.executed_lines().into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The lines in `file` where statements begin, sorted.
+    fn lines(&self, file: &str) -> Vec<usize> {
+        match self.0.get(file) {
+            Some(x) => x.lines().into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Exports the report in the LCOV tracefile format.
+    fn to_lcov(&self) -> String {
+        let mut result = String::new();
+        for (file, cov) in &self.0 {
+            let executed = cov.executed_lines();
+            let lines = cov.lines();
+            result.push_str("TN:\n");
+            writeln!(result, "SF:{}", file).unwrap();
+            for line in &lines {
+                let hits = executed.contains(line) as usize;
+                writeln!(result, "DA:{},{}", line + 1, hits).unwrap();
+            }
+            writeln!(result, "LF:{}", lines.len()).unwrap();
+            writeln!(result, "LH:{}", executed.len()).unwrap();
+            result.push_str("end_of_record\n");
+        }
+        result
+    }
+
+    /// Exports the report in the Cobertura XML format.
+    fn to_cobertura(&self) -> String {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_millis());
+
+        let mut classes = String::new();
+        let mut total_covered = 0;
+        let mut total_valid = 0;
+        for (file, cov) in &self.0 {
+            let executed = cov.executed_lines();
+            let lines = cov.lines();
+            total_covered += executed.len();
+            total_valid += lines.len();
+
+            let file = xml_escape(file);
+            writeln!(
+                classes,
+                r#"        <class name="{}" filename="{}" line-rate="{:.4}" branch-rate="0" complexity="0">"#,
+                file,
+                file,
+                Self::ratio(executed.len(), lines.len()),
+            )
+            .unwrap();
+            classes.push_str("          <methods/>\n          <lines>\n");
+            for line in &lines {
+                let hits = executed.contains(line) as usize;
+                writeln!(
+                    classes,
+                    r#"            <line number="{}" hits="{}"/>"#,
+                    line + 1,
+                    hits
+                )
+                .unwrap();
+            }
+            classes.push_str("          </lines>\n        </class>\n");
+        }
+
+        let line_rate = Self::ratio(total_covered, total_valid);
+        let mut result = String::new();
+        result.push_str("<?xml version=\"1.0\" ?>\n");
+        result.push_str(
+            "<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">\n",
+        );
+        writeln!(
+            result,
+            r#"<coverage line-rate="{:.4}" branch-rate="0" lines-covered="{}" lines-valid="{}" branches-covered="0" branches-valid="0" complexity="0" version="{}" timestamp="{}">"#,
+            line_rate,
+            total_covered,
+            total_valid,
+            option_env!("CARGO_PKG_VERSION").unwrap_or("unknown"),
+            timestamp,
+        )
+        .unwrap();
+        result.push_str("  <sources>\n    <source>.</source>\n  </sources>\n");
+        result.push_str("  <packages>\n");
+        writeln!(
+            result,
+            r#"    <package name="." line-rate="{:.4}" branch-rate="0" complexity="0">"#,
+            line_rate,
+        )
+        .unwrap();
+        result.push_str("      <classes>\n");
+        result.push_str(&classes);
+        result.push_str("      </classes>\n    </package>\n  </packages>\n</coverage>\n");
+        result
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use starlark::codemap::CodeMap;
+use starlark::collections::SmallMap;
+use starlark::docs::{
+    DocFunction, DocItem, DocMember, DocModule, DocObject, DocParam, DocProperty, DocReturn,
+    DocString, DocStringKind,
+};
+use starlark::syntax::AstModule;
+use starlark::typing::Ty;
+use starlark_syntax::syntax::ast::{
+    AstExpr, AstLiteral, AstNoPayload, AstParameter, AstStmt, AstTypeExpr, DefP, ExprP, ParameterP,
+    StmtP,
+};
+use starlark_syntax::syntax::module::AstModuleFields;
+use starlark_syntax::syntax::top_level_stmts::top_level_stmts;
+
+use crate::typing::ty_from_type_expr;
+
+mod render;
+pub(crate) use render::{render_module, DocFormat};
+
+fn to_json(x: &DocItem) -> PyResult<String> {
+    serde_json::to_string(x).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn doc_item_to_py(py: Python<'_>, item: DocItem) -> PyResult<PyObject> {
+    Ok(match item {
+        DocItem::Module(x) => PyDocModule(x).into_py(py),
+        DocItem::Object(x) => PyDocObject(x).into_py(py),
+        DocItem::Function(x) => PyDocFunction(x).into_py(py),
+        DocItem::Property(x) => PyDocProperty(x).into_py(py),
+    })
+}
+
+fn members_to_py<'py, 'a>(
+    py: Python<'py>,
+    members: impl IntoIterator<Item = (&'a String, &'a DocMember)>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let result = PyDict::new_bound(py);
+    for (name, member) in members {
+        result.set_item(name, doc_item_to_py(py, member.clone().to_doc_item())?)?;
+    }
+    Ok(result)
+}
+
+fn docs_to_py(x: &Option<DocString>) -> Option<PyDocString> {
+    x.clone().map(PyDocString)
+}
+
+/// A docstring, split into its summary line and the rest.
+#[pyclass(module = "xingque", name = "DocString", frozen)]
+pub(crate) struct PyDocString(DocString);
+
+#[pymethods]
+impl PyDocString {
+    fn __repr__(&self) -> String {
+        format!("<DocString {:?}>", self.0.summary)
+    }
+
+    fn __str__(&self) -> String {
+        match &self.0.details {
+            Some(details) => format!("{}\n\n{}", self.0.summary, details),
+            None => self.0.summary.clone(),
+        }
+    }
+
+    #[getter]
+    fn summary(&self) -> &str {
+        &self.0.summary
+    }
+
+    #[getter]
+    fn details(&self) -> Option<&str> {
+        self.0.details.as_deref()
+    }
+}
+
+// NOTE: keep this in sync with starlark::docs::DocParam
+#[pyclass(
+    module = "xingque",
+    name = "DocParamKind",
+    rename_all = "SCREAMING_SNAKE_CASE",
+    frozen,
+    eq,
+    hash
+)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub(crate) enum PyDocParamKind {
+    Arg,
+    NoArgs,
+    OnlyPosBefore,
+    Args,
+    Kwargs,
+}
+
+/// A parameter of a documented function.
+#[pyclass(module = "xingque", name = "DocParam", frozen)]
+pub(crate) struct PyDocParam(DocParam);
+
+#[pymethods]
+impl PyDocParam {
+    fn __repr__(&self) -> String {
+        match &self.0 {
+            DocParam::NoArgs => "<DocParam *>".to_owned(),
+            DocParam::OnlyPosBefore => "<DocParam />".to_owned(),
+            DocParam::Args { .. } => format!("<DocParam *{}>", self.name().unwrap()),
+            DocParam::Kwargs { .. } => format!("<DocParam **{}>", self.name().unwrap()),
+            DocParam::Arg { name, .. } => format!("<DocParam {}>", name),
+        }
+    }
+
+    #[getter]
+    fn kind(&self) -> PyDocParamKind {
+        match &self.0 {
+            DocParam::Arg { .. } => PyDocParamKind::Arg,
+            DocParam::NoArgs => PyDocParamKind::NoArgs,
+            DocParam::OnlyPosBefore => PyDocParamKind::OnlyPosBefore,
+            DocParam::Args { .. } => PyDocParamKind::Args,
+            DocParam::Kwargs { .. } => PyDocParamKind::Kwargs,
+        }
+    }
+
+    /// The name of the parameter, without any leading `*` or `**`, or `None`
+    /// for `*` and `/`.
+    #[getter]
+    fn name(&self) -> Option<&str> {
+        match &self.0 {
+            DocParam::Arg { name, .. } => Some(name),
+            // these are spelled with the stars for `def`s but not for native
+            // functions
+            DocParam::Args { name, .. } | DocParam::Kwargs { name, .. } => {
+                Some(name.trim_start_matches('*'))
+            }
+            DocParam::NoArgs | DocParam::OnlyPosBefore => None,
+        }
+    }
+
+    #[getter]
+    fn docs(&self) -> Option<PyDocString> {
+        self.0.get_doc_string().cloned().map(PyDocString)
+    }
+
+    /// The type of the parameter rendered as a Starlark type expression, or
+    /// `None` for `*` and `/`.
+    #[getter]
+    fn r#type(&self) -> Option<String> {
+        match &self.0 {
+            DocParam::Arg { typ, .. }
+            | DocParam::Args { typ, .. }
+            | DocParam::Kwargs { typ, .. } => Some(typ.to_string()),
+            DocParam::NoArgs | DocParam::OnlyPosBefore => None,
+        }
+    }
+
+    /// The default value of the parameter as source text, if any.
+    #[getter]
+    fn default_value(&self) -> Option<&str> {
+        match &self.0 {
+            DocParam::Arg { default_value, .. } => default_value.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// The return value of a documented function.
+#[pyclass(module = "xingque", name = "DocReturn", frozen)]
+pub(crate) struct PyDocReturn(DocReturn);
+
+#[pymethods]
+impl PyDocReturn {
+    #[getter]
+    fn docs(&self) -> Option<PyDocString> {
+        docs_to_py(&self.0.docs)
+    }
+
+    #[getter]
+    fn r#type(&self) -> String {
+        self.0.typ.to_string()
+    }
+}
+
+/// Documentation of a function.
+#[pyclass(module = "xingque", name = "DocFunction", frozen)]
+pub(crate) struct PyDocFunction(DocFunction);
+
+#[pymethods]
+impl PyDocFunction {
+    #[getter]
+    fn docs(&self) -> Option<PyDocString> {
+        docs_to_py(&self.0.docs)
+    }
+
+    #[getter]
+    fn params(&self) -> Vec<PyDocParam> {
+        self.0.params.iter().cloned().map(PyDocParam).collect()
+    }
+
+    #[getter]
+    fn ret(&self) -> PyDocReturn {
+        PyDocReturn(self.0.ret.clone())
+    }
+
+    /// The type this function constructs, if it acts as a type.
+    #[getter]
+    fn as_type(&self) -> Option<String> {
+        self.0.as_type.as_ref().map(ToString::to_string)
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&DocItem::Function(self.0.clone()))
+    }
+}
+
+/// Documentation of a value that is not a function.
+#[pyclass(module = "xingque", name = "DocProperty", frozen)]
+pub(crate) struct PyDocProperty(DocProperty);
+
+#[pymethods]
+impl PyDocProperty {
+    #[getter]
+    fn docs(&self) -> Option<PyDocString> {
+        docs_to_py(&self.0.docs)
+    }
+
+    #[getter]
+    fn r#type(&self) -> String {
+        self.0.typ.to_string()
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&DocItem::Property(self.0.clone()))
+    }
+}
+
+/// Documentation of an object with named members, such as a struct.
+#[pyclass(module = "xingque", name = "DocObject", frozen)]
+pub(crate) struct PyDocObject(DocObject);
+
+#[pymethods]
+impl PyDocObject {
+    #[getter]
+    fn docs(&self) -> Option<PyDocString> {
+        docs_to_py(&self.0.docs)
+    }
+
+    /// The members as a dict of name to `DocFunction` or `DocProperty`.
+    #[getter]
+    fn members<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        members_to_py(py, &self.0.members)
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&DocItem::Object(self.0.clone()))
+    }
+}
+
+/// Documentation of a module and its top-level members.
+#[pyclass(module = "xingque", name = "DocModule", frozen)]
+pub(crate) struct PyDocModule(DocModule);
+
+impl From<DocModule> for PyDocModule {
+    fn from(value: DocModule) -> Self {
+        Self(value)
+    }
+}
+
+#[pymethods]
+impl PyDocModule {
+    #[getter]
+    fn docs(&self) -> Option<PyDocString> {
+        docs_to_py(&self.0.docs)
+    }
+
+    /// The members as a dict of name to `DocFunction`, `DocProperty` or
+    /// `DocObject`.
+    #[getter]
+    fn members<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        members_to_py(py, &self.0.members)
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&DocItem::Module(self.0.clone()))
+    }
+
+    /// Renders the documentation as a Markdown or HTML reference page.
+    ///
+    /// Names written in backticks in docstrings, and names in types, link to
+    /// the member of that name, or to the URL given for it in `links`, e.g.
+    /// for symbols of modules loaded by this one.
+    #[pyo3(signature = (format = "markdown", *, title = None, links = None))]
+    fn render(
+        &self,
+        format: &str,
+        title: Option<&str>,
+        links: Option<HashMap<String, String>>,
+    ) -> PyResult<String> {
+        Ok(render_module(
+            &self.0,
+            DocFormat::from_name(format)?,
+            title,
+            links.unwrap_or_default(),
+        ))
+    }
+}
+
+fn raw_docstring(body: &AstStmt) -> Option<&str> {
+    let first = match &body.node {
+        StmtP::Statements(stmts) => stmts.first()?,
+        _ => body,
+    };
+    match &first.node {
+        StmtP::Expression(AstExpr {
+            node: ExprP::Literal(AstLiteral::String(s)),
+            ..
+        }) => Some(&s.node),
+        _ => None,
+    }
+}
+
+fn type_expr_to_ty(py: Python<'_>, codemap: &CodeMap, x: &Option<Box<AstTypeExpr>>) -> Ty {
+    // types that can't be resolved without evaluating the module, such as
+    // records defined in it, are documented as `typing.Any`
+    x.as_ref()
+        .and_then(|x| ty_from_type_expr(py, codemap.source_span(x.span)).ok())
+        .unwrap_or_else(Ty::any)
+}
+
+fn param_to_doc(py: Python<'_>, codemap: &CodeMap, param: &AstParameter) -> DocParam {
+    match &param.node {
+        ParameterP::Normal(name, ty) => DocParam::Arg {
+            name: name.ident.clone(),
+            docs: None,
+            typ: type_expr_to_ty(py, codemap, ty),
+            default_value: None,
+        },
+        ParameterP::WithDefaultValue(name, ty, default) => DocParam::Arg {
+            name: name.ident.clone(),
+            docs: None,
+            typ: type_expr_to_ty(py, codemap, ty),
+            default_value: Some(codemap.source_span(default.span).to_owned()),
+        },
+        ParameterP::NoArgs => DocParam::NoArgs,
+        ParameterP::Args(name, ty) => DocParam::Args {
+            name: name.ident.clone(),
+            docs: None,
+            typ: type_expr_to_ty(py, codemap, ty),
+        },
+        ParameterP::KwArgs(name, ty) => DocParam::Kwargs {
+            name: name.ident.clone(),
+            docs: None,
+            typ: type_expr_to_ty(py, codemap, ty),
+        },
+    }
+}
+
+/// Extracts the documentation of a `def` from its source.
+pub(crate) fn def_documentation(
+    py: Python<'_>,
+    codemap: &CodeMap,
+    def: &DefP<AstNoPayload>,
+) -> DocFunction {
+    DocFunction::from_docstring(
+        DocStringKind::Starlark,
+        def.params
+            .iter()
+            .map(|p| param_to_doc(py, codemap, p))
+            .collect(),
+        type_expr_to_ty(py, codemap, &def.return_type),
+        raw_docstring(&def.body),
+        None,
+    )
+}
+
+/// Extracts the documentation of a module from its source, without evaluating
+/// it.
+///
+/// Only the public top-level bindings are documented, like for a frozen
+/// module.
+pub(crate) fn ast_module_documentation(py: Python<'_>, ast: &AstModule) -> DocModule {
+    let codemap = ast.codemap();
+    let mut members = SmallMap::new();
+    let mut add = |name: &str, member: DocMember| {
+        if !name.starts_with('_') {
+            members.insert(name.to_owned(), member);
+        }
+    };
+    for stmt in top_level_stmts(ast.statement()) {
+        match &stmt.node {
+            StmtP::Def(x) => add(
+                &x.name.ident,
+                DocMember::Function(def_documentation(py, codemap, x)),
+            ),
+            StmtP::Assign(x) => x.lhs.visit_lvalue(|x| {
+                add(
+                    &x.ident,
+                    DocMember::Property(DocProperty {
+                        docs: None,
+                        typ: Ty::any(),
+                    }),
+                )
+            }),
+            _ => {}
+        }
+    }
+    DocModule {
+        docs: raw_docstring(ast.statement())
+            .and_then(|x| DocString::from_docstring(DocStringKind::Starlark, x)),
+        members,
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use starlark::collections::SmallMap;
+use starlark::docs::{DocFunction, DocMember, DocModule, DocParam, DocProperty, DocString};
+use starlark::typing::Ty;
+
+/// Functions with more parameters than this have their signature written over
+/// multiple lines, as in starlark-rust's own renderer.
+const MAX_ARGS_BEFORE_MULTILINE: usize = 3;
+const MAX_LENGTH_BEFORE_MULTILINE: usize = 80;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl DocFormat {
+    pub(crate) fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "markdown" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown documentation format '{}', expected 'markdown' or 'html'",
+                name
+            ))),
+        }
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn param_name(param: &DocParam) -> Option<String> {
+    match param {
+        DocParam::Arg { name, .. } => Some(name.clone()),
+        // the stars are part of the name for `def`s but not native functions
+        DocParam::Args { name, .. } => Some(format!("*{}", name.trim_start_matches('*'))),
+        DocParam::Kwargs { name, .. } => Some(format!("**{}", name.trim_start_matches('*'))),
+        DocParam::NoArgs | DocParam::OnlyPosBefore => None,
+    }
+}
+
+fn is_any(ty: &Ty) -> bool {
+    *ty == Ty::any()
+}
+
+fn type_suffix(prefix: &str, ty: &Ty) -> String {
+    if is_any(ty) {
+        String::new()
+    } else {
+        format!("{}{}", prefix, ty)
+    }
+}
+
+fn doc_text(docs: &DocString) -> String {
+    match &docs.details {
+        Some(details) => format!("{}\n\n{}", docs.summary, details),
+        None => docs.summary.clone(),
+    }
+}
+
+/// Renders a `DocModule` into a reference page.
+///
+/// Names of members written in backticks in docstrings, or used in types,
+/// are linked to their documentation, either on the same page or at the URL
+/// given for them in `links`.
+struct Renderer<'a> {
+    format: DocFormat,
+    links: &'a HashMap<String, String>,
+    out: String,
+}
+
+impl<'a> Renderer<'a> {
+    fn write(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn link_for(&self, name: &str) -> Option<&str> {
+        let name = name.strip_suffix("()").unwrap_or(name);
+        self.links.get(name).map(String::as_str)
+    }
+
+    /// Text in inline code, linked if it names a documented symbol.
+    fn code(&self, s: &str) -> String {
+        self.code_link(s, self.link_for(s))
+    }
+
+    fn code_link(&self, s: &str, link: Option<&str>) -> String {
+        match (self.format, link) {
+            (DocFormat::Markdown, None) => format!("`{}`", s),
+            (DocFormat::Markdown, Some(url)) => format!("[`{}`]({})", s, url),
+            (DocFormat::Html, None) => format!("<code>{}</code>", escape_html(s)),
+            (DocFormat::Html, Some(url)) => format!(
+                "<a href=\"{}\"><code>{}</code></a>",
+                escape_html(url),
+                escape_html(s)
+            ),
+        }
+    }
+
+    /// A type rendered for HTML, with every documented name in it linked.
+    fn html_type(&self, ty: &str) -> String {
+        let mut result = String::new();
+        let mut rest = ty;
+        while let Some(begin) = rest.find(|c: char| c.is_alphabetic() || c == '_') {
+            result.push_str(&escape_html(&rest[..begin]));
+            rest = &rest[begin..];
+            let end = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+            let ident = &rest[..end];
+            match self.links.get(ident) {
+                Some(url) => result.push_str(&format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape_html(url),
+                    escape_html(ident)
+                )),
+                None => result.push_str(&escape_html(ident)),
+            }
+            rest = &rest[end..];
+        }
+        result.push_str(&escape_html(rest));
+        result
+    }
+
+    /// Inline text of a docstring, with code spans linked.
+    fn inline(&self, text: &str) -> String {
+        let parts = text.split('`').collect::<Vec<_>>();
+        // with an unbalanced backtick, there are no code spans
+        if parts.len() % 2 == 0 {
+            return self.plain(text);
+        }
+        let mut result = String::new();
+        for (i, part) in parts.into_iter().enumerate() {
+            if i % 2 == 1 {
+                result.push_str(&self.code(part));
+            } else {
+                result.push_str(&self.plain(part));
+            }
+        }
+        result
+    }
+
+    fn plain(&self, s: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => s.to_owned(),
+            DocFormat::Html => escape_html(s),
+        }
+    }
+
+    /// Writes the text of a docstring as paragraphs, keeping fenced code
+    /// blocks.
+    fn text(&mut self, text: &str) {
+        if self.format == DocFormat::Markdown {
+            let mut in_fence = false;
+            let mut lines = Vec::new();
+            for line in text.lines() {
+                if line.trim_start().starts_with("```") {
+                    in_fence = !in_fence;
+                    lines.push(line.to_owned());
+                } else if in_fence {
+                    lines.push(line.to_owned());
+                } else {
+                    lines.push(self.inline(line));
+                }
+            }
+            self.write(&lines.join("\n"));
+            self.write("\n\n");
+            return;
+        }
+
+        let mut paragraph: Vec<&str> = Vec::new();
+        let mut fence: Option<Vec<&str>> = None;
+        for line in text.lines().chain(Some("")) {
+            if let Some(code) = &mut fence {
+                if line.trim_start().starts_with("```") {
+                    let code = escape_html(&code.join("\n"));
+                    self.write(&format!("<pre><code>{}</code></pre>\n", code));
+                    fence = None;
+                } else {
+                    code.push(line);
+                }
+                continue;
+            }
+            if line.trim().is_empty() || line.trim_start().starts_with("```") {
+                if !paragraph.is_empty() {
+                    let p = self.inline(&paragraph.join("\n"));
+                    self.write(&format!("<p>{}</p>\n", p));
+                    paragraph.clear();
+                }
+                if line.trim_start().starts_with("```") {
+                    fence = Some(Vec::new());
+                }
+            } else {
+                paragraph.push(line);
+            }
+        }
+        if let Some(code) = fence {
+            // unterminated code block
+            let code = escape_html(&code.join("\n"));
+            self.write(&format!("<pre><code>{}</code></pre>\n", code));
+        }
+    }
+
+    fn heading(&mut self, level: usize, anchor: &str, name: &str) {
+        match self.format {
+            DocFormat::Markdown => {
+                self.write(&format!(
+                    "<a id=\"{}\"></a>\n\n{} `{}`\n\n",
+                    escape_html(anchor),
+                    "#".repeat(level),
+                    name
+                ));
+            }
+            DocFormat::Html => {
+                self.write(&format!(
+                    "<h{level} id=\"{}\"><code>{}</code></h{level}>\n",
+                    escape_html(anchor),
+                    escape_html(name),
+                ));
+            }
+        }
+    }
+
+    /// Writes a signature, given as (plain text, HTML) pairs of its parts.
+    fn signature(&mut self, prefix: &str, items: &[(String, String)], suffix: &(String, String)) {
+        let single_line = format!(
+            "{}({}){}",
+            prefix,
+            items
+                .iter()
+                .map(|x| x.0.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            suffix.0
+        );
+        let multiline = items.len() > MAX_ARGS_BEFORE_MULTILINE
+            || single_line.len() > MAX_LENGTH_BEFORE_MULTILINE;
+        let (open, sep, close) = if multiline {
+            ("(\n    ", ",\n    ", ",\n)")
+        } else {
+            ("(", ", ", ")")
+        };
+        match self.format {
+            DocFormat::Markdown => {
+                let params = items.iter().map(|x| x.0.as_str()).collect::<Vec<_>>();
+                let params = params.join(sep);
+                let close = if items.is_empty() { ")" } else { close };
+                self.write(&format!(
+                    "```python\n{}{}{}{}{}\n```\n\n",
+                    prefix, open, params, close, suffix.0
+                ));
+            }
+            DocFormat::Html => {
+                let params = items.iter().map(|x| x.1.as_str()).collect::<Vec<_>>();
+                let params = params.join(sep);
+                let close = if items.is_empty() { ")" } else { close };
+                self.write(&format!(
+                    "<pre><code>{}{}{}{}{}</code></pre>\n",
+                    escape_html(prefix),
+                    open,
+                    params,
+                    close,
+                    suffix.1
+                ));
+            }
+        }
+    }
+
+    fn typed(&self, name: &str, ty: &Ty) -> (String, String) {
+        let text = format!("{}{}", name, type_suffix(": ", ty));
+        let html = if is_any(ty) {
+            escape_html(name)
+        } else {
+            format!("{}: {}", escape_html(name), self.html_type(&ty.to_string()))
+        };
+        (text, html)
+    }
+
+    fn function(&mut self, level: usize, name: &str, f: &DocFunction) {
+        self.heading(level, name, name);
+        let items = f
+            .params
+            .iter()
+            .map(|p| match p {
+                DocParam::Arg {
+                    typ,
+                    default_value: Some(default),
+                    ..
+                } => {
+                    let (text, html) = self.typed(&param_name(p).unwrap(), typ);
+                    (
+                        format!("{} = {}", text, default),
+                        format!("{} = {}", html, escape_html(default)),
+                    )
+                }
+                DocParam::Arg { typ, .. }
+                | DocParam::Args { typ, .. }
+                | DocParam::Kwargs { typ, .. } => self.typed(&param_name(p).unwrap(), typ),
+                DocParam::NoArgs => ("*".to_owned(), "*".to_owned()),
+                DocParam::OnlyPosBefore => ("/".to_owned(), "/".to_owned()),
+            })
+            .collect::<Vec<_>>();
+        let ret = if is_any(&f.ret.typ) {
+            (String::new(), String::new())
+        } else {
+            (
+                format!(" -> {}", f.ret.typ),
+                format!(" -&gt; {}", self.html_type(&f.ret.typ.to_string())),
+            )
+        };
+        self.signature(&format!("def {}", name), &items, &ret);
+        if let Some(docs) = &f.docs {
+            self.text(&doc_text(docs));
+        }
+
+        let params = f
+            .params
+            .iter()
+            .filter_map(|p| param_name(p).map(|name| (name, p)))
+            .collect::<Vec<_>>();
+        if !params.is_empty() {
+            self.section("Parameters");
+            self.list_begin();
+            for (name, p) in params {
+                let (typ, default, docs) = match p {
+                    DocParam::Arg {
+                        typ,
+                        default_value,
+                        docs,
+                        ..
+                    } => (typ, default_value.as_deref(), docs),
+                    DocParam::Args { typ, docs, .. } | DocParam::Kwargs { typ, docs, .. } => {
+                        (typ, None, docs)
+                    }
+                    DocParam::NoArgs | DocParam::OnlyPosBefore => unreachable!(),
+                };
+                let mut item = self.code_link(&name, None);
+                let mut details = Vec::new();
+                if !is_any(typ) {
+                    details.push(self.code(&typ.to_string()));
+                }
+                if let Some(default) = default {
+                    details.push(format!(
+                        "{} {}",
+                        self.plain("default"),
+                        self.code_link(default, None)
+                    ));
+                }
+                if !details.is_empty() {
+                    item.push_str(&format!(" ({})", details.join(", ")));
+                }
+                if let Some(docs) = docs {
+                    item.push_str(": ");
+                    item.push_str(&self.inline(&doc_text(docs).replace('\n', " ")));
+                }
+                self.list_item(&item);
+            }
+            self.list_end();
+        }
+
+        if f.ret.docs.is_some() || !is_any(&f.ret.typ) {
+            self.section("Returns");
+            let mut item = String::new();
+            if !is_any(&f.ret.typ) {
+                item.push_str(&self.code(&f.ret.typ.to_string()));
+            }
+            if let Some(docs) = &f.ret.docs {
+                if !item.is_empty() {
+                    item.push_str(": ");
+                }
+                item.push_str(&self.inline(&doc_text(docs).replace('\n', " ")));
+            }
+            match self.format {
+                DocFormat::Markdown => self.write(&format!("{}\n\n", item)),
+                DocFormat::Html => self.write(&format!("<p>{}</p>\n", item)),
+            }
+        }
+    }
+
+    fn property(&mut self, level: usize, name: &str, p: &DocProperty) {
+        self.heading(level, name, name);
+        let (text, html) = self.typed(name, &p.typ);
+        match self.format {
+            DocFormat::Markdown => self.write(&format!("```python\n{}\n```\n\n", text)),
+            DocFormat::Html => self.write(&format!("<pre><code>{}</code></pre>\n", html)),
+        }
+        if let Some(docs) = &p.docs {
+            self.text(&doc_text(docs));
+        }
+    }
+
+    fn member(&mut self, level: usize, name: &str, member: &DocMember) {
+        match member {
+            DocMember::Function(f) => self.function(level, name, f),
+            DocMember::Property(p) => self.property(level, name, p),
+        }
+    }
+
+    fn section(&mut self, title: &str) {
+        match self.format {
+            DocFormat::Markdown => self.write(&format!("**{}**\n\n", title)),
+            DocFormat::Html => self.write(&format!("<h4>{}</h4>\n", title)),
+        }
+    }
+
+    fn list_begin(&mut self) {
+        if self.format == DocFormat::Html {
+            self.write("<ul>\n");
+        }
+    }
+
+    fn list_item(&mut self, item: &str) {
+        match self.format {
+            DocFormat::Markdown => self.write(&format!("* {}\n", item)),
+            DocFormat::Html => self.write(&format!("<li>{}</li>\n", item)),
+        }
+    }
+
+    fn list_end(&mut self) {
+        match self.format {
+            DocFormat::Markdown => self.write("\n"),
+            DocFormat::Html => self.write("</ul>\n"),
+        }
+    }
+
+    fn module(&mut self, title: Option<&str>, module: &DocModule) {
+        if let Some(title) = title {
+            match self.format {
+                DocFormat::Markdown => self.write(&format!("# {}\n\n", title)),
+                DocFormat::Html => self.write(&format!("<h1>{}</h1>\n", escape_html(title))),
+            }
+        }
+        if let Some(docs) = &module.docs {
+            self.text(&doc_text(docs));
+        }
+
+        let members = sorted(&module.members);
+        if members.is_empty() {
+            return;
+        }
+        // an index of all members
+        self.list_begin();
+        for (name, member) in &members {
+            let mut item = self.code(name);
+            if let Some(summary) = member.get_doc_summary() {
+                item.push_str(": ");
+                item.push_str(&self.inline(summary));
+            }
+            self.list_item(&item);
+        }
+        self.list_end();
+
+        for (name, member) in members {
+            self.member(2, name, member);
+        }
+    }
+}
+
+fn sorted(members: &SmallMap<String, DocMember>) -> Vec<(&String, &DocMember)> {
+    let mut result = members.iter().collect::<Vec<_>>();
+    result.sort_by(|a, b| a.0.cmp(b.0));
+    result
+}
+
+/// Renders the documentation of a module as a Markdown or HTML page.
+///
+/// `links` maps names of symbols documented elsewhere, such as those of
+/// modules loaded by this one, to the URLs of their documentation.
+pub(crate) fn render_module(
+    module: &DocModule,
+    format: DocFormat,
+    title: Option<&str>,
+    links: HashMap<String, String>,
+) -> String {
+    let mut all_links = HashMap::new();
+    for name in module.members.keys() {
+        all_links.insert(name.clone(), format!("#{}", name));
+    }
+    all_links.extend(links);
+    let mut renderer = Renderer {
+        format,
+        links: &all_links,
+        out: String::new(),
+    };
+    if format == DocFormat::Html {
+        renderer.write(&format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+            escape_html(title.unwrap_or("Documentation"))
+        ));
+    }
+    renderer.module(title, module);
+    if format == DocFormat::Html {
+        renderer.write("</body>\n</html>\n");
+    }
+    let mut result = renderer.out;
+    // no trailing blank lines
+    let len = result.trim_end().len();
+    result.truncate(len);
+    result.push('\n');
+    result
+}
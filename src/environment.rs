@@ -1,8 +1,14 @@
+use std::collections::HashMap;
+
 use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::intern;
 use pyo3::prelude::*;
+use pyo3::types::{PyCFunction, PyDict, PyTuple};
 use starlark::environment::{FrozenModule, Globals, GlobalsBuilder, LibraryExtension, Module};
 use starlark::values::{FrozenStringValue, FrozenValue};
 
+use crate::docs::{render_module, DocFormat, PyDocModule};
+use crate::eval::print_builtins;
 use crate::py2sl::{self, sl_frozen_value_from_py};
 use crate::sl2py::{self, py_from_sl_frozen_value};
 
@@ -133,7 +139,10 @@ impl PyGlobals {
             }
             tmp
         };
-        Ok(Globals::extended_by(&extensions).into())
+        Ok(GlobalsBuilder::extended_by(&extensions)
+            .with(|x| print_builtins(x, &extensions))
+            .build()
+            .into())
     }
 
     fn names(slf: &Bound<'_, Self>) -> PyResult<Py<PyFrozenStringValueIterator>> {
@@ -159,7 +168,27 @@ impl PyGlobals {
         self.0.docstring()
     }
 
-    // TODO: documentation
+    /// Documentation for the globals and all of their members.
+    fn documentation(&self) -> PyDocModule {
+        self.0.documentation().into()
+    }
+
+    /// Renders the documentation as a Markdown or HTML reference page, see
+    /// `DocModule.render`.
+    #[pyo3(signature = (format = "markdown", *, title = None, links = None))]
+    fn render_docs(
+        &self,
+        format: &str,
+        title: Option<&str>,
+        links: Option<HashMap<String, String>>,
+    ) -> PyResult<String> {
+        Ok(render_module(
+            &self.0.documentation(),
+            DocFormat::from_name(format)?,
+            title,
+            links.unwrap_or_default(),
+        ))
+    }
 }
 
 #[pyclass(module = "xingque", name = "_FrozenStringValueIterator")]
@@ -267,7 +296,9 @@ impl PyGlobalsBuilder {
             }
             tmp
         };
-        Ok(GlobalsBuilder::extended_by(&extensions).into())
+        Ok(GlobalsBuilder::extended_by(&extensions)
+            .with(|x| print_builtins(x, &extensions))
+            .into())
     }
 
     fn r#struct(&mut self, name: &str, f: &Bound<'_, PyAny>) -> PyResult<()> {
@@ -280,15 +311,11 @@ impl PyGlobalsBuilder {
             }
         };
 
-        let mut err = None;
+        let mut result = Ok(());
         inner.struct_(name, |gb| {
-            let args = (PySubGlobalsBuilder::new(gb),);
-            err = f.call1(args).err();
+            result = PySubGlobalsBuilder::call_with(f.py(), gb, f);
         });
-        match err {
-            Some(e) => Err(e),
-            None => Ok(()),
-        }
+        result
     }
 
     fn with_<'py>(
@@ -307,12 +334,7 @@ impl PyGlobalsBuilder {
             }
         };
 
-        let args = (PySubGlobalsBuilder::new(inner),);
-        let err = f.call1(args).err();
-        match err {
-            Some(e) => Err(e),
-            None => Ok(slf),
-        }
+        PySubGlobalsBuilder::call_with(slf.py(), inner, f).map(|_| slf)
     }
 
     fn with_struct<'py>(
@@ -346,13 +368,64 @@ impl PyGlobalsBuilder {
                 ))
             }
         };
+        if let Ok(f) = value.downcast::<PyStarlarkFunction>() {
+            return f.get().set_into(inner, name);
+        }
         let heap = inner.frozen_heap();
 
         inner.set(name, sl_frozen_value_from_py(value, heap)?);
         Ok(())
     }
 
-    // TODO: set_function
+    #[pyo3(signature = (
+        name,
+        callable,
+        *,
+        signature = None,
+        docstring = None,
+        parameter_types = None,
+        return_type = None,
+    ))]
+    fn set_function(
+        &mut self,
+        name: &str,
+        callable: &Bound<'_, PyAny>,
+        signature: Option<&Bound<'_, PyAny>>,
+        docstring: Option<String>,
+        parameter_types: Option<&Bound<'_, PyDict>>,
+        return_type: Option<&str>,
+    ) -> PyResult<()> {
+        let inner = match &mut self.0 {
+            Some(inner) => inner,
+            None => {
+                return Err(PyRuntimeError::new_err(
+                    "this GlobalsBuilder has already been consumed",
+                ))
+            }
+        };
+        py2sl::set_py_function(
+            inner,
+            name,
+            callable,
+            signature,
+            docstring,
+            parameter_types,
+            return_type,
+        )
+    }
+
+    /// Decorator registering the decorated function as a Starlark builtin, with
+    /// its signature and types derived from the Python signature and type
+    /// annotations.
+    #[pyo3(signature = (f = None, /, *, name = None, docstring = None))]
+    fn function(
+        slf: &Bound<'_, Self>,
+        f: Option<&Bound<'_, PyAny>>,
+        name: Option<String>,
+        docstring: Option<String>,
+    ) -> PyResult<PyObject> {
+        function_decorator(slf.as_any(), f, name, docstring)
+    }
 
     // TODO: are those necessary?
     //
@@ -361,46 +434,82 @@ impl PyGlobalsBuilder {
     // * set_docstring
 }
 
+/// Implements the `function` decorator of `GlobalsBuilder` and
+/// `_SubGlobalsBuilder`, registering the function with the builder's `set`.
+fn function_decorator(
+    builder: &Bound<'_, PyAny>,
+    f: Option<&Bound<'_, PyAny>>,
+    name: Option<String>,
+    docstring: Option<String>,
+) -> PyResult<PyObject> {
+    let py = builder.py();
+    match f {
+        Some(f) => {
+            let f = Bound::new(py, PyStarlarkFunction::wrap(f, name, docstring)?)?;
+            builder.call_method1(intern!(py, "set"), (f.get().name.as_str(), &f))?;
+            Ok(f.into_any().unbind())
+        }
+        None => {
+            let builder = builder.clone().unbind();
+            decorator(py, move |f| {
+                function_decorator(
+                    builder.bind(f.py()),
+                    Some(f),
+                    name.clone(),
+                    docstring.clone(),
+                )
+            })
+        }
+    }
+}
+
 // necessary for proper ownership maintenance
+//
+// The builder is only lent to us for the duration of a callback, so it is
+// taken away once the callback returns, in case Python kept a reference.
 #[pyclass(module = "xingque", name = "_SubGlobalsBuilder", unsendable)]
-pub(crate) struct PySubGlobalsBuilder(&'static mut GlobalsBuilder);
+pub(crate) struct PySubGlobalsBuilder(Option<&'static mut GlobalsBuilder>);
 
 impl PySubGlobalsBuilder {
-    fn new(ptr: &mut GlobalsBuilder) -> Self {
-        // Safety TODO
-        let ptr: &'static mut GlobalsBuilder = unsafe { ::core::mem::transmute(ptr) };
-        Self(ptr)
+    /// Calls `f` with a sub-builder for `gb`, which becomes unusable when `f`
+    /// returns.
+    fn call_with(py: Python<'_>, gb: &mut GlobalsBuilder, f: &Bound<'_, PyAny>) -> PyResult<()> {
+        // Safety: the reference is dropped below, before `gb` goes out of
+        // scope
+        let gb: &'static mut GlobalsBuilder = unsafe { ::core::mem::transmute(gb) };
+        let sub = Bound::new(py, Self(Some(gb)))?;
+        let result = f.call1((&sub,));
+        sub.borrow_mut().0 = None;
+        result.map(|_| ())
+    }
+
+    fn inner(&mut self) -> PyResult<&mut GlobalsBuilder> {
+        match &mut self.0 {
+            Some(inner) => Ok(inner),
+            None => Err(PyRuntimeError::new_err(
+                "this _SubGlobalsBuilder is only usable within its callback",
+            )),
+        }
     }
 }
 
 #[pymethods]
 impl PySubGlobalsBuilder {
     fn r#struct(&mut self, name: &str, f: &Bound<'_, PyAny>) -> PyResult<()> {
-        let mut err = None;
-        self.0.struct_(name, |gb| {
-            let args = (PySubGlobalsBuilder::new(gb),);
-            err = f.call1(args).err();
+        let mut result = Ok(());
+        self.inner()?.struct_(name, |gb| {
+            result = Self::call_with(f.py(), gb, f);
         });
-        match err {
-            Some(e) => Err(e),
-            None => Ok(()),
-        }
+        result
     }
 
     fn with_<'py>(
         slf: &'py Bound<'py, Self>,
         f: &'py Bound<'py, PyAny>,
     ) -> PyResult<&'py Bound<'py, Self>> {
-        // implement the logic ourselves to avoid having to do ownership dance
         // it's basically just f(self) and return self
-        let mut me = slf.borrow_mut();
-
-        let args = (PySubGlobalsBuilder::new(me.0),);
-        let err = f.call1(args).err();
-        match err {
-            Some(e) => Err(e),
-            None => Ok(slf),
-        }
+        slf.borrow_mut().inner()?;
+        f.call1((slf,)).map(|_| slf)
     }
 
     fn with_struct<'py>(
@@ -417,10 +526,217 @@ impl PySubGlobalsBuilder {
     // to allow for a nested builder
 
     fn set(&mut self, name: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
-        let heap = self.0.frozen_heap();
-        self.0.set(name, sl_frozen_value_from_py(value, heap)?);
+        let inner = self.inner()?;
+        if let Ok(f) = value.downcast::<PyStarlarkFunction>() {
+            return f.get().set_into(inner, name);
+        }
+        let heap = inner.frozen_heap();
+        inner.set(name, sl_frozen_value_from_py(value, heap)?);
         Ok(())
     }
+
+    #[pyo3(signature = (
+        name,
+        callable,
+        *,
+        signature = None,
+        docstring = None,
+        parameter_types = None,
+        return_type = None,
+    ))]
+    fn set_function(
+        &mut self,
+        name: &str,
+        callable: &Bound<'_, PyAny>,
+        signature: Option<&Bound<'_, PyAny>>,
+        docstring: Option<String>,
+        parameter_types: Option<&Bound<'_, PyDict>>,
+        return_type: Option<&str>,
+    ) -> PyResult<()> {
+        py2sl::set_py_function(
+            self.inner()?,
+            name,
+            callable,
+            signature,
+            docstring,
+            parameter_types,
+            return_type,
+        )
+    }
+
+    /// Decorator registering the decorated function as a Starlark builtin, with
+    /// its signature and types derived from the Python signature and type
+    /// annotations.
+    #[pyo3(signature = (f = None, /, *, name = None, docstring = None))]
+    fn function(
+        slf: &Bound<'_, Self>,
+        f: Option<&Bound<'_, PyAny>>,
+        name: Option<String>,
+        docstring: Option<String>,
+    ) -> PyResult<PyObject> {
+        function_decorator(slf.as_any(), f, name, docstring)
+    }
+}
+
+/// A Python function marked for use as a Starlark builtin.
+///
+/// The Starlark signature and types are derived from the Python signature and
+/// type annotations when the function is decorated. Calling it from Python
+/// calls the wrapped function as usual.
+#[pyclass(module = "xingque", name = "StarlarkFunction", frozen)]
+pub(crate) struct PyStarlarkFunction {
+    callable: PyObject,
+    name: String,
+    docstring: Option<String>,
+    parameter_types: Py<PyDict>,
+    return_type: Option<String>,
+}
+
+impl PyStarlarkFunction {
+    fn wrap(
+        f: &Bound<'_, PyAny>,
+        name: Option<String>,
+        docstring: Option<String>,
+    ) -> PyResult<Self> {
+        let py = f.py();
+        // re-decorating only overrides what is explicitly given
+        if let Ok(f) = f.downcast::<Self>() {
+            let f = f.get();
+            return Ok(Self {
+                callable: f.callable.clone_ref(py),
+                name: name.unwrap_or_else(|| f.name.clone()),
+                docstring: docstring.or_else(|| f.docstring.clone()),
+                parameter_types: f.parameter_types.bind(py).copy()?.unbind(),
+                return_type: f.return_type.clone(),
+            });
+        }
+
+        let name = match name {
+            Some(x) => x,
+            None => f.getattr(intern!(py, "__name__"))?.extract()?,
+        };
+        let (parameter_types, return_type) = py2sl::types_from_annotations(f)?;
+        Ok(Self {
+            callable: f.clone().unbind(),
+            name,
+            docstring,
+            parameter_types: parameter_types.unbind(),
+            return_type,
+        })
+    }
+
+    fn set_into(&self, builder: &mut GlobalsBuilder, name: &str) -> PyResult<()> {
+        Python::with_gil(|py| {
+            py2sl::set_py_function(
+                builder,
+                name,
+                self.callable.bind(py),
+                None,
+                self.docstring.clone(),
+                Some(self.parameter_types.bind(py)),
+                self.return_type.as_deref(),
+            )
+        })
+    }
+}
+
+#[pymethods]
+impl PyStarlarkFunction {
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        let class_name = slf.get_type().qualname()?;
+        Ok(format!("<{} {}>", class_name, slf.get().name))
+    }
+
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python<'_>,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
+        self.callable.bind(py).call(args, kwargs).map(Bound::unbind)
+    }
+
+    /// Binds the wrapped function like the function itself would be bound,
+    /// so that decorated methods work as usual. Accessed on the class, the
+    /// decorated function itself is returned, so it can still be registered.
+    fn __get__(
+        slf: &Bound<'_, Self>,
+        obj: Option<&Bound<'_, PyAny>>,
+        objtype: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let py = slf.py();
+        let callable = slf.get().callable.bind(py);
+        let obj = match obj {
+            Some(x) if !x.is_none() && callable.hasattr(intern!(py, "__get__"))? => x,
+            _ => return Ok(slf.clone().into_any().unbind()),
+        };
+        callable
+            .call_method1(intern!(py, "__get__"), (obj, objtype))
+            .map(Bound::unbind)
+    }
+
+    #[getter]
+    fn __wrapped__(&self, py: Python<'_>) -> PyObject {
+        self.callable.clone_ref(py)
+    }
+
+    /// The name the function is registered under by default.
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The docstring override, if any; the wrapped function's own docstring is
+    /// used otherwise.
+    #[getter]
+    fn docstring(&self) -> Option<&str> {
+        self.docstring.as_deref()
+    }
+
+    /// The Starlark type expressions of the annotated parameters.
+    #[getter]
+    fn parameter_types<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.parameter_types.bind(py).copy()
+    }
+
+    /// The Starlark type expression of the return type, if annotated.
+    #[getter]
+    fn return_type(&self) -> Option<&str> {
+        self.return_type.as_deref()
+    }
+}
+
+/// Decorator marking a Python function for use as a Starlark builtin, to be
+/// registered later with `GlobalsBuilder.set`.
+#[pyfunction]
+#[pyo3(signature = (f = None, /, *, name = None, docstring = None))]
+pub(crate) fn starlark_function(
+    py: Python<'_>,
+    f: Option<&Bound<'_, PyAny>>,
+    name: Option<String>,
+    docstring: Option<String>,
+) -> PyResult<PyObject> {
+    match f {
+        Some(f) => Ok(PyStarlarkFunction::wrap(f, name, docstring)?.into_py(py)),
+        None => decorator(py, move |f| {
+            Ok(PyStarlarkFunction::wrap(f, name.clone(), docstring.clone())?.into_py(f.py()))
+        }),
+    }
+}
+
+// for supporting the `@decorator(option=...)` form
+fn decorator(
+    py: Python<'_>,
+    f: impl Fn(&Bound<'_, PyAny>) -> PyResult<PyObject> + Send + 'static,
+) -> PyResult<PyObject> {
+    let closure = move |args: &Bound<'_, PyTuple>, _: Option<&Bound<'_, PyDict>>| {
+        let (x,): (Bound<'_, PyAny>,) = args.extract()?;
+        f(&x)
+    };
+    Ok(PyCFunction::new_closure_bound(py, None, None, closure)?
+        .into_any()
+        .unbind())
 }
 
 #[pyclass(module = "xingque", name = "FrozenModule", frozen)]
@@ -466,7 +782,28 @@ impl PyFrozenModule {
         self.0.describe()
     }
 
-    // TODO: documentation
+    /// Documentation for the module and its public top-level values.
+    fn documentation(&self) -> PyDocModule {
+        self.0.documentation().into()
+    }
+
+    /// Renders the documentation as a Markdown or HTML reference page, see
+    /// `DocModule.render`.
+    #[pyo3(signature = (format = "markdown", *, title = None, links = None))]
+    fn render_docs(
+        &self,
+        format: &str,
+        title: Option<&str>,
+        links: Option<HashMap<String, String>>,
+    ) -> PyResult<String> {
+        Ok(render_module(
+            &self.0.documentation(),
+            DocFormat::from_name(format)?,
+            title,
+            links.unwrap_or_default(),
+        ))
+    }
+
     // TODO: aggregated_heap_profile_info
 
     #[getter]
@@ -533,7 +870,7 @@ impl PyModule {
 
     fn set(&mut self, name: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
         let inner = self.inner_mut()?;
-        inner.set(name, py2sl::sl_value_from_py(value, inner.heap()));
+        inner.set(name, py2sl::sl_value_from_py(value, inner.heap())?);
         Ok(())
     }
 
@@ -552,7 +889,7 @@ impl PyModule {
     #[setter]
     fn set_extra_value(&self, value: &Bound<'_, PyAny>) -> PyResult<()> {
         let inner = self.inner()?;
-        inner.set_extra_value(py2sl::sl_value_from_py(value, inner.heap()));
+        inner.set_extra_value(py2sl::sl_value_from_py(value, inner.heap())?);
         Ok(())
     }
 }
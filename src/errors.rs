@@ -0,0 +1,833 @@
+// create_exception! in PyO3 0.22.0 checks for a `gil-refs` feature in the
+// calling crate, which we don't have
+#![allow(unexpected_cfgs)]
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyType};
+use starlark::codemap::{CodeMap, FileSpan, ResolvedPos, ResolvedSpan};
+use starlark::errors::{EvalSeverity, Frame, Lint};
+use starlark::eval::CallStack;
+use starlark::ErrorKind;
+
+use crate::codemap::PyFileSpan;
+
+create_exception!(
+    xingque,
+    StarlarkError,
+    PyException,
+    "Base class of all errors reported by Starlark."
+);
+create_exception!(
+    xingque,
+    StarlarkParseError,
+    StarlarkError,
+    "Error when lexing or parsing Starlark source."
+);
+create_exception!(
+    xingque,
+    StarlarkTypeCheckError,
+    StarlarkError,
+    "Error found by the static typechecker."
+);
+create_exception!(
+    xingque,
+    StarlarkFailError,
+    StarlarkError,
+    "An explicit `fail` invocation."
+);
+create_exception!(
+    xingque,
+    StarlarkValueError,
+    StarlarkError,
+    "An error approximately associated with a value.\n\nIncludes unsupported operations, missing attributes, things of that sort."
+);
+create_exception!(
+    xingque,
+    StarlarkFunctionError,
+    StarlarkError,
+    "Errors relating to the way a function is called (wrong number of args, etc.)"
+);
+create_exception!(
+    xingque,
+    StarlarkScopeError,
+    StarlarkError,
+    "Out of scope variables and similar name resolution errors."
+);
+create_exception!(
+    xingque,
+    StarlarkNativeError,
+    StarlarkError,
+    "Error returned by native code during evaluation, or not otherwise classified.\n\nThis includes errors found by static typechecking enabled on an\n`Evaluator`, which starlark-rust doesn't tell apart."
+);
+create_exception!(
+    xingque,
+    StarlarkInternalError,
+    StarlarkError,
+    "Indicates a logic bug in starlark-rust."
+);
+
+/// A frame of the call-stack.
+#[pyclass(module = "xingque", name = "Frame", frozen)]
+#[derive(Clone)]
+pub(crate) struct PyFrame(Frame);
+
+impl From<Frame> for PyFrame {
+    fn from(value: Frame) -> Self {
+        Self(value)
+    }
+}
+
+#[pymethods]
+impl PyFrame {
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        let class_name = slf.get_type().qualname()?;
+        Ok(format!("<{} {}>", class_name, slf.get().0))
+    }
+
+    /// The name of the entry on the call-stack.
+    #[getter]
+    fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// The location this frame was called from, if available.
+    #[getter]
+    fn location(&self) -> Option<PyFileSpan> {
+        self.0.location.clone().map(Into::into)
+    }
+}
+
+/// A standardised set of severities.
+#[pyclass(
+    module = "xingque",
+    name = "EvalSeverity",
+    rename_all = "SCREAMING_SNAKE_CASE",
+    frozen,
+    eq,
+    hash
+)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum PyEvalSeverity {
+    /// An error while the program was being parsed.
+    Error,
+    /// The program parsed, but might not execute without error.
+    Warning,
+    /// This could be changed, but is not a blocking problem.
+    Advice,
+    /// This should be ignored
+    Disabled,
+}
+
+impl From<EvalSeverity> for PyEvalSeverity {
+    fn from(value: EvalSeverity) -> Self {
+        match value {
+            EvalSeverity::Error => Self::Error,
+            EvalSeverity::Warning => Self::Warning,
+            EvalSeverity::Advice => Self::Advice,
+            EvalSeverity::Disabled => Self::Disabled,
+        }
+    }
+}
+
+/// A lint produced by `AstModule.lint`.
+#[pyclass(module = "xingque", name = "Lint", frozen)]
+pub(crate) struct PyLint(Lint);
+
+impl From<Lint> for PyLint {
+    fn from(value: Lint) -> Self {
+        Self(value)
+    }
+}
+
+#[pymethods]
+impl PyLint {
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        let class_name = slf.get_type().qualname()?;
+        Ok(format!("<{} {}>", class_name, slf.get().0))
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    /// Which code location does this lint refer to.
+    #[getter]
+    fn location(&self) -> PyFileSpan {
+        self.0.location.clone().into()
+    }
+
+    /// kebab-case constant describing this issue, e.g. `missing-return`.
+    #[getter]
+    fn short_name(&self) -> &str {
+        &self.0.short_name
+    }
+
+    /// Is this code highly-likely to be wrong, rather
+    /// than merely stylistically non-ideal.
+    #[getter]
+    fn severity(&self) -> PyEvalSeverity {
+        self.0.severity.into()
+    }
+
+    /// A description of the underlying problem.
+    #[getter]
+    fn problem(&self) -> &str {
+        &self.0.problem
+    }
+
+    /// The source code at `location`.
+    #[getter]
+    fn original(&self) -> &str {
+        &self.0.original
+    }
+}
+
+/// A problem found in Starlark code, which can be rendered together with the
+/// offending source lines.
+#[pyclass(module = "xingque", name = "Diagnostic", frozen)]
+pub(crate) struct PyDiagnostic {
+    message: String,
+    span: Option<FileSpan>,
+    severity: PyEvalSeverity,
+    name: Option<String>,
+    label: Option<String>,
+    secondary_labels: Vec<(FileSpan, String)>,
+    notes: Vec<String>,
+}
+
+#[pymethods]
+impl PyDiagnostic {
+    #[new]
+    #[pyo3(signature = (
+        message,
+        span = None,
+        *,
+        severity = PyEvalSeverity::Error,
+        name = None,
+        label = None,
+        secondary_labels = Vec::new(),
+        notes = Vec::new(),
+    ))]
+    fn py_new(
+        message: String,
+        span: Option<PyFileSpan>,
+        severity: PyEvalSeverity,
+        name: Option<String>,
+        label: Option<String>,
+        secondary_labels: Vec<(PyFileSpan, String)>,
+        notes: Vec<String>,
+    ) -> Self {
+        Self {
+            message,
+            span: span.map(|x| x.0),
+            severity,
+            name,
+            label,
+            secondary_labels: secondary_labels
+                .into_iter()
+                .map(|(span, label)| (span.0, label))
+                .collect(),
+            notes,
+        }
+    }
+
+    /// Makes a diagnostic out of an exception, usually a `StarlarkError`
+    /// raised when parsing, typechecking or evaluating.
+    ///
+    /// The calls leading to the error are labeled too, if the exception has
+    /// its `call_stack` recorded. An exception without a span of its own is
+    /// then pointed at the innermost call.
+    #[staticmethod]
+    fn from_error(e: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let py = e.py();
+        let (message, mut span) = if e.is_instance_of::<StarlarkError>() {
+            let message = e.getattr(intern!(py, "message"))?.extract()?;
+            let span: Option<PyFileSpan> = e.getattr(intern!(py, "span"))?.extract()?;
+            (message, span.map(|x| x.0))
+        } else {
+            (e.str()?.to_cow()?.into_owned(), None)
+        };
+
+        let call_stack: Option<Vec<PyFrame>> = match e.getattr(intern!(py, "call_stack")) {
+            Ok(x) => x.extract().ok().flatten(),
+            Err(_) => None,
+        };
+        let mut frames: Vec<_> = call_stack
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|x| Some((x.0.location?, x.0.name)))
+            .collect();
+        if span.is_none() {
+            span = frames.pop().map(|x| x.0);
+        }
+        let secondary_labels = frames
+            .into_iter()
+            // the failing call is already underlined
+            .filter(|x| Some(&x.0) != span.as_ref())
+            .map(|(location, name)| (location, format!("in call to `{}`", name)))
+            .collect();
+
+        Ok(Self {
+            span,
+            secondary_labels,
+            ..Self::simple(message)
+        })
+    }
+
+    /// Makes a diagnostic out of a lint, named after the lint's short name.
+    #[staticmethod]
+    fn from_lint(lint: &PyLint) -> Self {
+        let lint = &lint.0;
+        Self {
+            span: Some(lint.location.clone()),
+            severity: lint.severity.into(),
+            name: Some(lint.short_name.clone()),
+            ..Self::simple(lint.problem.clone())
+        }
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        let class_name = slf.get_type().qualname()?;
+        let me = slf.get();
+        Ok(format!(
+            "<{} {}: {}>",
+            class_name,
+            severity_name(me.severity),
+            me.message
+        ))
+    }
+
+    fn __str__(&self) -> String {
+        self.render(false)
+    }
+
+    /// The description of the problem.
+    #[getter]
+    fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Where the problem is, if known.
+    #[getter]
+    fn span(&self) -> Option<PyFileSpan> {
+        self.span.clone().map(Into::into)
+    }
+
+    #[getter]
+    fn severity(&self) -> PyEvalSeverity {
+        self.severity
+    }
+
+    /// A short identifier of the kind of the problem, e.g. the short name of
+    /// a lint.
+    #[getter]
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The text shown next to the underlined `span`.
+    #[getter]
+    fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Other locations related to the problem, with their labels.
+    #[getter]
+    fn secondary_labels(&self) -> Vec<(PyFileSpan, String)> {
+        self.secondary_labels
+            .iter()
+            .map(|(span, label)| (span.clone().into(), label.clone()))
+            .collect()
+    }
+
+    /// Additional notes shown after the source snippets.
+    #[getter]
+    fn notes(&self) -> Vec<String> {
+        self.notes.clone()
+    }
+
+    /// Renders the diagnostic in the style of rustc, with the source lines of
+    /// the locations involved, optionally colored with ANSI escape codes.
+    #[pyo3(signature = (color = false))]
+    fn render(&self, color: bool) -> String {
+        let mut annotations = Vec::new();
+        if let Some(span) = &self.span {
+            annotations.push(Annotation {
+                span,
+                primary: true,
+                label: self.label.as_deref().unwrap_or(""),
+            });
+        }
+        for (span, label) in &self.secondary_labels {
+            annotations.push(Annotation {
+                span,
+                primary: false,
+                label,
+            });
+        }
+        DiagnosticRenderer {
+            diagnostic: self,
+            color,
+        }
+        .render(&annotations)
+    }
+}
+
+impl PyDiagnostic {
+    fn simple(message: String) -> Self {
+        Self {
+            message,
+            span: None,
+            severity: PyEvalSeverity::Error,
+            name: None,
+            label: None,
+            secondary_labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+}
+
+fn severity_name(severity: PyEvalSeverity) -> &'static str {
+    match severity {
+        PyEvalSeverity::Error => "error",
+        PyEvalSeverity::Warning => "warning",
+        PyEvalSeverity::Advice => "advice",
+        PyEvalSeverity::Disabled => "disabled",
+    }
+}
+
+/// Resolves `span`, moving an end at the start of a line back to the end of
+/// the previous line, so that spans ending with a newline don't spill over.
+fn resolve_span(span: &FileSpan) -> ResolvedSpan {
+    let mut resolved = span.resolve_span();
+    if resolved.end.column == 0 && resolved.end.line > resolved.begin.line {
+        let line = resolved.end.line - 1;
+        resolved.end = ResolvedPos {
+            line,
+            column: span.file.source_line(line).chars().count(),
+        };
+    }
+    resolved
+}
+
+struct Annotation<'a> {
+    span: &'a FileSpan,
+    primary: bool,
+    label: &'a str,
+}
+
+struct DiagnosticRenderer<'a> {
+    diagnostic: &'a PyDiagnostic,
+    color: bool,
+}
+
+// width of a tab in rendered source lines
+const TAB_WIDTH: usize = 4;
+
+impl DiagnosticRenderer<'_> {
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn severity_style(&self) -> &'static str {
+        match self.diagnostic.severity {
+            PyEvalSeverity::Error => "1;31",
+            PyEvalSeverity::Warning => "1;33",
+            PyEvalSeverity::Advice => "1;36",
+            PyEvalSeverity::Disabled => "1;32",
+        }
+    }
+
+    fn render(&self, annotations: &[Annotation]) -> String {
+        let d = self.diagnostic;
+        let mut out = String::new();
+
+        let mut title = severity_name(d.severity).to_owned();
+        if let Some(name) = &d.name {
+            title = format!("{}[{}]", title, name);
+        }
+        out.push_str(&self.paint(self.severity_style(), &title));
+        out.push_str(&self.paint("1", &format!(": {}", d.message)));
+        out.push('\n');
+
+        // annotations grouped by file, the file of the primary span first
+        let mut groups: Vec<(&CodeMap, Vec<&Annotation>)> = Vec::new();
+        for annotation in annotations {
+            let file = &annotation.span.file;
+            match groups.iter_mut().find(|(x, _)| *x == file) {
+                Some((_, group)) => group.push(annotation),
+                None => groups.push((file, vec![annotation])),
+            }
+        }
+
+        let width = annotations
+            .iter()
+            .map(|x| (resolve_span(x.span).end.line + 1).to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = |text: &str| self.paint("1;34", &format!("{:>width$} |", text));
+
+        for (i, (file, group)) in groups.iter().enumerate() {
+            let first = group[0].span.resolve_span().begin;
+            let arrow = if i == 0 { "-->" } else { ":::" };
+            out.push_str(&format!(
+                "{}{} {}:{}:{}\n",
+                " ".repeat(width),
+                self.paint("1;34", arrow),
+                file.filename(),
+                first.line + 1,
+                first.column + 1,
+            ));
+            out.push_str(&gutter(""));
+            out.push('\n');
+            self.render_lines(&mut out, file, group, &gutter);
+            out.push_str(&gutter(""));
+            out.push('\n');
+        }
+
+        for note in &d.notes {
+            out.push_str(&format!(
+                "{} {} note: {}\n",
+                " ".repeat(width),
+                self.paint("1;34", "="),
+                note
+            ));
+        }
+        out
+    }
+
+    fn render_lines(
+        &self,
+        out: &mut String,
+        file: &CodeMap,
+        group: &[&Annotation],
+        gutter: &dyn Fn(&str) -> String,
+    ) {
+        // the lines to show, with long multi-line spans elided in the middle
+        let mut lines = BTreeSet::new();
+        for annotation in group {
+            let span = resolve_span(annotation.span);
+            if span.end.line - span.begin.line > 3 {
+                lines.extend([span.begin.line, span.begin.line + 1, span.end.line]);
+            } else {
+                lines.extend(span.begin.line..=span.end.line);
+            }
+        }
+
+        let mut prev: Option<usize> = None;
+        for line in lines {
+            if let Some(prev) = prev {
+                if line > prev + 2 {
+                    out.push_str(&self.paint("1;34", "..."));
+                    out.push('\n');
+                } else if line == prev + 2 {
+                    self.render_line(out, file, prev + 1, &[], gutter);
+                }
+            }
+            self.render_line(out, file, line, group, gutter);
+            prev = Some(line);
+        }
+    }
+
+    fn render_line(
+        &self,
+        out: &mut String,
+        file: &CodeMap,
+        line: usize,
+        group: &[&Annotation],
+        gutter: &dyn Fn(&str) -> String,
+    ) {
+        let text: Vec<char> = file.source_line(line).chars().collect();
+        // display width of the first `n` characters of the line
+        let columns = |n: usize| -> usize {
+            text[..n.min(text.len())]
+                .iter()
+                .map(|&c| if c == '\t' { TAB_WIDTH } else { 1 })
+                .sum()
+        };
+        let shown: String = text
+            .iter()
+            .map(|&c| match c {
+                '\t' => " ".repeat(TAB_WIDTH),
+                c => c.to_string(),
+            })
+            .collect();
+        out.push_str(&gutter(&(line + 1).to_string()));
+        if !shown.is_empty() {
+            out.push(' ');
+            out.push_str(&shown);
+        }
+        out.push('\n');
+
+        let mut underlines: Vec<_> = group
+            .iter()
+            .filter_map(|annotation| {
+                let span = resolve_span(annotation.span);
+                if line < span.begin.line || line > span.end.line {
+                    return None;
+                }
+                let begin = if line == span.begin.line {
+                    span.begin.column
+                } else {
+                    text.iter().take_while(|c| c.is_whitespace()).count()
+                };
+                let end = if line == span.end.line {
+                    span.end.column
+                } else {
+                    text.len()
+                };
+                if line != span.begin.line && line != span.end.line {
+                    return None;
+                }
+                // multi-line spans are labelled on their last line only
+                let label = if line == span.end.line {
+                    annotation.label
+                } else {
+                    ""
+                };
+                Some((begin, end, annotation.primary, label))
+            })
+            .collect();
+        underlines.sort_by_key(|&(begin, _, primary, _)| (!primary, begin));
+
+        for (begin, end, primary, label) in underlines {
+            let start = columns(begin);
+            let len = columns(end).saturating_sub(start).max(1);
+            let (mark, style) = if primary {
+                ("^", self.severity_style())
+            } else {
+                ("-", "1;34")
+            };
+            let mut underline = mark.repeat(len);
+            if !label.is_empty() {
+                underline = format!("{} {}", underline, label);
+            }
+            out.push_str(&gutter(""));
+            out.push(' ');
+            out.push_str(&" ".repeat(start));
+            out.push_str(&self.paint(style, &underline));
+            out.push('\n');
+        }
+    }
+}
+
+pub(crate) fn py_frames_from_sl(call_stack: CallStack) -> Vec<PyFrame> {
+    call_stack
+        .into_frames()
+        .into_iter()
+        .map(Into::into)
+        .collect()
+}
+
+/// Records `call_stack` as the `call_stack` attribute of the exception, unless
+/// it already has one from a deeper call into Python.
+pub(crate) fn set_py_err_call_stack(py: Python<'_>, err: PyErr, call_stack: CallStack) -> PyErr {
+    let value = err.value_bound(py);
+    let name = intern!(py, "call_stack");
+    if value.getattr(name).is_ok_and(|x| !x.is_none()) {
+        return err;
+    }
+    // exceptions not accepting new attributes simply go without
+    let _ = value.setattr(name, py_frames_from_sl(call_stack).into_py(py));
+    err
+}
+
+fn new_py_err(py: Python<'_>, ty: Bound<'_, PyType>, e: &starlark::Error) -> PyErr {
+    let err = PyErr::from_type_bound(ty, e.to_string());
+    let result: PyResult<()> = (|| {
+        let value = err.value_bound(py);
+        value.setattr("message", e.without_diagnostic().to_string())?;
+        value.setattr("span", e.span().cloned().map(PyFileSpan::from).into_py(py))?;
+        // starlark-rust keeps the call stack of its errors to itself
+        value.setattr("call_stack", py.None())?;
+        Ok(())
+    })();
+    match result {
+        Ok(_) => err,
+        Err(e) => e,
+    }
+}
+
+fn sl_error_kind_inner(kind: &ErrorKind) -> Option<&anyhow::Error> {
+    match kind {
+        ErrorKind::Fail(x)
+        | ErrorKind::Value(x)
+        | ErrorKind::Function(x)
+        | ErrorKind::Scope(x)
+        | ErrorKind::Lexer(x)
+        | ErrorKind::Internal(x)
+        | ErrorKind::Other(x) => Some(x),
+        _ => None,
+    }
+}
+
+/// An error from loading a file with `FilesystemLoader`, passed through the
+/// `load()` as is, so that it can be raised with its own kind and location.
+pub(crate) struct LoadError {
+    path: PathBuf,
+    error: starlark::Error,
+}
+
+impl LoadError {
+    pub(crate) fn new(path: PathBuf, error: starlark::Error) -> Self {
+        Self { path, error }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error.without_diagnostic(), f)
+    }
+}
+
+impl fmt::Debug for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Unwraps the errors of loaded files, returning the innermost error and the
+/// `load()`s leading to it, outermost first.
+fn unwrap_load_errors(mut e: starlark::Error) -> (starlark::Error, Vec<String>) {
+    let mut context = Vec::new();
+    loop {
+        if !matches!(e.kind(), ErrorKind::Other(x) if x.is::<LoadError>()) {
+            return (e, context);
+        }
+        let span = e.span().cloned();
+        let LoadError {
+            path,
+            error: mut inner,
+        } = match e.into_kind() {
+            ErrorKind::Other(x) => x.downcast().unwrap(),
+            _ => unreachable!(),
+        };
+        if let Some(span) = span {
+            if inner.span().is_none() {
+                // e.g. a load cycle, best pointed at by the `load()` itself
+                inner.set_span(span.span, &span.file);
+            } else {
+                context.push(format!("while loading `{}` at {}", path.display(), span));
+            }
+        }
+        e = inner;
+    }
+}
+
+// mimics BaseException.add_note, which is only available since Python 3.11
+fn add_note(py: Python<'_>, err: &PyErr, note: String) -> PyResult<()> {
+    let value = err.value_bound(py);
+    let name = intern!(py, "__notes__");
+    match value.getattr(name) {
+        Ok(notes) => notes
+            .call_method1(intern!(py, "append"), (note,))
+            .map(|_| ()),
+        Err(_) => value.setattr(name, PyList::new_bound(py, [note])),
+    }
+}
+
+/// Converts a Starlark error into the `StarlarkError` subclass matching its
+/// kind.
+///
+/// If the error originally came from Python, the original exception is
+/// returned instead, with a note showing where it crossed Starlark frames.
+/// Errors of files loaded by `FilesystemLoader` are raised as they are, with
+/// notes of the `load()`s leading to them.
+pub(crate) fn py_err_from_sl(py: Python<'_>, e: starlark::Error) -> PyErr {
+    let (e, context) = unwrap_load_errors(e);
+    let err = py_err_from_sl_inner(py, e);
+    for note in context {
+        if let Err(e) = add_note(py, &err, note) {
+            return e;
+        }
+    }
+    err
+}
+
+fn py_err_from_sl_inner(py: Python<'_>, e: starlark::Error) -> PyErr {
+    if let Some(orig) = sl_error_kind_inner(e.kind()).and_then(|x| x.downcast_ref::<PyErr>()) {
+        let orig = orig.clone_ref(py);
+        if e.has_diagnostic() {
+            let note = format!("The exception was propagated through Starlark:\n{}", e);
+            if let Err(e) = add_note(py, &orig, note) {
+                return e;
+            }
+        }
+        return orig;
+    }
+
+    let ty = match e.kind() {
+        ErrorKind::Fail(_) => py.get_type_bound::<StarlarkFailError>(),
+        ErrorKind::Value(_) => py.get_type_bound::<StarlarkValueError>(),
+        ErrorKind::Function(_) => py.get_type_bound::<StarlarkFunctionError>(),
+        ErrorKind::Scope(_) => py.get_type_bound::<StarlarkScopeError>(),
+        ErrorKind::Lexer(_) => py.get_type_bound::<StarlarkParseError>(),
+        ErrorKind::Internal(_) => py.get_type_bound::<StarlarkInternalError>(),
+        ErrorKind::Other(_) => py.get_type_bound::<StarlarkNativeError>(),
+        _ => py.get_type_bound::<StarlarkError>(),
+    };
+    new_py_err(py, ty, &e)
+}
+
+/// Converts a Starlark error from parsing into a `StarlarkParseError`,
+/// regardless of its kind.
+pub(crate) fn py_parse_err_from_sl(py: Python<'_>, e: starlark::Error) -> PyErr {
+    new_py_err(py, py.get_type_bound::<StarlarkParseError>(), &e)
+}
+
+/// Converts an error reported by the static typechecker into a
+/// `StarlarkTypeCheckError`.
+pub(crate) fn py_typecheck_err_from_sl(py: Python<'_>, e: starlark::Error) -> PyErr {
+    new_py_err(py, py.get_type_bound::<StarlarkTypeCheckError>(), &e)
+}
+
+pub(crate) fn register_exceptions(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("StarlarkError", py.get_type_bound::<StarlarkError>())?;
+    m.add(
+        "StarlarkParseError",
+        py.get_type_bound::<StarlarkParseError>(),
+    )?;
+    m.add(
+        "StarlarkTypeCheckError",
+        py.get_type_bound::<StarlarkTypeCheckError>(),
+    )?;
+    m.add(
+        "StarlarkFailError",
+        py.get_type_bound::<StarlarkFailError>(),
+    )?;
+    m.add(
+        "StarlarkValueError",
+        py.get_type_bound::<StarlarkValueError>(),
+    )?;
+    m.add(
+        "StarlarkFunctionError",
+        py.get_type_bound::<StarlarkFunctionError>(),
+    )?;
+    m.add(
+        "StarlarkScopeError",
+        py.get_type_bound::<StarlarkScopeError>(),
+    )?;
+    m.add(
+        "StarlarkNativeError",
+        py.get_type_bound::<StarlarkNativeError>(),
+    )?;
+    m.add(
+        "StarlarkInternalError",
+        py.get_type_bound::<StarlarkInternalError>(),
+    )?;
+    Ok(())
+}
@@ -1,18 +1,98 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
 
 use anyhow::anyhow;
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::intern;
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyTuple};
-use starlark::environment::{FrozenModule, Module};
-use starlark::eval::{Evaluator, FileLoader};
-
-use crate::codemap::PyFileSpan;
+use starlark::environment::{FrozenModule, Globals, GlobalsBuilder, LibraryExtension, Module};
+use starlark::eval::{CallStack, Evaluator, FileLoader, ProfileData, ProfileMode};
+use starlark::starlark_module;
+use starlark::syntax::{AstModule, Dialect};
+use starlark::values::none::NoneType;
+use starlark::values::tuple::UnpackTuple;
+use starlark::values::Value;
+
+use crate::codemap::{PyFileSpan, PyResolvedFileSpan};
 use crate::environment::{PyFrozenModule, PyGlobals, PyModule};
-use crate::syntax::PyAstModule;
+use crate::errors::{py_err_from_sl, py_frames_from_sl, set_py_err_call_stack, LoadError, PyFrame};
+use crate::syntax::{PyAstModule, PyDialect};
 use crate::{py2sl, sl2py};
 
+// An Evaluator object is mutably borrowed for the whole duration of an
+// evaluation, so Python code invoked by Starlark can't query it directly.
+// Instead, running evaluations are tracked here, and calls into Python publish
+// a snapshot of the call stack for the duration of the call.
+struct RunningEvaluation {
+    py_evaluator: usize,
+    evaluator: usize,
+    call_stack: Option<CallStack>,
+    /// A Python error that couldn't be propagated through Starlark, raised
+    /// once the evaluation returns.
+    pending_error: Option<PyErr>,
+    print_handler: Option<PyObject>,
+}
+
+thread_local! {
+    static RUNNING_EVALUATIONS: RefCell<Vec<RunningEvaluation>> = const { RefCell::new(Vec::new()) };
+}
+
+struct RunningEvaluationGuard;
+
+impl Drop for RunningEvaluationGuard {
+    fn drop(&mut self) {
+        RUNNING_EVALUATIONS.with_borrow_mut(|x| x.pop());
+    }
+}
+
+fn evaluator_addr(eval: &Evaluator<'_, '_>) -> usize {
+    eval as *const Evaluator<'_, '_> as usize
+}
+
+/// Runs `f` with the call stack of `eval` made available to
+/// `Evaluator.call_stack()` and friends. An exception raised by `f` is given
+/// the call stack as its `call_stack` attribute.
+pub(crate) fn with_call_stack_exposed<'v, 'a, R>(
+    eval: &mut Evaluator<'v, 'a>,
+    f: impl FnOnce(&mut Evaluator<'v, 'a>) -> PyResult<R>,
+) -> PyResult<R> {
+    let addr = evaluator_addr(eval);
+    let result =
+        match RUNNING_EVALUATIONS.with_borrow(|x| x.iter().rposition(|r| r.evaluator == addr)) {
+            Some(idx) => {
+                let prev = RUNNING_EVALUATIONS
+                    .with_borrow_mut(|x| x[idx].call_stack.replace(eval.call_stack()));
+                let result = f(eval);
+                RUNNING_EVALUATIONS.with_borrow_mut(|x| {
+                    if let Some(r) = x.get_mut(idx) {
+                        r.call_stack = prev;
+                    }
+                });
+                result
+            }
+            // not evaluating on behalf of an Evaluator object
+            None => f(eval),
+        };
+    result.map_err(|e| Python::with_gil(|py| set_py_err_call_stack(py, e, eval.call_stack())))
+}
+
+/// Keeps `err` to be raised when the innermost running evaluation returns,
+/// for errors that Starlark offers no way to propagate. Returns `err` back if
+/// nothing is being evaluated, or an earlier error is already pending.
+pub(crate) fn set_pending_error(err: PyErr) -> Result<(), PyErr> {
+    RUNNING_EVALUATIONS.with_borrow_mut(|x| match x.last_mut() {
+        Some(r) if r.pending_error.is_none() => {
+            r.pending_error = Some(err);
+            Ok(())
+        }
+        _ => Err(err),
+    })
+}
+
 // it seems the Evaluator contains many thread-unsafe states
 #[pyclass(module = "xingque", name = "Evaluator", unsendable)]
 pub(crate) struct PyEvaluator(
@@ -20,6 +100,10 @@ pub(crate) struct PyEvaluator(
     // this reference is necessary for memory safety
     #[allow(dead_code)] Py<PyModule>,
     PyObjectFileLoader,
+    // the print handler, see `print_builtins`
+    Option<PyObject>,
+    // ProfileData doesn't expose its mode
+    Option<PyProfileMode>,
 );
 
 impl PyEvaluator {
@@ -32,12 +116,65 @@ impl PyEvaluator {
             Evaluator::new(module),
             module_ref,
             PyObjectFileLoader::default(),
+            None,
+            None,
         ))
     }
 
     fn ensure_module_available(&self, py: Python) -> PyResult<()> {
         self.1.bind(py).borrow().inner().map(|_| ())
     }
+
+    fn run<R>(
+        slf: &Bound<'_, Self>,
+        f: impl FnOnce(&mut Evaluator<'static, 'static>) -> PyResult<R>,
+    ) -> PyResult<R> {
+        let mut me = slf.borrow_mut();
+        me.ensure_module_available(slf.py())?;
+
+        RUNNING_EVALUATIONS.with_borrow_mut(|x| {
+            x.push(RunningEvaluation {
+                py_evaluator: slf.as_ptr() as usize,
+                evaluator: evaluator_addr(&me.0),
+                call_stack: None,
+                pending_error: None,
+                print_handler: me.3.as_ref().map(|x| x.clone_ref(slf.py())),
+            })
+        });
+        let _guard = RunningEvaluationGuard;
+        let result = f(&mut me.0);
+        match RUNNING_EVALUATIONS.with_borrow_mut(|x| x.last_mut()?.pending_error.take()) {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+
+    fn with_call_stack<R>(slf: &Bound<'_, Self>, f: impl FnOnce(CallStack) -> R) -> PyResult<R> {
+        if let Ok(me) = slf.try_borrow() {
+            me.ensure_module_available(slf.py())?;
+            // Evaluator::call_stack panics on an empty stack
+            let call_stack = match me.0.call_stack_count() {
+                0 => CallStack::default(),
+                _ => me.0.call_stack(),
+            };
+            return Ok(f(call_stack));
+        }
+
+        // we are being called from inside an evaluation
+        let key = slf.as_ptr() as usize;
+        let call_stack = RUNNING_EVALUATIONS.with_borrow(|x| {
+            x.iter()
+                .rev()
+                .find(|r| r.py_evaluator == key)
+                .and_then(|r| r.call_stack.clone())
+        });
+        match call_stack {
+            Some(x) => Ok(f(x)),
+            None => Err(PyRuntimeError::new_err(
+                "the call stack of this Evaluator is not available at this point",
+            )),
+        }
+    }
 }
 
 #[pymethods]
@@ -53,19 +190,16 @@ impl PyEvaluator {
     // TODO: disable_gc
 
     fn eval_statements(
-        &mut self,
-        py: Python,
+        slf: &Bound<'_, Self>,
         statements: &Bound<'_, PyAstModule>,
     ) -> PyResult<PyObject> {
-        self.ensure_module_available(py)?;
-
-        match self
-            .0
-            .eval_statements(statements.borrow_mut().take_inner()?)
-        {
-            Ok(sl) => sl2py::py_from_sl_value(py, sl),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
-        }
+        let py = slf.py();
+        Self::run(slf, |eval| {
+            match eval.eval_statements(statements.borrow_mut().take_inner()?) {
+                Ok(sl) => sl2py::py_from_sl_value(py, sl),
+                Err(e) => Err(py_err_from_sl(py, e)),
+            }
+        })
     }
 
     fn local_variables(&self, py: Python) -> PyResult<HashMap<String, PyObject>> {
@@ -102,10 +236,47 @@ impl PyEvaluator {
         Ok(())
     }
 
-    // TODO: enable_profile
-    // TODO: write_profile
-    // TODO: gen_profile
-    // TODO: coverage
+    fn enable_profile(&mut self, py: Python, mode: PyProfileMode) -> PyResult<()> {
+        self.ensure_module_available(py)?;
+        self.0.enable_profile(&mode.into())?;
+        self.4 = Some(mode);
+        Ok(())
+    }
+
+    fn write_profile(&mut self, py: Python, filename: ::std::path::PathBuf) -> PyResult<()> {
+        self.ensure_module_available(py)?;
+        self.0.write_profile(filename)?;
+        Ok(())
+    }
+
+    fn gen_profile(&mut self, py: Python) -> PyResult<PyProfileData> {
+        self.ensure_module_available(py)?;
+        let data = self.0.gen_profile()?;
+        // gen_profile only succeeds after a successful enable_profile
+        let mode = self.4.expect("profile mode should be set");
+        Ok(PyProfileData { mode, data })
+    }
+
+    /// Obtains the spans of the statements executed so far, which requires
+    /// the profile mode to be `ProfileMode.COVERAGE`.
+    ///
+    /// Note coverage is not precise, because some optimizer transformations
+    /// may create incorrect spans or remove statements.
+    fn coverage(&self, py: Python) -> PyResult<Vec<PyResolvedFileSpan>> {
+        self.ensure_module_available(py)?;
+        let mut result: Vec<_> = self.0.coverage()?.into_iter().collect();
+        // the set is unordered
+        result.sort_by_key(|x| {
+            (
+                x.file.clone(),
+                x.span.begin.line,
+                x.span.begin.column,
+                x.span.end.line,
+                x.span.end.column,
+            )
+        });
+        Ok(result.into_iter().map(Into::into).collect())
+    }
 
     fn enable_terminal_breakpoint_console(&mut self, py: Python) -> PyResult<()> {
         self.ensure_module_available(py)?;
@@ -113,20 +284,47 @@ impl PyEvaluator {
         Ok(())
     }
 
-    // TODO: call_stack
-    // TODO: call_stack_top_frame
+    /// Obtains the current call stack, outermost frame first.
+    ///
+    /// This also works from Python code invoked by Starlark during an
+    /// evaluation.
+    fn call_stack(slf: &Bound<'_, Self>) -> PyResult<Vec<PyFrame>> {
+        Self::with_call_stack(slf, py_frames_from_sl)
+    }
 
-    fn call_stack_count(&self, py: Python) -> PyResult<usize> {
-        self.ensure_module_available(py)?;
-        Ok(self.0.call_stack_count())
+    /// Obtains the top frame of the call stack, or `None` if empty.
+    fn call_stack_top_frame(slf: &Bound<'_, Self>) -> PyResult<Option<PyFrame>> {
+        Self::with_call_stack(slf, |x| py_frames_from_sl(x).pop())
+    }
+
+    fn call_stack_count(slf: &Bound<'_, Self>) -> PyResult<usize> {
+        Self::with_call_stack(slf, |x| x.into_frames().len())
+    }
+
+    fn call_stack_top_location(slf: &Bound<'_, Self>) -> PyResult<Option<PyFileSpan>> {
+        Self::with_call_stack(slf, |x| {
+            x.into_frames()
+                .pop()
+                .and_then(|x| x.location)
+                .map(PyFileSpan::from)
+        })
     }
 
-    fn call_stack_top_location(&self, py: Python) -> PyResult<Option<PyFileSpan>> {
+    /// Sets the callable receiving the output of `print` and `pprint`, called
+    /// once per `print` or `pprint` call with the text to print, which may
+    /// span several lines. The call stack of this evaluator is available to
+    /// the handler, with the `print` call on top. `None` restores printing to
+    /// stderr.
+    fn set_print_handler(&mut self, py: Python, handler: PyObject) -> PyResult<()> {
         self.ensure_module_available(py)?;
-        Ok(self.0.call_stack_top_location().map(PyFileSpan::from))
+        self.3 = if handler.is_none(py) {
+            None
+        } else {
+            Some(handler)
+        };
+        Ok(())
     }
 
-    // TODO: set_print_handler
     // TODO: heap
 
     #[getter]
@@ -145,39 +343,47 @@ impl PyEvaluator {
     }
 
     fn eval_module(
-        &mut self,
-        py: Python,
+        slf: &Bound<'_, Self>,
         ast: &Bound<'_, PyAstModule>,
         globals: &Bound<'_, PyGlobals>,
     ) -> PyResult<PyObject> {
-        self.ensure_module_available(py)?;
-
-        match self
-            .0
-            .eval_module(ast.borrow_mut().take_inner()?, &globals.borrow().0)
-        {
-            Ok(sl) => sl2py::py_from_sl_value(py, sl),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
-        }
+        let py = slf.py();
+        Self::run(slf, |eval| {
+            match eval.eval_module(ast.borrow_mut().take_inner()?, &globals.borrow().0) {
+                Ok(sl) => sl2py::py_from_sl_value(py, sl),
+                Err(e) => Err(py_err_from_sl(py, e)),
+            }
+        })
     }
 
     #[pyo3(signature = (function, *args, **kwargs))]
     fn eval_function(
-        &mut self,
-        py: Python,
+        slf: &Bound<'_, Self>,
         function: &Bound<'_, PyAny>,
         args: &Bound<'_, PyTuple>,
         kwargs: Option<&Bound<'_, PyDict>>,
     ) -> PyResult<PyObject> {
-        self.ensure_module_available(py)?;
+        Self::run(slf, |eval| {
+            Self::eval_function_impl(eval, function, args, kwargs)
+        })
+    }
+}
 
-        let heap = self.0.heap();
+impl PyEvaluator {
+    fn eval_function_impl(
+        eval: &mut Evaluator<'static, 'static>,
+        function: &Bound<'_, PyAny>,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
+        let py = function.py();
+        let heap = eval.heap();
         let to_sl = |x| py2sl::sl_value_from_py(x, heap);
-        let function = to_sl(function);
-        let positional: Vec<_> = args
+        let function = to_sl(function)?;
+        let positional = args
             .iter_borrowed()
             .map(|x| py2sl::sl_value_from_py(&x, heap)) // borrowck doesn't let me use to_sl, sigh
-            .collect();
+            .collect::<PyResult<Vec<_>>>()?;
         let named: Vec<_> = if let Some(kwargs) = kwargs {
             let mut tmp = Vec::with_capacity(kwargs.len());
             for (k, v) in kwargs.clone().into_iter() {
@@ -187,15 +393,157 @@ impl PyEvaluator {
         } else {
             Vec::new()
         };
-        let named: Vec<_> = named.iter().map(|(k, v)| (k.as_str(), to_sl(v))).collect();
+        let named = named
+            .iter()
+            .map(|(k, v)| Ok((k.as_str(), to_sl(v)?)))
+            .collect::<PyResult<Vec<_>>>()?;
 
-        match self.0.eval_function(function, &positional, &named) {
+        match eval.eval_function(function, &positional, &named) {
             Ok(sl) => sl2py::py_from_sl_value(py, sl),
-            Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+            Err(e) => Err(py_err_from_sl(py, e)),
         }
     }
 }
 
+/// How to profile Starlark code.
+#[pyclass(
+    module = "xingque",
+    name = "ProfileMode",
+    rename_all = "SCREAMING_SNAKE_CASE",
+    frozen,
+    eq,
+    hash
+)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum PyProfileMode {
+    /// The heap profile mode provides information about the time spent in each function and allocations
+    /// performed by each function. Enabling this mode the side effect of disabling garbage-collection.
+    /// This profiling mode is the recommended one.
+    HeapSummaryAllocated,
+    /// Like heap summary, but information about retained memory after module is frozen.
+    HeapSummaryRetained,
+    /// Like heap profile, but writes output comparible with
+    /// [flamegraph.pl](https://github.com/brendangregg/FlameGraph/blob/master/flamegraph.pl).
+    HeapFlameAllocated,
+    /// Like heap flame, but information about retained memory after module is frozen.
+    HeapFlameRetained,
+    /// The statement profile mode provides information about time spent in each statement.
+    Statement,
+    /// Code coverage.
+    Coverage,
+    /// The bytecode profile mode provides information about bytecode instructions.
+    Bytecode,
+    /// The bytecode profile mode provides information about bytecode instruction pairs.
+    BytecodePairs,
+    /// Provide output compatible with
+    /// [flamegraph.pl](https://github.com/brendangregg/FlameGraph/blob/master/flamegraph.pl).
+    TimeFlame,
+    /// Profile runtime typechecking.
+    Typecheck,
+    // NOTE: keep this in sync with ProfileMode
+}
+
+impl From<PyProfileMode> for ProfileMode {
+    fn from(value: PyProfileMode) -> Self {
+        match value {
+            PyProfileMode::HeapSummaryAllocated => Self::HeapSummaryAllocated,
+            PyProfileMode::HeapSummaryRetained => Self::HeapSummaryRetained,
+            PyProfileMode::HeapFlameAllocated => Self::HeapFlameAllocated,
+            PyProfileMode::HeapFlameRetained => Self::HeapFlameRetained,
+            PyProfileMode::Statement => Self::Statement,
+            PyProfileMode::Coverage => Self::Coverage,
+            PyProfileMode::Bytecode => Self::Bytecode,
+            PyProfileMode::BytecodePairs => Self::BytecodePairs,
+            PyProfileMode::TimeFlame => Self::TimeFlame,
+            PyProfileMode::Typecheck => Self::Typecheck,
+        }
+    }
+}
+
+impl PyProfileMode {
+    fn is_flame_graph(self) -> bool {
+        matches!(
+            self,
+            Self::HeapFlameAllocated | Self::HeapFlameRetained | Self::TimeFlame
+        )
+    }
+}
+
+#[pymethods]
+impl PyProfileMode {
+    fn __str__(&self) -> String {
+        ProfileMode::from(*self).to_string()
+    }
+}
+
+/// Collected profiling data.
+#[pyclass(module = "xingque", name = "ProfileData", frozen)]
+pub(crate) struct PyProfileData {
+    mode: PyProfileMode,
+    data: ProfileData,
+}
+
+#[pymethods]
+impl PyProfileData {
+    #[getter]
+    fn profile_mode(&self) -> PyProfileMode {
+        self.mode
+    }
+
+    /// Generate a string with profile data (e.g. CSV or flamegraph, depending on profile type).
+    fn gen(&self) -> PyResult<String> {
+        Ok(self.data.gen()?)
+    }
+
+    /// Write to a file.
+    fn write(&self, path: ::std::path::PathBuf) -> PyResult<()> {
+        self.data.write(&path)?;
+        Ok(())
+    }
+
+    /// Parse the generated profile data into rows.
+    ///
+    /// For flame graph modes, each row is a tuple of the stack (outermost
+    /// first) and its sample value. For the other modes, each row is a dict
+    /// mapping CSV column names to the (string) values.
+    fn rows(&self, py: Python) -> PyResult<Vec<PyObject>> {
+        let text = self.data.gen()?;
+        if self.mode.is_flame_graph() {
+            let mut result = Vec::new();
+            for line in text.lines().filter(|x| !x.is_empty()) {
+                let (stack, value) = line.rsplit_once(' ').ok_or_else(|| {
+                    PyValueError::new_err(format!("malformed flame graph line: {}", line))
+                })?;
+                let value: u64 = value.parse().map_err(|_| {
+                    PyValueError::new_err(format!("malformed flame graph line: {}", line))
+                })?;
+                let stack: Vec<_> = stack.split(';').collect();
+                result.push((stack, value).into_py(py));
+            }
+            return Ok(result);
+        }
+
+        let csv = py.import_bound(intern!(py, "csv"))?;
+        let reader = csv.call_method1(
+            intern!(py, "DictReader"),
+            (text.lines().collect::<Vec<_>>(),),
+        )?;
+        reader.iter()?.map(|x| x.map(Bound::unbind)).collect()
+    }
+
+    /// Merge profiles (aggregate).
+    #[staticmethod]
+    fn merge(profiles: Vec<Py<PyProfileData>>, py: Python) -> PyResult<Self> {
+        let profiles: Vec<_> = profiles.iter().map(|x| x.bind(py).get()).collect();
+        let data = ProfileData::merge(profiles.iter().map(|x| &x.data))?;
+        // merge checks that the modes are all the same
+        Ok(Self {
+            mode: profiles[0].mode,
+            data,
+        })
+    }
+}
+
 // it would be good if https://github.com/PyO3/pyo3/issues/1190 is implemented
 // so we could have stronger typing
 // but currently duck-typing isn't bad anyway
@@ -213,10 +561,13 @@ impl FileLoader for PyObjectFileLoader {
     fn load(&self, path: &str) -> anyhow::Result<FrozenModule> {
         if let Some(inner) = self.0.as_ref() {
             Python::with_gil(|py| {
-                // first check if it's a PyDictFileLoader and forward to its impl
+                // first check if it's one of our loaders and forward to its impl
                 if let Ok(x) = inner.downcast_bound::<PyDictFileLoader>(py) {
                     return x.borrow().load(path);
                 }
+                if let Ok(x) = inner.downcast_bound::<PyFilesystemLoader>(py) {
+                    return x.get().load(path);
+                }
 
                 // duck-typing
                 // call the wrapped PyObject's "load" method with the path
@@ -237,6 +588,74 @@ impl FileLoader for PyObjectFileLoader {
     }
 }
 
+/// Replaces the `print` and `pprint` functions of `builder` with ones calling
+/// the print handler of the running `Evaluator` object, if there are any.
+///
+/// starlark-rust gives a `PrintHandler` only the text to print, so the
+/// handler couldn't otherwise see where `print` was called from.
+pub(crate) fn print_builtins(builder: &mut GlobalsBuilder, extensions: &[LibraryExtension]) {
+    if extensions.contains(&LibraryExtension::Print) {
+        print_builtin(builder);
+    }
+    if extensions.contains(&LibraryExtension::Pprint) {
+        pprint_builtin(builder);
+    }
+}
+
+fn print_text(eval: &mut Evaluator, text: &str) -> anyhow::Result<()> {
+    let addr = evaluator_addr(eval);
+    Python::with_gil(|py| {
+        let handler = RUNNING_EVALUATIONS.with_borrow(|x| {
+            x.iter()
+                .rfind(|r| r.evaluator == addr)
+                .and_then(|r| r.print_handler.as_ref().map(|x| x.clone_ref(py)))
+        });
+        match handler {
+            Some(handler) => {
+                with_call_stack_exposed(eval, |_| handler.call1(py, (text,)))?;
+            }
+            // same as the default handler
+            None => eprintln!("{}", text),
+        }
+        Ok(())
+    })
+}
+
+#[starlark_module]
+fn print_builtin(builder: &mut GlobalsBuilder) {
+    /// Print some values to the output.
+    fn print(
+        #[starlark(args)] args: UnpackTuple<Value>,
+        eval: &mut Evaluator,
+    ) -> anyhow::Result<NoneType> {
+        let text = args
+            .items
+            .iter()
+            .map(|x| x.to_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        print_text(eval, &text)?;
+        Ok(NoneType)
+    }
+}
+
+#[starlark_module]
+fn pprint_builtin(builder: &mut GlobalsBuilder) {
+    fn pprint(
+        #[starlark(args)] args: UnpackTuple<Value>,
+        eval: &mut Evaluator,
+    ) -> anyhow::Result<NoneType> {
+        let text = args
+            .items
+            .iter()
+            .map(|x| format!("{:#}", x))
+            .collect::<Vec<_>>()
+            .join(" ");
+        print_text(eval, &text)?;
+        Ok(NoneType)
+    }
+}
+
 // a PyDict is wrapped here instead of the ReturnFileLoader (so we effectively
 // don't wrap ReturnFileLoader but provide equivalent functionality that's
 // idiomatic in Python), because unfortunately ReturnFileLoader has a lifetime
@@ -266,3 +685,244 @@ impl FileLoader for PyDictFileLoader {
         ))
     }
 }
+
+struct CachedModule {
+    content_hash: u64,
+    // the loaded files with their fingerprints at the time of loading
+    deps: Vec<(PathBuf, u64)>,
+    // hash of the contents and the fingerprints of the dependencies
+    fingerprint: u64,
+    module: FrozenModule,
+}
+
+#[pyclass(module = "xingque", name = "FilesystemLoader", frozen)]
+pub(crate) struct PyFilesystemLoader {
+    root: PathBuf,
+    globals: Globals,
+    dialect: Dialect,
+    prefixes: HashMap<String, PathBuf>,
+    cache: Mutex<HashMap<PathBuf, CachedModule>>,
+}
+
+#[pymethods]
+impl PyFilesystemLoader {
+    #[new]
+    #[pyo3(signature = (root, globals = None, dialect = &PyDialect::STANDARD, prefixes = None))]
+    fn py_new(
+        root: PathBuf,
+        globals: Option<&PyGlobals>,
+        dialect: &PyDialect,
+        prefixes: Option<HashMap<String, PathBuf>>,
+    ) -> Self {
+        Self {
+            root,
+            globals: globals.map_or_else(Globals::standard, |x| x.0.clone()),
+            dialect: dialect.0.clone(),
+            prefixes: prefixes.unwrap_or_default(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[getter]
+    fn root(&self) -> PathBuf {
+        self.root.clone()
+    }
+
+    /// Resolves `path` as written in a `load()` of the file `current_file`
+    /// (the root if `None`) to the path of the file to load.
+    #[pyo3(signature = (path, current_file = None))]
+    fn resolve(&self, path: &str, current_file: Option<PathBuf>) -> PyResult<PathBuf> {
+        Ok(self.resolve_path(path, current_file.as_deref())?)
+    }
+
+    #[pyo3(name = "load")]
+    fn py_load(&self, py: Python, path: &str) -> PyResult<PyFrozenModule> {
+        let path = self.resolve_path(path, None)?;
+        match self.load_file(&path, None) {
+            Ok((x, _)) => Ok(PyFrozenModule(x)),
+            Err(e) => Err(py_err_from_sl(py, e)),
+        }
+    }
+
+    /// Forgets all cached modules.
+    fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+impl PyFilesystemLoader {
+    fn resolve_path(&self, path: &str, current_file: Option<&Path>) -> anyhow::Result<PathBuf> {
+        let result = if let Some((prefix, rest)) = path.split_once("//") {
+            let base = match prefix {
+                "" => &self.root,
+                _ => self
+                    .prefixes
+                    .get(prefix)
+                    .ok_or_else(|| anyhow!("unknown load prefix `{}` in `{}`", prefix, path))?,
+            };
+            // `//pkg:file.star` is `pkg/file.star`
+            match rest.split_once(':') {
+                Some((package, file)) => base.join(package).join(file),
+                None => base.join(rest),
+            }
+        } else {
+            let base = match current_file.and_then(Path::parent) {
+                Some(x) => x,
+                None => &self.root,
+            };
+            // `:file.star` is in the same package, i.e. directory
+            base.join(path.strip_prefix(':').unwrap_or(path))
+        };
+        Ok(normalize_path(&result))
+    }
+
+    /// Loads the file at `path` on behalf of the files in `chain`, returning
+    /// its module and fingerprint.
+    fn load_file(
+        &self,
+        path: &Path,
+        chain: Option<&LoadChain<'_>>,
+    ) -> starlark::Result<(FrozenModule, u64)> {
+        if let Some(cycle) = chain.and_then(|x| x.cycle(path)) {
+            let cycle: Vec<_> = cycle
+                .iter()
+                .chain(Some(&path))
+                .map(|x| format!("`{}`", x.display()))
+                .collect();
+            return Err(anyhow!("load cycle detected: {}", cycle.join(" -> ")).into());
+        }
+        let chain = LoadChain {
+            file: path,
+            parent: chain,
+        };
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read `{}`: {}", path.display(), e))?;
+        let content_hash = hash_of(&content);
+
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(path)
+            .filter(|x| x.content_hash == content_hash)
+            .map(|x| (x.deps.clone(), x.fingerprint, x.module.clone()));
+        if let Some((deps, fingerprint, module)) = cached {
+            let mut fresh = true;
+            for (dep, dep_fingerprint) in deps {
+                if self.load_file(&dep, Some(&chain))?.1 != dep_fingerprint {
+                    fresh = false;
+                    break;
+                }
+            }
+            if fresh {
+                return Ok((module, fingerprint));
+            }
+        }
+
+        let ast = AstModule::parse(&path.to_string_lossy(), content, &self.dialect)?;
+        let loader = DependencyLoader {
+            parent: self,
+            chain,
+            deps: RefCell::new(Vec::new()),
+        };
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            eval.set_loader(&loader);
+            eval.eval_module(ast, &self.globals)?;
+        }
+        let module = module.freeze()?;
+        let deps = loader.deps.into_inner();
+        let fingerprint = hash_of(&(content_hash, &deps));
+        self.cache.lock().unwrap().insert(
+            path.to_path_buf(),
+            CachedModule {
+                content_hash,
+                deps,
+                fingerprint,
+                module: module.clone(),
+            },
+        );
+        Ok((module, fingerprint))
+    }
+}
+
+impl FileLoader for PyFilesystemLoader {
+    fn load(&self, path: &str) -> anyhow::Result<FrozenModule> {
+        let path = self.resolve_path(path, None)?;
+        match self.load_file(&path, None) {
+            Ok((module, _)) => Ok(module),
+            Err(e) => Err(LoadError::new(path, e).into()),
+        }
+    }
+}
+
+// the files being loaded by one load, innermost first, each loading the
+// next; kept per load so that concurrent loads don't see each other
+struct LoadChain<'a> {
+    file: &'a Path,
+    parent: Option<&'a LoadChain<'a>>,
+}
+
+impl LoadChain<'_> {
+    /// The files from the one loading `path` to the innermost, if `path` is
+    /// being loaded already.
+    fn cycle(&self, path: &Path) -> Option<Vec<&Path>> {
+        let mut result = Vec::new();
+        let mut link = Some(self);
+        while let Some(x) = link {
+            result.push(x.file);
+            if x.file == path {
+                result.reverse();
+                return Some(result);
+            }
+            link = x.parent;
+        }
+        None
+    }
+}
+
+// the loader of the dependencies of a file, which resolves relative paths
+// against that file and records the loaded files
+struct DependencyLoader<'a> {
+    parent: &'a PyFilesystemLoader,
+    chain: LoadChain<'a>,
+    deps: RefCell<Vec<(PathBuf, u64)>>,
+}
+
+impl FileLoader for DependencyLoader<'_> {
+    fn load(&self, path: &str) -> anyhow::Result<FrozenModule> {
+        let path = self.parent.resolve_path(path, Some(self.chain.file))?;
+        let (module, fingerprint) = match self.parent.load_file(&path, Some(&self.chain)) {
+            Ok(x) => x,
+            Err(e) => return Err(LoadError::new(path, e).into()),
+        };
+        self.deps.borrow_mut().push((path, fingerprint));
+        Ok(module)
+    }
+}
+
+fn hash_of(x: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    x.hash(&mut hasher);
+    hasher.finish()
+}
+
+// removes `.` and `..` components without touching the file system, so that
+// the same file is always cached under the same path
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    result.push(component);
+                }
+            }
+            _ => result.push(component),
+        }
+    }
+    result
+}
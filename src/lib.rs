@@ -1,21 +1,70 @@
 use pyo3::prelude::*;
 
+mod ast;
 mod codemap;
+mod coverage;
+mod docs;
 mod environment;
+mod errors;
 mod eval;
+mod lsp;
 mod py2sl;
 mod repr_utils;
 mod sl2py;
 mod syntax;
+mod typing;
 mod values;
 
 #[pymodule]
+#[pyo3(name = "_xingque")]
 fn xingque(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add(
         "VERSION",
         option_env!("CARGO_PKG_VERSION").unwrap_or("unknown"),
     )?;
     m.add("STARLARK_RUST_VERSION", "0.12.0")?; // TODO: query this from Cargo
+    m.add_class::<ast::PyArgumentKind>()?;
+    m.add_class::<ast::PyAstNode>()?;
+    m.add_class::<ast::PyNodeTransformer>()?;
+    m.add_class::<ast::PyNodeVisitor>()?;
+    m.add_class::<ast::PyParameterKind>()?;
+    m.add_class::<ast::PyPass>()?;
+    m.add_class::<ast::PyBreak>()?;
+    m.add_class::<ast::PyContinue>()?;
+    m.add_class::<ast::PyReturn>()?;
+    m.add_class::<ast::PyExpression>()?;
+    m.add_class::<ast::PyAssign>()?;
+    m.add_class::<ast::PyAssignModify>()?;
+    m.add_class::<ast::PyIf>()?;
+    m.add_class::<ast::PyFor>()?;
+    m.add_class::<ast::PyDef>()?;
+    m.add_class::<ast::PyLoad>()?;
+    m.add_class::<ast::PyLoadArg>()?;
+    m.add_class::<ast::PyTupleExpr>()?;
+    m.add_class::<ast::PyDot>()?;
+    m.add_class::<ast::PyCall>()?;
+    m.add_class::<ast::PyArgument>()?;
+    m.add_class::<ast::PyIndex>()?;
+    m.add_class::<ast::PyIndex2>()?;
+    m.add_class::<ast::PySlice>()?;
+    m.add_class::<ast::PyIdentifier>()?;
+    m.add_class::<ast::PyLambda>()?;
+    m.add_class::<ast::PyParameter>()?;
+    m.add_class::<ast::PyLiteral>()?;
+    m.add_class::<ast::PyNot>()?;
+    m.add_class::<ast::PyMinus>()?;
+    m.add_class::<ast::PyPlus>()?;
+    m.add_class::<ast::PyBitNot>()?;
+    m.add_class::<ast::PyOp>()?;
+    m.add_class::<ast::PyIfExpr>()?;
+    m.add_class::<ast::PyListExpr>()?;
+    m.add_class::<ast::PyDictExpr>()?;
+    m.add_class::<ast::PyListComprehension>()?;
+    m.add_class::<ast::PyDictComprehension>()?;
+    m.add_class::<ast::PyForClause>()?;
+    m.add_class::<ast::PyIfClause>()?;
+    m.add_class::<ast::PyFString>()?;
+    m.add_function(wrap_pyfunction!(ast::unparse, m)?)?;
     m.add_class::<codemap::PyCodeMap>()?;
     m.add_class::<codemap::PyFileSpan>()?;
     m.add_class::<codemap::PyPos>()?;
@@ -24,16 +73,40 @@ fn xingque(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<codemap::PyResolvedPos>()?;
     m.add_class::<codemap::PyResolvedSpan>()?;
     m.add_class::<codemap::PySpan>()?;
+    m.add_class::<coverage::PyCoverageReport>()?;
+    m.add_class::<docs::PyDocFunction>()?;
+    m.add_class::<docs::PyDocModule>()?;
+    m.add_class::<docs::PyDocObject>()?;
+    m.add_class::<docs::PyDocParam>()?;
+    m.add_class::<docs::PyDocParamKind>()?;
+    m.add_class::<docs::PyDocProperty>()?;
+    m.add_class::<docs::PyDocReturn>()?;
+    m.add_class::<docs::PyDocString>()?;
     m.add_class::<environment::PyFrozenModule>()?;
     m.add_class::<environment::PyGlobals>()?;
     m.add_class::<environment::PyGlobalsBuilder>()?;
     m.add_class::<environment::PyLibraryExtension>()?;
     m.add_class::<environment::PyModule>()?;
+    m.add_class::<environment::PyStarlarkFunction>()?;
+    m.add_function(wrap_pyfunction!(environment::starlark_function, m)?)?;
+    m.add_class::<errors::PyEvalSeverity>()?;
+    m.add_class::<errors::PyFrame>()?;
+    m.add_class::<errors::PyLint>()?;
+    m.add_class::<errors::PyDiagnostic>()?;
+    errors::register_exceptions(m)?;
     m.add_class::<eval::PyDictFileLoader>()?;
     m.add_class::<eval::PyEvaluator>()?;
+    m.add_class::<eval::PyFilesystemLoader>()?;
+    m.add_class::<eval::PyProfileData>()?;
+    m.add_class::<eval::PyProfileMode>()?;
+    lsp::register_module(m)?;
+    m.add_function(wrap_pyfunction!(py2sl::register_converter, m)?)?;
+    m.add_function(wrap_pyfunction!(py2sl::register_type, m)?)?;
     m.add_class::<syntax::PyAstModule>()?;
     m.add_class::<syntax::PyDialect>()?;
     m.add_class::<syntax::PyDialectTypes>()?;
+    m.add_function(wrap_pyfunction!(syntax::format_source, m)?)?;
+    m.add_class::<typing::PyInterface>()?;
     m.add_class::<values::PyFrozenValue>()?;
     m.add_class::<values::PyHeap>()?;
     m.add_class::<values::PyHeapSummary>()?;
@@ -0,0 +1,413 @@
+use std::path::Path;
+use std::thread;
+
+use anyhow::anyhow;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{Exit, LogMessage, Notification as _};
+use lsp_types::request::{
+    Completion, GotoDefinition, HoverRequest, Initialize, Request as _, Shutdown,
+};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, LogMessageParams, MessageType, NumberOrString, Position, Range,
+    Url,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use starlark::analysis::{AstModuleLint, EvalSeverity};
+use starlark::codemap::Span;
+use starlark::docs::DocModule;
+use starlark::environment::Globals;
+use starlark::syntax::{AstModule, Dialect};
+use starlark_lsp::server::{
+    server_with_connection, LspContext, LspEvalResult, LspUrl, StringLiteralResult,
+};
+
+use crate::environment::PyGlobals;
+use crate::syntax::PyDialect;
+
+/// Requests handled by the starlark-rust server once initialized; it leaves
+/// the others unanswered.
+const SUPPORTED_REQUESTS: &[&str] = &[
+    GotoDefinition::METHOD,
+    Completion::METHOD,
+    HoverRequest::METHOD,
+    Shutdown::METHOD,
+    "starlark/fileContents",
+];
+
+/// Converts byte offsets into a document to LSP positions, whose columns count
+/// UTF-16 code units.
+struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(text: &'a str) -> Self {
+        let line_starts = Some(0)
+            .into_iter()
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&x| x <= offset) - 1;
+        let column = self.text[self.line_starts[line]..offset]
+            .encode_utf16()
+            .count();
+        Position::new(line as u32, column as u32)
+    }
+
+    fn range(&self, span: Span) -> Range {
+        Range::new(
+            self.position(span.begin().get() as usize),
+            self.position(span.end().get() as usize),
+        )
+    }
+}
+
+fn url_to_path(url: &LspUrl) -> String {
+    match url {
+        LspUrl::File(x) => x.to_string_lossy().into_owned(),
+        _ => url.to_string(),
+    }
+}
+
+fn path_to_url(path: &str) -> anyhow::Result<LspUrl> {
+    let url = match Url::from_file_path(path) {
+        Ok(x) => x,
+        Err(_) => Url::parse(path)?,
+    };
+    Ok(LspUrl::try_from(url)?)
+}
+
+/// The Python context object, as seen by the starlark-rust server.
+struct PyLspContext {
+    context: PyObject,
+    dialect: Dialect,
+}
+
+impl PyLspContext {
+    /// The globals for the file, from the context's `get_globals` if any.
+    fn globals(&self, py: Python<'_>, path: &str) -> PyResult<Globals> {
+        let context = self.context.bind(py);
+        let name = intern!(py, "get_globals");
+        if !context.hasattr(name)? {
+            return Ok(Globals::standard());
+        }
+        let globals = context.call_method1(name, (path,))?;
+        Ok(globals.downcast::<PyGlobals>()?.get().0.clone())
+    }
+
+    fn diagnostics(&self, path: &str, text: &str) -> (Vec<Diagnostic>, Option<AstModule>) {
+        let index = LineIndex::new(text);
+        let diagnostic = |range, severity, code: Option<String>, message| Diagnostic {
+            range,
+            severity: Some(severity),
+            code: code.map(NumberOrString::String),
+            source: Some("xingque".to_owned()),
+            message,
+            ..Default::default()
+        };
+        let ast = match AstModule::parse(path, text.to_owned(), &self.dialect) {
+            Ok(x) => x,
+            Err(e) => {
+                let diagnostic = diagnostic(
+                    e.span().map(|x| index.range(x.span)).unwrap_or_default(),
+                    DiagnosticSeverity::ERROR,
+                    None,
+                    e.without_diagnostic().to_string(),
+                );
+                return (vec![diagnostic], None);
+            }
+        };
+        let names = Python::with_gil(|py| self.globals(py, path)).map(|x| {
+            x.names()
+                .map(|x| x.as_str().to_owned())
+                .collect::<std::collections::HashSet<_>>()
+        });
+        let mut diagnostics = Vec::new();
+        match &names {
+            Ok(_) => {}
+            Err(e) => diagnostics.push(diagnostic(
+                Range::default(),
+                DiagnosticSeverity::ERROR,
+                None,
+                format!("getting the globals failed: {}", e),
+            )),
+        }
+        diagnostics.extend(
+            ast.lint(names.as_ref().ok())
+                .into_iter()
+                .filter_map(|lint| {
+                    let severity = match lint.severity {
+                        EvalSeverity::Error => DiagnosticSeverity::ERROR,
+                        EvalSeverity::Warning => DiagnosticSeverity::WARNING,
+                        EvalSeverity::Advice => DiagnosticSeverity::HINT,
+                        EvalSeverity::Disabled => return None,
+                    };
+                    Some(diagnostic(
+                        index.range(lint.location.span),
+                        severity,
+                        Some(lint.short_name),
+                        lint.problem,
+                    ))
+                }),
+        );
+        (diagnostics, Some(ast))
+    }
+}
+
+impl LspContext for PyLspContext {
+    fn parse_file_with_contents(&self, uri: &LspUrl, content: String) -> LspEvalResult {
+        let (diagnostics, ast) = self.diagnostics(&url_to_path(uri), &content);
+        LspEvalResult { diagnostics, ast }
+    }
+
+    fn resolve_load(
+        &self,
+        path: &str,
+        current_file: &LspUrl,
+        _workspace_root: Option<&Path>,
+    ) -> anyhow::Result<LspUrl> {
+        let resolved: String = Python::with_gil(|py| {
+            self.context
+                .bind(py)
+                .call_method1(
+                    intern!(py, "resolve_load"),
+                    (path, url_to_path(current_file)),
+                )?
+                .extract()
+        })?;
+        path_to_url(&resolved)
+    }
+
+    fn render_as_load(
+        &self,
+        _target: &LspUrl,
+        _current_file: &LspUrl,
+        _workspace_root: Option<&Path>,
+    ) -> anyhow::Result<String> {
+        // only needed for symbols from `get_url_for_global_symbol`
+        Err(anyhow!("rendering load paths is not supported"))
+    }
+
+    fn resolve_string_literal(
+        &self,
+        _literal: &str,
+        _current_file: &LspUrl,
+        _workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Option<StringLiteralResult>> {
+        Ok(None)
+    }
+
+    /// The contents of the file at `uri`, from the context's
+    /// `get_load_contents` or from the file system.
+    fn get_load_contents(&self, uri: &LspUrl) -> anyhow::Result<Option<String>> {
+        let LspUrl::File(path) = uri else {
+            return Ok(None);
+        };
+        let path = path.to_string_lossy();
+        let from_context = Python::with_gil(|py| {
+            let context = self.context.bind(py);
+            let name = intern!(py, "get_load_contents");
+            if !context.hasattr(name)? {
+                return Ok(None);
+            }
+            context
+                .call_method1(name, (path.as_ref(),))?
+                .extract::<Option<String>>()
+                .map(Some)
+        })?;
+        match from_context {
+            Some(x) => Ok(x),
+            None => Ok(std::fs::read_to_string(path.as_ref()).ok()),
+        }
+    }
+
+    fn get_environment(&self, uri: &LspUrl) -> DocModule {
+        Python::with_gil(|py| self.globals(py, &url_to_path(uri)))
+            .map(|x| x.documentation())
+            .unwrap_or_default()
+    }
+
+    fn get_url_for_global_symbol(
+        &self,
+        _current_file: &LspUrl,
+        _symbol: &str,
+    ) -> anyhow::Result<Option<LspUrl>> {
+        Ok(None)
+    }
+}
+
+/// Reads a message from `stdin`, or `None` at the end of the input.
+fn read_message(stdin: &Bound<'_, PyAny>) -> PyResult<Option<Result<Message, serde_json::Error>>> {
+    let py = stdin.py();
+    let mut length = None;
+    loop {
+        let line = stdin.call_method0(intern!(py, "readline"))?;
+        let line = line.downcast::<PyBytes>()?.as_bytes();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        PyValueError::new_err("LSP message without a valid Content-Length header")
+    })?;
+    let mut body = Vec::with_capacity(length);
+    while body.len() < length {
+        let chunk = stdin.call_method1(intern!(py, "read"), (length - body.len(),))?;
+        let chunk = chunk.downcast::<PyBytes>()?.as_bytes();
+        if chunk.is_empty() {
+            return Ok(None);
+        }
+        body.extend_from_slice(chunk);
+    }
+    Ok(Some(serde_json::from_slice(&body)))
+}
+
+fn write_message(stdout: &Bound<'_, PyAny>, message: Message) -> PyResult<()> {
+    let py = stdout.py();
+    let mut data = Vec::new();
+    message.write(&mut data)?;
+    stdout.call_method1(intern!(py, "write"), (PyBytes::new_bound(py, &data),))?;
+    stdout.call_method0(intern!(py, "flush"))?;
+    Ok(())
+}
+
+fn error_response(req: Request, code: ErrorCode, message: String) -> Message {
+    Response::new_err(req.id, code as i32, message).into()
+}
+
+/// Runs a Language Server Protocol server over the binary streams `stdin`
+/// and `stdout`, until the client asks it to exit or closes `stdin`.
+///
+/// The server is the one of starlark-rust, providing diagnostics from the
+/// parser and linter, hover documentation, go-to-definition and completion.
+///
+/// `context` resolves `load()`s with a `resolve_load(module, current_file)`
+/// method returning the path of the loaded file. It may also have
+/// `get_load_contents(path)` for reading files not open in the editor,
+/// `get_globals(current_file)` returning the `Globals` available to the file
+/// and a `dialect` attribute, which defaults to `Dialect.EXTENDED`.
+#[pyfunction]
+pub(crate) fn serve(
+    stdin: &Bound<'_, PyAny>,
+    stdout: &Bound<'_, PyAny>,
+    context: &Bound<'_, PyAny>,
+) -> PyResult<()> {
+    let py = context.py();
+    let dialect = match context.getattr(intern!(py, "dialect")) {
+        Ok(x) => x.downcast::<PyDialect>()?.borrow().0.clone(),
+        Err(_) => Dialect::Extended,
+    };
+    let context = PyLspContext {
+        context: context.clone().unbind(),
+        dialect,
+    };
+
+    let (server, client) = Connection::memory();
+    let output = server.sender.clone();
+    let server = thread::spawn(move || server_with_connection(server, context));
+    let writer = {
+        let stdout = stdout.clone().unbind();
+        let receiver = client.receiver;
+        thread::spawn(move || -> PyResult<()> {
+            for message in receiver {
+                Python::with_gil(|py| write_message(stdout.bind(py), message))?;
+            }
+            Ok(())
+        })
+    };
+
+    // the server takes care of the requests, but those it doesn't handle
+    // would go unanswered, and it gives up on anything but `exit` after
+    // `shutdown`
+    let mut initialized = false;
+    let mut shut_down = false;
+    let result = loop {
+        if let Err(e) = py.check_signals() {
+            break Err(e);
+        }
+        let message = match read_message(stdin) {
+            Ok(Some(Ok(x))) => x,
+            Ok(Some(Err(e))) => {
+                let _ = output.send(Message::Notification(Notification::new(
+                    LogMessage::METHOD.to_owned(),
+                    LogMessageParams {
+                        typ: MessageType::ERROR,
+                        message: format!("invalid message: {}", e),
+                    },
+                )));
+                continue;
+            }
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(e),
+        };
+        let message = match message {
+            Message::Request(req) if shut_down => {
+                let reason = "the server has been shut down".to_owned();
+                let _ = output.send(error_response(req, ErrorCode::InvalidRequest, reason));
+                continue;
+            }
+            Message::Request(req)
+                if initialized && !SUPPORTED_REQUESTS.contains(&req.method.as_str()) =>
+            {
+                let reason = format!("unsupported method {}", req.method);
+                let _ = output.send(error_response(req, ErrorCode::MethodNotFound, reason));
+                continue;
+            }
+            Message::Request(req) => {
+                initialized |= req.method == Initialize::METHOD;
+                shut_down = req.method == Shutdown::METHOD;
+                Message::Request(req)
+            }
+            Message::Notification(x) if shut_down && x.method != Exit::METHOD => continue,
+            x => x,
+        };
+        let exit = matches!(&message, Message::Notification(x) if x.method == Exit::METHOD);
+        if client.sender.send(message).is_err() || exit {
+            break Ok(());
+        }
+    };
+
+    // let the server and writer finish once everything is sent
+    drop(client.sender);
+    drop(output);
+    let (server, writer) = py.allow_threads(|| (server.join(), writer.join()));
+    result?;
+    writer.expect("LSP writer thread panicked")?;
+    let server = server.expect("LSP server thread panicked");
+    if initialized {
+        server?;
+    }
+    // otherwise the client went away before initializing the server
+    Ok(())
+}
+
+/// Adds the `xingque.lsp` submodule to `m`.
+pub(crate) fn register_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    let lsp = PyModule::new_bound(py, "lsp")?;
+    lsp.add_function(wrap_pyfunction!(serve, &lsp)?)?;
+    m.add_submodule(&lsp)?;
+    // make `import xingque.lsp` work too
+    py.import_bound(intern!(py, "sys"))?
+        .getattr(intern!(py, "modules"))?
+        .set_item("xingque.lsp", lsp)?;
+    Ok(())
+}
@@ -6,17 +6,30 @@ use starlark::values::list::AllocList;
 use starlark::values::tuple::AllocTuple;
 use starlark::values::{FrozenHeap, FrozenValue, Heap, Value};
 
+mod native_function;
+pub(crate) use native_function::{set_py_function, types_from_annotations};
+mod registry;
+pub(crate) use registry::{register_converter, register_type};
 mod slpyobject;
-pub(crate) use slpyobject::SlPyObject;
+pub(crate) use slpyobject::{SlPyClass, SlPyObject};
 
 use crate::values::{PyFrozenValue, PyValue};
 
+pub(crate) fn sl_value_err_from_py(e: PyErr) -> starlark::Error {
+    starlark::Error::new(starlark::ErrorKind::Value(e.into()))
+}
+
 pub(crate) fn sl_frozen_value_from_py(
     value: &Bound<'_, PyAny>,
     heap: &FrozenHeap,
 ) -> PyResult<FrozenValue> {
     if value.is_none() {
         Ok(FrozenValue::new_none())
+    } else if let Some(converted) = convert_py(value)? {
+        match converted {
+            Ok(x) => sl_frozen_value_from_py(&x, heap),
+            Err(x) => Ok(heap.alloc(SlPyObject::new(&x))),
+        }
     } else if let Ok(x) = value.extract::<bool>() {
         Ok(FrozenValue::new_bool(x))
     } else if let Ok(x) = value.extract::<i64>() {
@@ -66,44 +79,80 @@ pub(crate) fn sl_frozen_value_from_py(
         Err(PyValueError::new_err(
             "Value must be frozen before use in this context",
         ))
+    } else if let Some(schema) = registry::schema_of_class(value) {
+        Ok(heap.alloc_simple(SlPyClass::new(value, schema)))
     } else {
-        Ok(heap.alloc(SlPyObject::from(value.clone().unbind())))
+        Ok(heap.alloc(SlPyObject::new(value)))
     }
 }
 
-pub(crate) fn sl_value_from_py<'v>(value: &Bound<'_, PyAny>, heap: &'v Heap) -> Value<'v> {
+pub(crate) fn sl_value_from_py<'v>(
+    value: &Bound<'_, PyAny>,
+    heap: &'v Heap,
+) -> PyResult<Value<'v>> {
     if value.is_none() {
-        Value::new_none()
+        Ok(Value::new_none())
+    } else if let Some(converted) = convert_py(value)? {
+        match converted {
+            Ok(x) => sl_value_from_py(&x, heap),
+            Err(x) => Ok(heap.alloc(SlPyObject::new(&x))),
+        }
     } else if let Ok(x) = value.extract::<bool>() {
-        Value::new_bool(x)
+        Ok(Value::new_bool(x))
     } else if let Ok(x) = value.extract::<i64>() {
-        heap.alloc(x)
+        Ok(heap.alloc(x))
     } else if let Ok(x) = value.extract::<u64>() {
-        heap.alloc(x)
+        Ok(heap.alloc(x))
     } else if let Ok(x) = value.extract::<num_bigint::BigInt>() {
-        heap.alloc(x)
+        Ok(heap.alloc(x))
     } else if let Ok(x) = value.extract::<f64>() {
-        heap.alloc(x)
+        Ok(heap.alloc(x))
     } else if let Ok(x) = value.extract::<String>() {
-        heap.alloc(x)
+        Ok(heap.alloc(x))
     } else if let Ok(x) = value.downcast::<PyTuple>() {
-        let entries = x.iter_borrowed().map(|elem| sl_value_from_py(&elem, heap));
-        heap.alloc(AllocTuple(entries))
+        let entries = x
+            .iter_borrowed()
+            .map(|elem| sl_value_from_py(&elem, heap))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(heap.alloc(AllocTuple(entries)))
     } else if let Ok(x) = value.downcast::<PyList>() {
-        let entries = x.into_iter().map(|elem| sl_value_from_py(&elem, heap));
-        heap.alloc(AllocList(entries))
+        let entries = x
+            .into_iter()
+            .map(|elem| sl_value_from_py(&elem, heap))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(heap.alloc(AllocList(entries)))
     } else if let Ok(x) = value.downcast::<PyDict>() {
         let entries = x
             .into_iter()
-            .map(|(k, v)| (sl_value_from_py(&k, heap), sl_value_from_py(&v, heap)));
-        heap.alloc(AllocDict(entries))
+            .map(|(k, v)| Ok((sl_value_from_py(&k, heap)?, sl_value_from_py(&v, heap)?)))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(heap.alloc(AllocDict(entries)))
     } else if let Ok(x) = value.downcast::<PyFrozenValue>() {
-        x.borrow().0.to_value()
+        Ok(x.borrow().0.to_value())
     } else if let Ok(x) = value.downcast::<PyValue>() {
         // XXX: This is going to cause problems when value is shared cross-heap,
         // so more design is needed to correctly track each value's belonging heap.
-        unsafe { ::core::mem::transmute(x.borrow().0) }
+        Ok(unsafe { ::core::mem::transmute(x.borrow().0) })
+    } else if let Some(schema) = registry::schema_of_class(value) {
+        Ok(heap.alloc_simple(SlPyClass::new(value, schema)))
     } else {
-        heap.alloc(SlPyObject::from(value.clone().unbind()))
+        Ok(heap.alloc(SlPyObject::new(value)))
+    }
+}
+
+/// Applies the converter registered for the class of `value`, if any. The
+/// result is `Err` if it is handled by the same converter again, and has to
+/// be passed as is to avoid an endless loop.
+fn convert_py<'py>(
+    value: &Bound<'py, PyAny>,
+) -> PyResult<Option<Result<Bound<'py, PyAny>, Bound<'py, PyAny>>>> {
+    let converter = match registry::converter_of_instance(value) {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let converted = converter.bind(value.py()).call1((value,))?;
+    match registry::converter_of_instance(&converted) {
+        Some(x) if x.is(&converter) => Ok(Some(Err(converted))),
+        _ => Ok(Some(Ok(converted))),
     }
 }
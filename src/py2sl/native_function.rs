@@ -0,0 +1,376 @@
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+use starlark::environment::GlobalsBuilder;
+use starlark::eval::{Arguments, Evaluator, ParametersSpec};
+use starlark::typing::Ty;
+use starlark::values::dict::DictRef;
+use starlark::values::function::{NativeCallableRawDocs, NativeFunc};
+use starlark::values::tuple::TupleRef;
+use starlark::values::{FrozenValue, Value};
+
+use crate::eval::with_call_stack_exposed;
+use crate::py2sl::{sl_frozen_value_from_py, sl_value_err_from_py, sl_value_from_py};
+use crate::sl2py::py_from_sl_value;
+use crate::typing::ty_from_type_expr;
+
+// NOTE: keep the values in sync with inspect._ParameterKind
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PyParamKind {
+    PositionalOnly,
+    PositionalOrKeyword,
+    VarPositional,
+    KeywordOnly,
+    VarKeyword,
+}
+
+impl TryFrom<u8> for PyParamKind {
+    type Error = PyErr;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::PositionalOnly),
+            1 => Ok(Self::PositionalOrKeyword),
+            2 => Ok(Self::VarPositional),
+            3 => Ok(Self::KeywordOnly),
+            4 => Ok(Self::VarKeyword),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown parameter kind {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// A Python callable exposed to Starlark as a native function, with arguments
+/// checked by Starlark according to its signature.
+struct PyNativeFunc {
+    callable: PyObject,
+    spec: ParametersSpec<FrozenValue>,
+    param_kinds: Vec<(String, PyParamKind)>,
+}
+
+impl PyNativeFunc {
+    fn call_py<'v>(
+        &self,
+        py: Python<'_>,
+        slots: &[Cell<Option<Value<'v>>>],
+        eval: &mut Evaluator<'v, '_>,
+    ) -> PyResult<Value<'v>> {
+        let mut args = Vec::with_capacity(slots.len());
+        let kwargs = PyDict::new_bound(py);
+        for ((name, kind), slot) in self.param_kinds.iter().zip(slots) {
+            let sl = match slot.get() {
+                Some(sl) => sl,
+                None => continue,
+            };
+            match kind {
+                PyParamKind::PositionalOnly | PyParamKind::PositionalOrKeyword => {
+                    args.push(py_from_sl_value(py, sl)?);
+                }
+                PyParamKind::KeywordOnly => {
+                    kwargs.set_item(name, py_from_sl_value(py, sl)?)?;
+                }
+                PyParamKind::VarPositional => {
+                    if let Some(x) = TupleRef::from_value(sl) {
+                        for elem in x.content() {
+                            args.push(py_from_sl_value(py, *elem)?);
+                        }
+                    }
+                }
+                PyParamKind::VarKeyword => {
+                    if let Some(x) = DictRef::from_value(sl) {
+                        for (k, v) in x.iter() {
+                            kwargs.set_item(py_from_sl_value(py, k)?, py_from_sl_value(py, v)?)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let result = self
+            .callable
+            .bind(py)
+            .call(PyTuple::new_bound(py, args), Some(&kwargs))?;
+        sl_value_from_py(&result, eval.heap())
+    }
+}
+
+impl NativeFunc for PyNativeFunc {
+    fn invoke<'v>(
+        &self,
+        eval: &mut Evaluator<'v, '_>,
+        args: &Arguments<'v, '_>,
+    ) -> starlark::Result<Value<'v>> {
+        let slots: Vec<_> = (0..self.spec.len()).map(|_| Cell::new(None)).collect();
+        self.spec.collect(args, &slots, eval.heap())?;
+        Python::with_gil(|py| with_call_stack_exposed(eval, |eval| self.call_py(py, &slots, eval)))
+            .map_err(sl_value_err_from_py)
+    }
+}
+
+static DOCSTRINGS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+/// Returns a `'static` copy of `docstring`.
+///
+/// The docs of native functions are static in starlark-rust, because they are
+/// usually generated from Rust doc comments, so each distinct docstring is
+/// leaked once and shared from then on, keeping memory bounded however often
+/// globals are rebuilt.
+fn intern_docstring(docstring: &str) -> &'static str {
+    let mut docstrings = DOCSTRINGS.lock().unwrap();
+    match docstrings.get(docstring) {
+        Some(x) => x,
+        None => {
+            let x: &'static str = Box::leak(docstring.to_owned().into_boxed_str());
+            docstrings.insert(x);
+            x
+        }
+    }
+}
+
+/// Registers a Python callable as a Starlark native function named `name`.
+///
+/// The parameter list is taken from `signature` (an `inspect.Signature`), or
+/// the callable's own signature if not given. Types are given as Starlark type
+/// expressions, with `parameter_types` keyed by parameter name; parameters
+/// without a declared type accept anything.
+pub(crate) fn set_py_function(
+    builder: &mut GlobalsBuilder,
+    name: &str,
+    callable: &Bound<'_, PyAny>,
+    signature: Option<&Bound<'_, PyAny>>,
+    docstring: Option<String>,
+    parameter_types: Option<&Bound<'_, PyDict>>,
+    return_type: Option<&str>,
+) -> PyResult<()> {
+    let py = callable.py();
+    let inspect = py.import_bound(intern!(py, "inspect"))?;
+
+    let signature = match signature {
+        Some(x) => x.clone(),
+        None => inspect.call_method1(intern!(py, "signature"), (callable,))?,
+    };
+    // inspect.Signature validates the order and uniqueness of parameters upon
+    // construction, which ParametersSpecBuilder would otherwise assert
+    if !signature.is_instance(&inspect.getattr(intern!(py, "Signature"))?)? {
+        return Err(PyTypeError::new_err(
+            "signature must be an inspect.Signature",
+        ));
+    }
+    let empty = inspect
+        .getattr(intern!(py, "Parameter"))?
+        .getattr(intern!(py, "empty"))?;
+
+    let docstring = match docstring {
+        Some(x) => Some(x),
+        None => inspect
+            .call_method1(intern!(py, "getdoc"), (callable,))?
+            .extract::<Option<String>>()?,
+    };
+
+    let params = signature
+        .getattr(intern!(py, "parameters"))?
+        .call_method0(intern!(py, "values"))?;
+    let mut spec = ParametersSpec::with_capacity(name.to_owned(), params.len()?);
+    let mut param_kinds = Vec::with_capacity(params.len()?);
+    let mut param_types = Vec::with_capacity(params.len()?);
+    let mut seen_positional = false;
+    let mut seen_named_only = false;
+    for param in params.iter()? {
+        let param = param?;
+        let param_name: String = param.getattr(intern!(py, "name"))?.extract()?;
+        let kind = PyParamKind::try_from(param.getattr(intern!(py, "kind"))?.extract::<u8>()?)?;
+
+        if kind != PyParamKind::PositionalOnly && !seen_positional {
+            spec.no_more_positional_only_args();
+            seen_positional = true;
+        }
+        match kind {
+            PyParamKind::VarPositional => {
+                spec.args();
+                seen_named_only = true;
+            }
+            PyParamKind::VarKeyword => spec.kwargs(),
+            _ => {
+                if kind == PyParamKind::KeywordOnly && !seen_named_only {
+                    spec.no_more_positional_args();
+                    seen_named_only = true;
+                }
+                let default = param.getattr(intern!(py, "default"))?;
+                if default.is(&empty) {
+                    spec.required(&param_name);
+                } else {
+                    let default = sl_frozen_value_from_py(&default, builder.frozen_heap())?;
+                    spec.defaulted(&param_name, default);
+                }
+            }
+        }
+
+        let ty = match parameter_types
+            .map(|x| x.get_item(&param_name))
+            .transpose()?
+            .flatten()
+        {
+            Some(expr) => ty_from_type_expr(py, &expr.extract::<String>()?)?,
+            None => Ty::any(),
+        };
+        param_types.push(ty);
+        param_kinds.push((param_name, kind));
+    }
+    let spec = spec.finish();
+
+    let return_type = match return_type {
+        Some(expr) => ty_from_type_expr(py, expr)?,
+        None => Ty::any(),
+    };
+
+    let raw_docs = NativeCallableRawDocs {
+        rust_docstring: docstring.map(|x| intern_docstring(&x)),
+        signature: spec.clone(),
+        parameter_types: param_types,
+        return_type,
+        as_type: None,
+    };
+    let f = PyNativeFunc {
+        callable: callable.clone().unbind(),
+        spec,
+        param_kinds,
+    };
+    builder.set_function(name, false, raw_docs, None, None, None, f);
+    Ok(())
+}
+
+/// Translates a Python type annotation into a Starlark type expression, or
+/// `None` if it has no Starlark counterpart.
+///
+/// Strings are taken verbatim as Starlark type expressions. Unrecognized types
+/// nested inside recognized generics become `typing.Any`.
+pub(crate) fn type_expr_from_annotation(ann: &Bound<'_, PyAny>) -> PyResult<Option<String>> {
+    let py = ann.py();
+    if let Ok(x) = ann.extract::<String>() {
+        return Ok(Some(x));
+    }
+    if ann.is_none() || ann.is(&py.None().bind(py).get_type()) {
+        return Ok(Some("None".to_owned()));
+    }
+
+    let builtins = py.import_bound(intern!(py, "builtins"))?;
+    for name in ["bool", "int", "float", "str", "list", "dict", "tuple"] {
+        if ann.is(&builtins.getattr(name)?) {
+            return Ok(Some(name.to_owned()));
+        }
+    }
+
+    let typing = py.import_bound(intern!(py, "typing"))?;
+    let origin = typing.call_method1(intern!(py, "get_origin"), (ann,))?;
+    if origin.is_none() {
+        return Ok(None);
+    }
+    let args: Vec<Bound<'_, PyAny>> = typing
+        .call_method1(intern!(py, "get_args"), (ann,))?
+        .extract()?;
+    let nested = |x: &Bound<'_, PyAny>| -> PyResult<String> {
+        Ok(type_expr_from_annotation(x)?.unwrap_or_else(|| "typing.Any".to_owned()))
+    };
+
+    if origin.is(&builtins.getattr("list")?) {
+        return Ok(Some(match args.as_slice() {
+            [x] => format!("list[{}]", nested(x)?),
+            _ => "list".to_owned(),
+        }));
+    }
+    if origin.is(&builtins.getattr("dict")?) {
+        return Ok(Some(match args.as_slice() {
+            [k, v] => format!("dict[{}, {}]", nested(k)?, nested(v)?),
+            _ => "dict".to_owned(),
+        }));
+    }
+    if origin.is(&builtins.getattr("tuple")?) {
+        return Ok(Some(match args.as_slice() {
+            [] => "tuple".to_owned(),
+            [x, rest] if rest.is(&py.Ellipsis()) => format!("tuple[{}, ...]", nested(x)?),
+            _ => {
+                let elems = args.iter().map(nested).collect::<PyResult<Vec<_>>>()?;
+                format!("({},)", elems.join(", "))
+            }
+        }));
+    }
+
+    // types.UnionType (for `X | Y`) is only available since Python 3.10
+    let union_type = py
+        .import_bound(intern!(py, "types"))?
+        .getattr(intern!(py, "UnionType"))
+        .ok();
+    if origin.is(&typing.getattr(intern!(py, "Union"))?)
+        || union_type.is_some_and(|x| origin.is(&x))
+    {
+        // a union is only as precise as its least precise member
+        let mut members = Vec::with_capacity(args.len());
+        for x in &args {
+            match type_expr_from_annotation(x)? {
+                Some(x) => members.push(x),
+                None => return Ok(None),
+            }
+        }
+        return Ok(Some(members.join(" | ")));
+    }
+
+    let abc = py.import_bound(intern!(py, "collections.abc"))?;
+    for name in ["Callable", "Iterable"] {
+        if origin.is(&abc.getattr(name)?) {
+            return Ok(Some(format!("typing.{}", name)));
+        }
+    }
+    Ok(None)
+}
+
+/// The type annotations of a Python callable or class.
+pub(crate) fn type_hints<'py>(obj: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyDict>> {
+    let py = obj.py();
+    let typing = py.import_bound(intern!(py, "typing"))?;
+    // get_type_hints resolves string annotations (e.g. with `from __future__
+    // import annotations`), but fails on unresolvable forward references, in
+    // which case we fall back to the raw annotations
+    let hints = match typing.call_method1(intern!(py, "get_type_hints"), (obj,)) {
+        Ok(x) => x,
+        Err(_) => match obj.getattr(intern!(py, "__annotations__")) {
+            Ok(x) => x,
+            Err(_) => PyDict::new_bound(py).into_any(),
+        },
+    };
+    Ok(hints.downcast_into::<PyDict>()?)
+}
+
+/// Derives the Starlark parameter and return types of a Python callable from
+/// its type annotations.
+///
+/// Annotations that cannot be expressed in Starlark are left out, making the
+/// corresponding parameter accept anything.
+pub(crate) fn types_from_annotations<'py>(
+    callable: &Bound<'py, PyAny>,
+) -> PyResult<(Bound<'py, PyDict>, Option<String>)> {
+    let py = callable.py();
+    let hints = type_hints(callable)?;
+
+    let parameter_types = PyDict::new_bound(py);
+    let mut return_type = None;
+    for (k, v) in hints.iter() {
+        let name: String = k.extract()?;
+        let expr = match type_expr_from_annotation(&v)? {
+            Some(x) => x,
+            None => continue,
+        };
+        if name == "return" {
+            return_type = Some(expr);
+        } else {
+            parameter_types.set_item(name, expr)?;
+        }
+    }
+    Ok((parameter_types, return_type))
+}
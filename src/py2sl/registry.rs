@@ -0,0 +1,192 @@
+use std::sync::{Arc, Mutex};
+
+use allocative::Allocative;
+use pyo3::exceptions::PyTypeError;
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyType};
+use starlark::typing::{Ty, TyStarlarkValue, TyUser, TyUserFields, TyUserParams};
+use starlark::values::typing::{TypeInstanceId, TypeMatcher, TypeMatcherFactory};
+use starlark::values::{Value, ValueLike};
+use starlark_map::sorted_map::SortedMap;
+
+use super::native_function::{type_expr_from_annotation, type_hints};
+use super::SlPyObject;
+use crate::typing::ty_from_type_expr;
+
+/// The declared shape of a Python class registered with `register_type`.
+pub(crate) struct TypeSchema {
+    class: Py<PyType>,
+    pub(crate) name: String,
+    /// Names of the declared attributes, sorted.
+    pub(crate) attrs: Vec<String>,
+    id: TypeInstanceId,
+    /// The type of instances of the class, as seen by the typechecker.
+    pub(crate) ty: Ty,
+}
+
+impl TypeSchema {
+    pub(crate) fn has_attr(&self, attribute: &str) -> bool {
+        self.attrs
+            .binary_search_by(|x| x.as_str().cmp(attribute))
+            .is_ok()
+    }
+}
+
+static REGISTRY: Mutex<Vec<Arc<TypeSchema>>> = Mutex::new(Vec::new());
+
+/// The schema for `value`, taken from the nearest registered class in its
+/// MRO.
+pub(crate) fn schema_of_instance(value: &Bound<'_, PyAny>) -> Option<Arc<TypeSchema>> {
+    let registry = REGISTRY.lock().unwrap();
+    if registry.is_empty() {
+        return None;
+    }
+    let py = value.py();
+    value.get_type().mro().iter().find_map(|class| {
+        registry
+            .iter()
+            .find(|x| x.class.bind(py).is(&class))
+            .cloned()
+    })
+}
+
+/// The schema for `value` if it is itself a registered class.
+pub(crate) fn schema_of_class(value: &Bound<'_, PyAny>) -> Option<Arc<TypeSchema>> {
+    let py = value.py();
+    REGISTRY
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|x| x.class.bind(py).is(value))
+        .cloned()
+}
+
+static CONVERTERS: Mutex<Vec<(Py<PyType>, PyObject)>> = Mutex::new(Vec::new());
+
+/// The converter registered for the class of `value`, taken from the nearest
+/// registered class in its MRO.
+pub(crate) fn converter_of_instance(value: &Bound<'_, PyAny>) -> Option<PyObject> {
+    let converters = CONVERTERS.lock().unwrap();
+    if converters.is_empty() {
+        return None;
+    }
+    let py = value.py();
+    value.get_type().mro().iter().find_map(|class| {
+        converters
+            .iter()
+            .find(|x| x.0.bind(py).is(&class))
+            .map(|x| x.1.clone_ref(py))
+    })
+}
+
+/// Matches the Starlark values of instances of a registered class.
+#[derive(Clone, Debug, Allocative)]
+struct SchemaMatcher {
+    id: TypeInstanceId,
+}
+
+impl TypeMatcher for SchemaMatcher {
+    fn matches(&self, value: Value) -> bool {
+        match value.downcast_ref::<SlPyObject>() {
+            Some(x) => x.schema().is_some_and(|x| x.id == self.id),
+            None => false,
+        }
+    }
+}
+
+/// Registers a Python class, so that its instances appear in Starlark as
+/// values of their own type.
+///
+/// The declared attributes and their types default to the annotations of the
+/// class. Only the declared attributes are visible from Starlark, and their
+/// types are known to the static typechecker. The type is known under `name`
+/// to annotations and the typechecker only: `type()` of the instances is still
+/// `pyobject`, as starlark-rust fixes it per Rust type.
+#[pyfunction]
+#[pyo3(signature = (cls, *, name = None, attrs = None))]
+pub(crate) fn register_type<'py>(
+    cls: &Bound<'py, PyType>,
+    name: Option<String>,
+    attrs: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyType>> {
+    let py = cls.py();
+    let name = match name {
+        Some(x) => x,
+        None => cls.getattr(intern!(py, "__name__"))?.extract()?,
+    };
+    let attrs = match attrs {
+        Some(x) => x.clone(),
+        None => type_hints(cls.as_any())?,
+    };
+
+    let mut fields = Vec::with_capacity(attrs.len());
+    for (k, v) in attrs.iter() {
+        let attr: String = k.extract()?;
+        let ty = match type_expr_from_annotation(&v)? {
+            Some(expr) => ty_from_type_expr(py, &expr)?,
+            None => Ty::any(),
+        };
+        fields.push((attr, ty));
+    }
+    let attr_names = {
+        let mut tmp: Vec<_> = fields.iter().map(|(k, _)| k.clone()).collect();
+        tmp.sort();
+        tmp
+    };
+
+    let id = TypeInstanceId::gen();
+    let ty = Ty::custom(TyUser::new(
+        name.clone(),
+        TyStarlarkValue::new::<SlPyObject>(),
+        id,
+        TyUserParams {
+            matcher: Some(TypeMatcherFactory::new(SchemaMatcher { id })),
+            fields: TyUserFields {
+                known: fields.into_iter().collect::<SortedMap<_, _>>(),
+                unknown: false,
+            },
+            ..TyUserParams::default()
+        },
+    )?);
+
+    let schema = Arc::new(TypeSchema {
+        class: cls.clone().unbind(),
+        name,
+        attrs: attr_names,
+        id,
+        ty,
+    });
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.retain(|x| !x.class.bind(py).is(cls));
+    registry.push(schema);
+    Ok(cls.clone())
+}
+
+/// Registers a converter for instances of a Python class and its subclasses,
+/// called with such an instance whenever it is passed to Starlark.
+///
+/// The converter returns the value to pass instead, which is converted in
+/// turn, so it is usually one with a Starlark counterpart like a `str` or a
+/// `list`. Values converted this way come back to Python as their Starlark
+/// counterparts. A result handled by the same converter is passed as an opaque
+/// value. `None` removes the converter registered for the class.
+#[pyfunction]
+#[pyo3(signature = (cls, to_starlark))]
+pub(crate) fn register_converter(
+    cls: &Bound<'_, PyType>,
+    to_starlark: Option<PyObject>,
+) -> PyResult<()> {
+    let py = cls.py();
+    if let Some(x) = &to_starlark {
+        if !x.bind(py).is_callable() {
+            return Err(PyTypeError::new_err("the converter must be callable"));
+        }
+    }
+    let mut converters = CONVERTERS.lock().unwrap();
+    converters.retain(|x| !x.0.bind(py).is(cls));
+    if let Some(x) = to_starlark {
+        converters.push((cls.clone().unbind(), x));
+    }
+    Ok(())
+}
@@ -1,28 +1,42 @@
 use core::cmp::Ordering;
 use std::hash::Hasher;
+use std::sync::Arc;
 
 use allocative::Allocative;
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
+use pyo3::types::PyIterator;
+use pyo3::types::PySlice;
 use pyo3::types::PyTuple;
 use starlark::any::ProvidesStaticType;
 use starlark::collections::StarlarkHasher;
 use starlark::eval::{Arguments, Evaluator};
+use starlark::typing::Ty;
 use starlark::values::{
     starlark_value, AllocFrozenValue, AllocValue, Freeze, Freezer, FrozenHeap, FrozenValue, Heap,
-    NoSerialize, StarlarkValue, Trace, Value,
+    NoSerialize, StarlarkValue, Trace, Value, ValueError, ValueLike,
 };
 
-use crate::py2sl::sl_value_from_py;
+use super::registry::{schema_of_instance, TypeSchema};
+use crate::eval::{set_pending_error, with_call_stack_exposed};
+use crate::py2sl::{sl_value_err_from_py, sl_value_from_py};
 use crate::sl2py::py_from_sl_value;
 
 #[derive(Trace, NoSerialize, ProvidesStaticType, Allocative)]
-pub(crate) struct SlPyObject(#[allocative(skip)] pub(crate) PyObject);
+pub(crate) struct SlPyObject(
+    #[allocative(skip)] pub(crate) PyObject,
+    #[allocative(skip)] Option<Arc<TypeSchema>>,
+);
+
+impl SlPyObject {
+    pub(crate) fn new(value: &Bound<'_, PyAny>) -> Self {
+        Self(value.clone().unbind(), schema_of_instance(value))
+    }
 
-impl From<PyObject> for SlPyObject {
-    fn from(value: PyObject) -> Self {
-        Self(value)
+    /// The schema of the object's class, if registered with `register_type`.
+    pub(crate) fn schema(&self) -> Option<&TypeSchema> {
+        self.1.as_deref()
     }
 }
 
@@ -58,33 +72,24 @@ impl Freeze for SlPyObject {
     }
 }
 
-fn sl_value_err_from_py(e: PyErr) -> starlark::Error {
-    starlark::Error::new(starlark::ErrorKind::Value(e.into()))
-}
-
 #[starlark_value(type = "pyobject")]
 impl<'v> StarlarkValue<'v> for SlPyObject {
     type Canonical = Self;
 
+    fn typechecker_ty(&self) -> Option<Ty> {
+        self.schema().map(|x| x.ty.clone())
+    }
+
+    fn matches_type(&self, ty: &str) -> bool {
+        ty == Self::TYPE || self.schema().is_some_and(|x| x.name == ty)
+    }
+
     fn to_bool(&self) -> bool {
-        let result: PyResult<bool> = Python::with_gil(|py| {
-            let inner = self.0.bind(py);
-            inner.is_truthy()
-        });
-        result.unwrap_or(true)
+        to_bool_py(&self.0)
     }
 
     fn write_hash(&self, hasher: &mut StarlarkHasher) -> starlark::Result<()> {
-        Python::with_gil(|py| {
-            let inner = self.0.bind(py);
-            match inner.hash() {
-                Ok(hash) => {
-                    hasher.write_isize(hash);
-                    Ok(())
-                }
-                Err(e) => Err(sl_value_err_from_py(e)),
-            }
-        })
+        write_hash_py(&self.0, hasher)
     }
 
     fn equals(&self, other: Value<'v>) -> starlark::Result<bool> {
@@ -101,7 +106,20 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
         let result = Python::with_gil(|py| {
             let inner = self.0.bind(py);
             let other = py_from_sl_value(py, other)?;
-            inner.compare(other)
+            let other = other.bind(py);
+            if inner.eq(other)? {
+                Ok(Ordering::Equal)
+            } else if inner.lt(other)? {
+                Ok(Ordering::Less)
+            } else if inner.gt(other)? {
+                Ok(Ordering::Greater)
+            } else {
+                Err(PyTypeError::new_err(format!(
+                    "instances of '{}' and '{}' are not comparable",
+                    inner.get_type().qualname()?,
+                    other.get_type().qualname()?,
+                )))
+            }
         });
 
         result.map_err(sl_value_err_from_py)
@@ -113,65 +131,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
         args: &Arguments<'v, '_>,
         eval: &mut Evaluator<'v, '_>,
     ) -> starlark::Result<Value<'v>> {
-        let heap = eval.heap();
-        let result: PyResult<Value<'v>> = Python::with_gil(|py| {
-            let inner = self.0.bind(py);
-
-            let py_args = {
-                let mut result = Vec::new();
-                match args.positions(heap) {
-                    Ok(sl_args) => {
-                        for sl in sl_args {
-                            result.push(py_from_sl_value(py, sl)?);
-                        }
-                    }
-                    Err(e) => {
-                        return Err(PyRuntimeError::new_err(format!(
-                            "failed to unpack Starlark positional args: {}",
-                            e.to_string()
-                        )));
-                    }
-                }
-                PyTuple::new_bound(py, result)
-            };
-
-            let py_kwargs = match args.names_map() {
-                Ok(sl_kwargs) => {
-                    if sl_kwargs.len() == 0 {
-                        None
-                    } else {
-                        let result = PyDict::new_bound(py);
-                        for (k, v) in sl_kwargs {
-                            let k = k.as_str();
-                            match py_from_sl_value(py, v) {
-                                Ok(v) => {
-                                    if let Err(e) = result.set_item(k, v) {
-                                        return Err(e);
-                                    }
-                                }
-                                Err(e) => {
-                                    return Err(e);
-                                }
-                            }
-                        }
-                        Some(result)
-                    }
-                }
-
-                Err(e) => {
-                    return Err(PyRuntimeError::new_err(format!(
-                        "failed to unpack Starlark keyword args: {}",
-                        e.to_string()
-                    )));
-                }
-            };
-
-            inner
-                .call(py_args, py_kwargs.as_ref())
-                .map(|v| sl_value_from_py(&v, heap))
-        });
-
-        result.map_err(sl_value_err_from_py)
+        invoke_py(&self.0, args, eval)
     }
 
     fn length(&self) -> starlark::Result<i32> {
@@ -194,11 +154,70 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
         result.map_err(sl_value_err_from_py)
     }
 
+    unsafe fn iterate(&self, _me: Value<'v>, heap: &'v Heap) -> starlark::Result<Value<'v>> {
+        let result = Python::with_gil(|py| {
+            let inner = self.0.bind(py);
+            inner
+                .iter()
+                .map(|it| heap.alloc_simple(SlPyIterator(it.into_any().unbind())))
+        });
+
+        result.map_err(sl_value_err_from_py)
+    }
+
+    fn at(&self, index: Value<'v>, heap: &'v Heap) -> starlark::Result<Value<'v>> {
+        let result = Python::with_gil(|py| {
+            let inner = self.0.bind(py);
+            let index = py_from_sl_value(py, index)?;
+            inner
+                .get_item(index)
+                .and_then(|v| sl_value_from_py(&v, heap))
+        });
+
+        result.map_err(sl_value_err_from_py)
+    }
+
+    fn slice(
+        &self,
+        start: Option<Value<'v>>,
+        stop: Option<Value<'v>>,
+        stride: Option<Value<'v>>,
+        heap: &'v Heap,
+    ) -> starlark::Result<Value<'v>> {
+        let result = Python::with_gil(|py| {
+            let inner = self.0.bind(py);
+            let convert = |v: Option<Value<'v>>| match v {
+                Some(v) => py_from_sl_value(py, v),
+                None => Ok(py.None()),
+            };
+            let slice = py.get_type_bound::<PySlice>().call1((
+                convert(start)?,
+                convert(stop)?,
+                convert(stride)?,
+            ))?;
+            inner
+                .get_item(slice)
+                .and_then(|v| sl_value_from_py(&v, heap))
+        });
+
+        result.map_err(sl_value_err_from_py)
+    }
+
+    fn set_at(&self, index: Value<'v>, new_value: Value<'v>) -> starlark::Result<()> {
+        Python::with_gil(|py| {
+            let inner = self.0.bind(py);
+            let index = py_from_sl_value(py, index)?;
+            let new_value = py_from_sl_value(py, new_value)?;
+            inner.set_item(index, new_value)
+        })
+        .map_err(sl_value_err_from_py)
+    }
+
     fn plus(&self, heap: &'v Heap) -> starlark::Result<Value<'v>> {
         Python::with_gil(|py| {
             let inner = self.0.bind(py);
             match inner.pos() {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -208,46 +227,39 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
         Python::with_gil(|py| {
             let inner = self.0.bind(py);
             match inner.neg() {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
     }
 
     fn get_attr(&self, attribute: &str, heap: &'v Heap) -> Option<Value<'v>> {
-        Python::with_gil(|py| {
-            let inner = self.0.bind(py);
-            // no way to propagate error with this interface
-            if let Some(v) = inner.getattr(attribute).ok() {
-                Some(sl_value_from_py(&v, heap))
-            } else {
-                None
-            }
-        })
+        if self.schema().is_some_and(|x| !x.has_attr(attribute)) {
+            return None;
+        }
+        get_attr_py(&self.0, attribute, heap)
     }
 
     fn has_attr(&self, attribute: &str, _heap: &'v Heap) -> bool {
-        Python::with_gil(|py| {
-            let inner = self.0.bind(py);
-            // no way to propagate error with this interface
-            inner.hasattr(attribute)
-        })
-        .unwrap_or(false)
+        if self.schema().is_some_and(|x| !x.has_attr(attribute)) {
+            return false;
+        }
+        has_attr_py(&self.0, attribute)
     }
 
     fn dir_attr(&self) -> Vec<String> {
-        Python::with_gil(|py| {
-            let inner = self.0.bind(py);
-            inner
-                .dir()
-                .unwrap() // no way to propagate error with this interface
-                .into_iter()
-                .map(|x| x.extract::<String>().unwrap())
-                .collect()
-        })
+        if let Some(schema) = self.schema() {
+            return schema.attrs.clone();
+        }
+        dir_attr_py(&self.0)
     }
 
     fn set_attr(&self, attribute: &str, new_value: Value<'v>) -> starlark::Result<()> {
+        if let Some(schema) = self.schema() {
+            if !schema.has_attr(attribute) {
+                return ValueError::unsupported(self, &format!(".{}=", attribute));
+            }
+        }
         Python::with_gil(|py| {
             let inner = self.0.bind(py);
             let new_value = py_from_sl_value(py, new_value)?;
@@ -256,6 +268,21 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
         .map_err(sl_value_err_from_py)
     }
 
+    fn radd(&self, lhs: Value<'v>, heap: &'v Heap) -> Option<starlark::Result<Value<'v>>> {
+        Python::with_gil(|py| {
+            let inner = self.0.bind(py);
+            let lhs = match py_from_sl_value(py, lhs) {
+                Ok(lhs) => lhs,
+                Err(e) => return Some(Err(sl_value_err_from_py(e))),
+            };
+            // let Python dispatch to `__radd__`
+            match lhs.bind(py).add(inner) {
+                Ok(result) => Some(sl_value_from_py(&result, heap).map_err(sl_value_err_from_py)),
+                Err(e) => Some(Err(sl_value_err_from_py(e))),
+            }
+        })
+    }
+
     fn add(&self, rhs: Value<'v>, heap: &'v Heap) -> Option<starlark::Result<Value<'v>>> {
         Python::with_gil(|py| {
             let inner = self.0.bind(py);
@@ -264,7 +291,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Some(Err(sl_value_err_from_py(e))),
             };
             match inner.add(rhs.bind(py)) {
-                Ok(result) => Some(Ok(sl_value_from_py(&result, heap))),
+                Ok(result) => Some(sl_value_from_py(&result, heap).map_err(sl_value_err_from_py)),
                 Err(e) => Some(Err(sl_value_err_from_py(e))),
             }
         })
@@ -278,12 +305,27 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.sub(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
     }
 
+    fn rmul(&self, lhs: Value<'v>, heap: &'v Heap) -> Option<starlark::Result<Value<'v>>> {
+        Python::with_gil(|py| {
+            let inner = self.0.bind(py);
+            let lhs = match py_from_sl_value(py, lhs) {
+                Ok(lhs) => lhs,
+                Err(e) => return Some(Err(sl_value_err_from_py(e))),
+            };
+            // let Python dispatch to `__rmul__`
+            match lhs.bind(py).mul(inner) {
+                Ok(result) => Some(sl_value_from_py(&result, heap).map_err(sl_value_err_from_py)),
+                Err(e) => Some(Err(sl_value_err_from_py(e))),
+            }
+        })
+    }
+
     fn mul(&self, rhs: Value<'v>, heap: &'v Heap) -> Option<starlark::Result<Value<'v>>> {
         Python::with_gil(|py| {
             let inner = self.0.bind(py);
@@ -292,7 +334,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Some(Err(sl_value_err_from_py(e))),
             };
             match inner.mul(rhs.bind(py)) {
-                Ok(result) => Some(Ok(sl_value_from_py(&result, heap))),
+                Ok(result) => Some(sl_value_from_py(&result, heap).map_err(sl_value_err_from_py)),
                 Err(e) => Some(Err(sl_value_err_from_py(e))),
             }
         })
@@ -306,7 +348,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.div(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -320,7 +362,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.rem(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -334,7 +376,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.floor_div(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -348,7 +390,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.bitand(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -362,7 +404,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.bitor(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -376,7 +418,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.bitxor(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -386,7 +428,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
         Python::with_gil(|py| {
             let inner = self.0.bind(py);
             match inner.bitnot() {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -400,7 +442,7 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.lshift(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
@@ -414,9 +456,246 @@ impl<'v> StarlarkValue<'v> for SlPyObject {
                 Err(e) => return Err(sl_value_err_from_py(e)),
             };
             match inner.rshift(rhs.bind(py)) {
-                Ok(result) => Ok(sl_value_from_py(&result, heap)),
+                Ok(result) => sl_value_from_py(&result, heap).map_err(sl_value_err_from_py),
                 Err(e) => Err(sl_value_err_from_py(e)),
             }
         })
     }
 }
+
+fn to_bool_py(obj: &PyObject) -> bool {
+    let result: PyResult<bool> = Python::with_gil(|py| {
+        let inner = obj.bind(py);
+        inner.is_truthy()
+    });
+    result.unwrap_or(true)
+}
+
+fn write_hash_py(obj: &PyObject, hasher: &mut StarlarkHasher) -> starlark::Result<()> {
+    Python::with_gil(|py| {
+        let inner = obj.bind(py);
+        match inner.hash() {
+            Ok(hash) => {
+                hasher.write_isize(hash);
+                Ok(())
+            }
+            Err(e) => Err(sl_value_err_from_py(e)),
+        }
+    })
+}
+
+fn get_attr_py<'v>(obj: &PyObject, attribute: &str, heap: &'v Heap) -> Option<Value<'v>> {
+    Python::with_gil(|py| {
+        let inner = obj.bind(py);
+        // no way to propagate error with this interface
+        let v = inner.getattr(attribute).ok()?;
+        match sl_value_from_py(&v, heap) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                // see `SlPyIterator::iter_next`
+                if let Err(e) = set_pending_error(e) {
+                    e.write_unraisable_bound(py, Some(inner));
+                }
+                None
+            }
+        }
+    })
+}
+
+fn has_attr_py(obj: &PyObject, attribute: &str) -> bool {
+    Python::with_gil(|py| {
+        let inner = obj.bind(py);
+        // no way to propagate error with this interface
+        inner.hasattr(attribute)
+    })
+    .unwrap_or(false)
+}
+
+fn dir_attr_py(obj: &PyObject) -> Vec<String> {
+    Python::with_gil(|py| {
+        let inner = obj.bind(py);
+        inner
+            .dir()
+            .unwrap() // no way to propagate error with this interface
+            .into_iter()
+            .map(|x| x.extract::<String>().unwrap())
+            .collect()
+    })
+}
+
+fn invoke_py<'v>(
+    callable: &PyObject,
+    args: &Arguments<'v, '_>,
+    eval: &mut Evaluator<'v, '_>,
+) -> starlark::Result<Value<'v>> {
+    let heap = eval.heap();
+    let result: PyResult<Value<'v>> = Python::with_gil(|py| {
+        let inner = callable.bind(py);
+
+        let py_args = {
+            let mut result = Vec::new();
+            match args.positions(heap) {
+                Ok(sl_args) => {
+                    for sl in sl_args {
+                        result.push(py_from_sl_value(py, sl)?);
+                    }
+                }
+                Err(e) => {
+                    return Err(PyRuntimeError::new_err(format!(
+                        "failed to unpack Starlark positional args: {}",
+                        e.to_string()
+                    )));
+                }
+            }
+            PyTuple::new_bound(py, result)
+        };
+
+        let py_kwargs = match args.names_map() {
+            Ok(sl_kwargs) => {
+                if sl_kwargs.len() == 0 {
+                    None
+                } else {
+                    let result = PyDict::new_bound(py);
+                    for (k, v) in sl_kwargs {
+                        let k = k.as_str();
+                        match py_from_sl_value(py, v) {
+                            Ok(v) => {
+                                if let Err(e) = result.set_item(k, v) {
+                                    return Err(e);
+                                }
+                            }
+                            Err(e) => {
+                                return Err(e);
+                            }
+                        }
+                    }
+                    Some(result)
+                }
+            }
+
+            Err(e) => {
+                return Err(PyRuntimeError::new_err(format!(
+                    "failed to unpack Starlark keyword args: {}",
+                    e.to_string()
+                )));
+            }
+        };
+
+        with_call_stack_exposed(eval, |_| inner.call(py_args, py_kwargs.as_ref()))
+            .and_then(|v| sl_value_from_py(&v, heap))
+    });
+
+    result.map_err(sl_value_err_from_py)
+}
+
+/// A Python class registered with `register_type`, which can be called to
+/// make instances and used as a type in Starlark.
+///
+/// This is kept apart from `SlPyObject`, because types implementing
+/// `eval_type` cannot have `|` forwarded to Python.
+#[derive(Trace, NoSerialize, ProvidesStaticType, Allocative)]
+pub(crate) struct SlPyClass(
+    #[allocative(skip)] pub(crate) PyObject,
+    #[allocative(skip)] Arc<TypeSchema>,
+);
+
+impl SlPyClass {
+    pub(crate) fn new(value: &Bound<'_, PyAny>, schema: Arc<TypeSchema>) -> Self {
+        Self(value.clone().unbind(), schema)
+    }
+}
+
+impl ::core::fmt::Debug for SlPyClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ::core::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl ::std::fmt::Display for SlPyClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ::std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[starlark_value(type = "pyclass")]
+impl<'v> StarlarkValue<'v> for SlPyClass {
+    fn eval_type(&self) -> Option<Ty> {
+        Some(self.1.ty.clone())
+    }
+
+    fn to_bool(&self) -> bool {
+        to_bool_py(&self.0)
+    }
+
+    fn write_hash(&self, hasher: &mut StarlarkHasher) -> starlark::Result<()> {
+        write_hash_py(&self.0, hasher)
+    }
+
+    fn equals(&self, other: Value<'v>) -> starlark::Result<bool> {
+        Ok(other
+            .downcast_ref::<SlPyClass>()
+            .is_some_and(|x| Python::with_gil(|py| x.0.bind(py).is(self.0.bind(py)))))
+    }
+
+    fn invoke(
+        &self,
+        _me: Value<'v>,
+        args: &Arguments<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> starlark::Result<Value<'v>> {
+        invoke_py(&self.0, args, eval)
+    }
+
+    fn get_attr(&self, attribute: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        get_attr_py(&self.0, attribute, heap)
+    }
+
+    fn has_attr(&self, attribute: &str, _heap: &'v Heap) -> bool {
+        has_attr_py(&self.0, attribute)
+    }
+
+    fn dir_attr(&self) -> Vec<String> {
+        dir_attr_py(&self.0)
+    }
+}
+
+/// A Python iterator being iterated over by Starlark, obtained by `iter()` on
+/// a `SlPyObject` so that items are fetched lazily.
+#[derive(Trace, NoSerialize, ProvidesStaticType, Allocative)]
+struct SlPyIterator(#[allocative(skip)] PyObject);
+
+impl ::core::fmt::Debug for SlPyIterator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ::core::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl ::std::fmt::Display for SlPyIterator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ::std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[starlark_value(type = "pyiterator")]
+impl<'v> StarlarkValue<'v> for SlPyIterator {
+    unsafe fn iter_next(&self, _index: usize, heap: &'v Heap) -> Option<Value<'v>> {
+        Python::with_gil(|py| {
+            let mut inner = self.0.bind(py).downcast::<PyIterator>().ok()?.clone();
+            match inner.next()?.and_then(|v| sl_value_from_py(&v, heap)) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    // no way to propagate error with this interface, so end
+                    // the iteration and raise it when the evaluation returns,
+                    // or report it like Python does for errors in finalizers
+                    // if there is no evaluation to raise it from
+                    if let Err(e) = set_pending_error(e) {
+                        e.write_unraisable_bound(py, Some(inner.as_any()));
+                    }
+                    None
+                }
+            }
+        })
+    }
+
+    unsafe fn iter_stop(&self) {}
+}
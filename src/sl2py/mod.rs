@@ -8,7 +8,7 @@ use starlark::values::list::ListRef;
 use starlark::values::tuple::{FrozenTupleRef, TupleRef};
 use starlark::values::{FrozenValue, UnpackValue, Value, ValueLike};
 
-use crate::py2sl::SlPyObject;
+use crate::py2sl::{SlPyClass, SlPyObject};
 use crate::values::{PyFrozenValue, PyValue};
 
 mod native_function;
@@ -51,6 +51,8 @@ pub(crate) fn py_from_sl_frozen_value(py: Python<'_>, sl: FrozenValue) -> PyResu
         PySlNativeFunction::new_py_any(py, x.as_ref())
     } else if let Some(x) = sl.downcast_frozen_ref::<SlPyObject>() {
         Ok(x.0.clone_ref(py))
+    } else if let Some(x) = sl.downcast_frozen_ref::<SlPyClass>() {
+        Ok(x.0.clone_ref(py))
     } else {
         Ok(Py::new(py, PyFrozenValue::from(sl))?.into_any())
     }
@@ -93,6 +95,8 @@ pub(crate) fn py_from_sl_value(py: Python<'_>, sl: Value<'_>) -> PyResult<PyObje
         PySlNativeFunction::new_py_any(py, x)
     } else if let Some(x) = sl.downcast_ref::<SlPyObject>() {
         Ok(x.0.clone_ref(py))
+    } else if let Some(x) = sl.downcast_ref::<SlPyClass>() {
+        Ok(x.0.clone_ref(py))
     } else {
         Ok(Py::new(py, PyValue::from(sl))?.into_any())
     }
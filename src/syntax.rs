@@ -1,11 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::exceptions::PyRuntimeError;
 use pyo3::prelude::*;
+use starlark::analysis::AstModuleLint;
 use starlark::syntax::{AstLoad, AstModule, Dialect, DialectTypes};
+use starlark::typing::AstModuleTypecheck;
+use starlark_syntax::syntax::ast::StmtP;
+use starlark_syntax::syntax::module::AstModuleFields;
+use starlark_syntax::syntax::top_level_stmts::top_level_stmts;
 
+use crate::ast::{stmts_to_py, Unparser};
 use crate::codemap::{PyFileSpan, PySpan};
+use crate::docs::{ast_module_documentation, PyDocModule};
+use crate::environment::PyGlobals;
+use crate::errors::{py_parse_err_from_sl, py_typecheck_err_from_sl, PyLint};
 use crate::repr_utils::{PyReprBool, PyReprDialectTypes};
+use crate::typing::PyInterface;
 
 #[pyclass(
     module = "xingque",
@@ -43,7 +53,7 @@ impl From<DialectTypes> for PyDialectTypes {
 }
 
 #[pyclass(module = "xingque", name = "Dialect")]
-pub(crate) struct PyDialect(Dialect);
+pub(crate) struct PyDialect(pub(crate) Dialect);
 
 macro_rules! trivial_bool_prop {
     // still no concat_idents! so we have to duplicate a little
@@ -121,7 +131,7 @@ impl PyDialect {
     const EXTENDED: Self = Self(Dialect::Extended);
 
     #[classattr]
-    const STANDARD: Self = Self(Dialect::Standard);
+    pub(crate) const STANDARD: Self = Self(Dialect::Standard);
 
     #[getter]
     fn get_enable_types(&self) -> PyResult<PyDialectTypes> {
@@ -177,29 +187,36 @@ impl From<Dialect> for PyDialect {
 }
 
 #[pyclass(module = "xingque", name = "AstModule")]
-pub(crate) struct PyAstModule(Option<AstModule>);
+pub(crate) struct PyAstModule {
+    ast: Option<AstModule>,
+    // operator replacements applied so far, replayed on fresh copies
+    replaced_operators: Vec<HashMap<String, String>>,
+}
 
 impl From<AstModule> for PyAstModule {
     fn from(value: AstModule) -> Self {
-        Self(Some(value))
+        Self {
+            ast: Some(value),
+            replaced_operators: Vec::new(),
+        }
     }
 }
 
 impl PyAstModule {
     pub(crate) fn inner(&self) -> PyResult<&AstModule> {
-        self.0.as_ref().ok_or(PyRuntimeError::new_err(
+        self.ast.as_ref().ok_or(PyRuntimeError::new_err(
             "this AstModule is already consumed",
         ))
     }
 
     pub(crate) fn inner_mut(&mut self) -> PyResult<&mut AstModule> {
-        self.0.as_mut().ok_or(PyRuntimeError::new_err(
+        self.ast.as_mut().ok_or(PyRuntimeError::new_err(
             "this AstModule is already consumed",
         ))
     }
 
     pub(crate) fn take_inner(&mut self) -> PyResult<AstModule> {
-        self.0.take().ok_or(PyRuntimeError::new_err(
+        self.ast.take().ok_or(PyRuntimeError::new_err(
             "this AstModule is already consumed",
         ))
     }
@@ -209,19 +226,19 @@ impl PyAstModule {
 impl PyAstModule {
     #[staticmethod]
     #[pyo3(signature = (path, dialect = &PyDialect::STANDARD))]
-    fn parse_file(path: ::std::path::PathBuf, dialect: &PyDialect) -> PyResult<Self> {
+    fn parse_file(py: Python, path: ::std::path::PathBuf, dialect: &PyDialect) -> PyResult<Self> {
         match AstModule::parse_file(&path, &dialect.0) {
             Ok(x) => Ok(x.into()),
-            Err(e) => Err(PyValueError::new_err(e.to_string())),
+            Err(e) => Err(py_parse_err_from_sl(py, e)),
         }
     }
 
     #[staticmethod]
     #[pyo3(signature = (filename, content, dialect = &PyDialect::STANDARD))]
-    fn parse(filename: &str, content: String, dialect: &PyDialect) -> PyResult<Self> {
+    fn parse(py: Python, filename: &str, content: String, dialect: &PyDialect) -> PyResult<Self> {
         match AstModule::parse(filename, content, &dialect.0) {
             Ok(x) => Ok(x.into()),
-            Err(e) => Err(PyValueError::new_err(e.to_string())),
+            Err(e) => Err(py_parse_err_from_sl(py, e)),
         }
     }
 
@@ -244,9 +261,148 @@ impl PyAstModule {
             .collect())
     }
 
+    /// The top-level statements of the module, as a read-only view of the
+    /// AST.
+    #[getter]
+    fn statements(&self, py: Python) -> PyResult<PyObject> {
+        stmts_to_py(py, self.inner()?.statement())
+    }
+
+    /// Turns the statements back into source text.
+    ///
+    /// `statements` defaults to the module's own statements, and may be a
+    /// modified version of them, in which case comments and blank lines
+    /// around statements taken from this module are kept.
+    #[pyo3(signature = (statements = None))]
+    fn unparse(&self, py: Python, statements: Option<&Bound<'_, PyAny>>) -> PyResult<String> {
+        let inner = self.inner()?;
+        let mut unparser = Unparser::new(Some(inner.codemap()));
+        let statements = match statements {
+            Some(x) => x.clone(),
+            None => stmts_to_py(py, inner.statement())?.into_bound(py),
+        };
+        unparser.stmts(&statements.iter()?.collect::<PyResult<Vec<_>>>()?, true)?;
+        Ok(unparser.finish())
+    }
+
+    /// Returns the module's source in canonical formatting, keeping comments
+    /// and single blank lines.
+    ///
+    /// Calls, collections, `load`s and parameter lists are written one item
+    /// per line if they don't fit on a line, contain comments, or were
+    /// already written over multiple lines. Binary operations with comments
+    /// inside are parenthesized and written one operand per line; comments
+    /// within other multi-line expressions outside brackets are moved after
+    /// their statement.
+    fn format(&self, py: Python) -> PyResult<String> {
+        self.unparse(py, None)
+    }
+
     fn replace_binary_operators(&mut self, replace: HashMap<String, String>) -> PyResult<()> {
-        Ok(self.inner_mut()?.replace_binary_operators(&replace))
+        self.inner_mut()?.replace_binary_operators(&replace);
+        self.replaced_operators.push(replace);
+        Ok(())
     }
+
+    /// Extracts documentation for the module's docstring and its public
+    /// top-level `def`s and assignments, without evaluating the module.
+    ///
+    /// Parameter and return types are taken from the annotations where they
+    /// only refer to builtin types, and are `typing.Any` otherwise.
+    fn documentation(&self, py: Python) -> PyResult<PyDocModule> {
+        Ok(ast_module_documentation(py, self.inner()?).into())
+    }
+
+    /// Runs the linter over the module.
+    ///
+    /// If the complete set of global variables is known, pass it as `globals`,
+    /// either as a `Globals` or the names, to also get name-resolution lints.
+    #[pyo3(signature = (globals = None))]
+    fn lint(&self, globals: Option<&Bound<'_, PyAny>>) -> PyResult<Vec<PyLint>> {
+        let globals = match globals {
+            None => None,
+            Some(x) => match x.downcast::<PyGlobals>() {
+                Ok(x) => Some(x.get().0.names().map(|x| x.as_str().to_owned()).collect()),
+                Err(_) => Some(
+                    x.iter()?
+                        .map(|x| x?.extract::<String>())
+                        .collect::<PyResult<HashSet<_>>>()?,
+                ),
+            },
+        };
+        Ok(self
+            .inner()?
+            .lint(globals.as_ref())
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Runs the static typechecker over the module, without evaluating it.
+    ///
+    /// `loads` maps the module IDs loaded by this module to their interfaces,
+    /// as returned by previous `typecheck` calls. Returns the type errors found
+    /// and the interface of this module.
+    #[pyo3(signature = (globals, loads = None))]
+    fn typecheck(
+        &self,
+        py: Python,
+        globals: &PyGlobals,
+        loads: Option<HashMap<String, PyInterface>>,
+    ) -> PyResult<(Vec<PyObject>, PyInterface)> {
+        let inner = self.inner()?;
+        // typechecking consumes the module and `AstModule` can't be cloned,
+        // so work on a fresh parse with the same edits applied
+        let mut ast = AstModule::parse(
+            inner.codemap().filename(),
+            inner.codemap().source().to_owned(),
+            inner.dialect(),
+        )
+        .map_err(|e| py_parse_err_from_sl(py, e))?;
+        for replace in &self.replaced_operators {
+            ast.replace_binary_operators(replace);
+        }
+        let names = top_level_names(inner);
+        let loads = loads
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(k, v)| (k, v.inner().clone()))
+            .collect();
+
+        let (errors, _, interface, _) = ast.typecheck(&globals.0, &loads);
+        let errors = errors
+            .into_iter()
+            .map(|e| py_typecheck_err_from_sl(py, e).into_value(py).into_any())
+            .collect();
+        Ok((errors, PyInterface::new(interface, names)))
+    }
+}
+
+/// Names bound by the top-level statements of the module, in order.
+fn top_level_names(ast: &AstModule) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    let mut add = |name: &str| {
+        if !result.iter().any(|x| x == name) {
+            result.push(name.to_owned());
+        }
+    };
+    for stmt in top_level_stmts(ast.statement()) {
+        match &stmt.node {
+            StmtP::Assign(x) => x.lhs.visit_lvalue(|x| add(&x.ident)),
+            StmtP::AssignModify(lhs, _, _) => lhs.visit_lvalue(|x| add(&x.ident)),
+            StmtP::Def(x) => add(&x.name.ident),
+            StmtP::Load(x) => x.args.iter().for_each(|x| add(&x.local.ident)),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Formats Starlark source text, like `AstModule.format`.
+#[pyfunction]
+#[pyo3(signature = (text, dialect = &PyDialect::STANDARD))]
+pub(crate) fn format_source(py: Python, text: String, dialect: &PyDialect) -> PyResult<String> {
+    PyAstModule::parse(py, "<string>", text, dialect)?.format(py)
 }
 
 #[pyclass(module = "xingque", name = "AstLoad", frozen)]
@@ -0,0 +1,92 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use starlark::environment::{Globals, LibraryExtension, Module};
+use starlark::eval::Evaluator;
+use starlark::syntax::{AstModule, Dialect};
+use starlark::typing::{Interface, Ty};
+use starlark::values::typing::TypeCompiled;
+
+use crate::errors::{py_err_from_sl, py_parse_err_from_sl};
+
+/// Evaluates a Starlark type expression such as `list[str] | None` into a
+/// `Ty`.
+pub(crate) fn ty_from_type_expr(py: Python<'_>, expr: &str) -> PyResult<Ty> {
+    let ast = AstModule::parse("<type>", expr.to_owned(), &Dialect::Extended)
+        .map_err(|e| py_parse_err_from_sl(py, e))?;
+    let globals = Globals::extended_by(&[LibraryExtension::Typing]);
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let value = eval
+        .eval_module(ast, &globals)
+        .map_err(|e| py_err_from_sl(py, e))?;
+    let ty = TypeCompiled::new(value, module.heap())?;
+    Ok(ty.as_ty().clone())
+}
+
+/// The types of the public top-level bindings of a module, as inferred by the
+/// static typechecker.
+#[pyclass(module = "xingque", name = "Interface", frozen)]
+#[derive(Clone)]
+pub(crate) struct PyInterface {
+    inner: Interface,
+    // Interface doesn't expose its bindings, so remember the names ourselves
+    names: Vec<String>,
+}
+
+impl PyInterface {
+    pub(crate) fn new(inner: Interface, names: impl IntoIterator<Item = String>) -> Self {
+        let names = names
+            .into_iter()
+            .filter(|x| inner.get(x).is_some())
+            .collect();
+        Self { inner, names }
+    }
+
+    pub(crate) fn inner(&self) -> &Interface {
+        &self.inner
+    }
+}
+
+#[pymethods]
+impl PyInterface {
+    #[new]
+    fn py_new() -> Self {
+        Self {
+            inner: Interface::empty(),
+            names: Vec::new(),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Interface {}>", self.names.join(", "))
+    }
+
+    fn __len__(&self) -> usize {
+        self.names.len()
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.inner.get(name).is_some()
+    }
+
+    /// Names of the bindings, in the order they are defined.
+    fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    /// The type of the binding `name` rendered as a Starlark type expression,
+    /// or `None` if there is no such binding.
+    fn get(&self, name: &str) -> Option<String> {
+        self.inner.get(name).map(ToString::to_string)
+    }
+
+    /// All bindings as a dict of name to type.
+    #[getter]
+    fn types<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let result = PyDict::new_bound(py);
+        for name in &self.names {
+            result.set_item(name, self.get(name))?;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,111 @@
+import contextlib
+import io
+import sys
+
+import pytest
+
+import xingque
+from xingque.cli import main, parse_extensions
+
+
+def run(argv: "list[str]", stdin: str = "") -> "tuple[int, str, str]":
+    stdout = io.StringIO()
+    stderr = io.StringIO()
+    orig_stdin = sys.stdin
+    sys.stdin = io.StringIO(stdin)
+    try:
+        with contextlib.redirect_stdout(stdout), contextlib.redirect_stderr(stderr):
+            status = main(argv)
+    finally:
+        sys.stdin = orig_stdin
+    return status, stdout.getvalue(), stderr.getvalue()
+
+
+def test_parse_extensions():
+    assert parse_extensions("json,Print") == [
+        xingque.LibraryExtension.JSON,
+        xingque.LibraryExtension.PRINT,
+    ]
+    assert xingque.LibraryExtension.TYPING in parse_extensions("all")
+    with pytest.raises(Exception):
+        parse_extensions("nonexistent")
+
+
+def test_run(tmp_path):
+    (tmp_path / "pkg").mkdir()
+    (tmp_path / "pkg" / "lib.star").write_text("def double(x):\n    return x * 2\n")
+    main_file = tmp_path / "main.star"
+    main_file.write_text(
+        'load("//pkg:lib.star", "double")\n'
+        'load("pkg/lib.star", double2 = "double")\n'
+        "json.encode([double(1), double2(2)])\n"
+    )
+    assert run(["run", "--ext", "json", str(main_file)]) == (0, "'[2,4]'\n", "")
+
+    main_file.write_text("1 + None\n")
+    status, stdout, stderr = run(["run", str(main_file)])
+    assert status == 1
+    assert stdout == ""
+    assert "1 + None" in stderr
+
+    main_file.write_text('load("missing.star", "x")\n')
+    status, _, stderr = run(["run", str(main_file)])
+    assert status == 1
+    assert "missing.star" in stderr
+
+    # errors in loaded files and load cycles are reported once
+    (tmp_path / "bad.star").write_text('fail("boom")\n')
+    main_file.write_text('load("bad.star", "x")\n')
+    status, _, stderr = run(["run", str(main_file)])
+    assert status == 1
+    assert stderr.count("error:") == 1
+    assert stderr.count('fail("boom")') == 1
+    assert f"--> {tmp_path / 'bad.star'}:1:1" in stderr
+
+    (tmp_path / "a.star").write_text('load("main.star", "x")\n')
+    main_file.write_text('load("a.star", "x")\n')
+    status, _, stderr = run(["run", str(main_file)])
+    assert status == 1
+    assert stderr.count("error:") == 1
+    assert stderr.count("load cycle detected") == 1
+    assert stderr.count('load("a.star", "x")') == 1
+
+
+def test_check(tmp_path):
+    good = tmp_path / "good.star"
+    good.write_text("def f(x: int) -> int:\n    return x\n")
+    bad = tmp_path / "bad.star"
+    bad.write_text("a = b\nb = 1\ndef g(x: int) -> str:\n    return x\n")
+
+    assert run(["check", str(good)]) == (0, "", "")
+
+    status, stdout, _ = run(["check", str(good), str(bad)])
+    assert status == 1
+    assert stdout.startswith(
+        f"{bad}:1:5: warning: Use of unassigned variable `b` [using-unassigned]\n"
+    )
+    assert "Expected type `str` but got `int`" in stdout
+
+
+def test_fmt(tmp_path):
+    file = tmp_path / "x.star"
+    file.write_text("x=[1,2]\n")
+
+    assert run(["fmt", "--check", str(file)]) == (1, f"would reformat {file}\n", "")
+    assert file.read_text() == "x=[1,2]\n"
+
+    assert run(["fmt", str(file)]) == (0, f"reformatted {file}\n", "")
+    assert file.read_text() == "x = [1, 2]\n"
+    assert run(["fmt", "--check", str(file)]) == (0, "", "")
+
+    assert run(["fmt", "-"], stdin="f( a )\n") == (0, "f(a)\n", "")
+
+
+def test_repl():
+    status, stdout, _ = run(
+        ["repl", "--dialect", "standard", "--ext", "print"], stdin="x = 20\nx + 1\n"
+    )
+    assert status == 0
+    assert "21\n" in stdout
+    # the console is the default
+    assert "21\n" in run([], stdin="x = 20\nx + 1\n")[1]
@@ -1,4 +1,6 @@
 import gc
+import json
+import typing
 
 import pytest
 import xingque
@@ -106,3 +108,256 @@ def test_module_freeze():
 
     with pytest.raises(RuntimeError):
         m.get("a")
+
+
+def test_globals_builder_set_function():
+    calls = []
+
+    def f(a, /, b, c=3, *args, d, e="e", **kwargs):
+        """Does things."""
+        calls.append((a, b, c, args, d, e, kwargs))
+        return a + b + c
+
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set_function("f", f, return_type="int")
+    g = gb.build()
+    assert "f" in set(g.names())
+
+    def run(text: str) -> object:
+        am = xingque.AstModule.parse("test.star", text)
+        return xingque.Evaluator().eval_module(am, g)
+
+    assert run("f(1, 2, d=4)") == 6
+    assert run("f(1, b=2, d=4)") == 6
+    assert run("f(1, 2, 5, 6, 7, d=4, e=None, x=8)") == 8
+    assert calls == [
+        (1, 2, 3, (), 4, "e", {}),
+        (1, 2, 3, (), 4, "e", {}),
+        (1, 2, 5, (6, 7), 4, None, {"x": 8}),
+    ]
+
+    # argument errors are reported by Starlark
+    for text in ("f(1, 2)", "f(a=1, b=2, d=4)", "f()"):
+        with pytest.raises(xingque.StarlarkFunctionError):
+            run(text)
+    assert len(calls) == 3
+
+
+def test_globals_builder_set_function_explicit_signature():
+    import inspect
+
+    def f(*args, **kwargs):
+        return [args, kwargs]
+
+    sig = inspect.Signature(
+        [
+            inspect.Parameter("x", inspect.Parameter.POSITIONAL_OR_KEYWORD),
+            inspect.Parameter("y", inspect.Parameter.KEYWORD_ONLY, default=1),
+        ]
+    )
+
+    def build(sb):
+        sb.set_function("f", f, signature=sig, docstring="doc")
+
+    gb = xingque.GlobalsBuilder()
+    gb.struct("s", build)
+    g = gb.build()
+
+    am = xingque.AstModule.parse("test.star", "s.f(x=0)")
+    assert xingque.Evaluator().eval_module(am, g) == [(0,), {"y": 1}]
+
+    with pytest.raises(TypeError):
+        xingque.GlobalsBuilder().set_function("f", f, signature="(x, y)")
+
+
+def test_globals_builder_function_decorator():
+    gb = xingque.GlobalsBuilder.standard()
+
+    @gb.function
+    def add(x: int, y: int = 1) -> int:
+        """Adds things."""
+        return x + y
+
+    @gb.function(name="joined")
+    def join(xs: typing.List[str], *, sep: typing.Optional[str] = None) -> str:
+        return (sep or ",").join(xs)
+
+    # the decorated functions remain callable from Python
+    assert isinstance(add, xingque.StarlarkFunction)
+    assert add(1, 2) == 3
+    assert add.__wrapped__.__doc__ == "Adds things."
+    assert add.parameter_types == {"x": "int", "y": "int"}
+    assert add.return_type == "int"
+    assert join.name == "joined"
+    assert join.parameter_types == {"xs": "list[str]", "sep": "str | None"}
+
+    g = gb.build()
+    assert {"add", "joined"} <= set(g.names())
+
+    def run(text: str, typecheck: bool = False) -> object:
+        am = xingque.AstModule.parse("test.star", text)
+        e = xingque.Evaluator()
+        e.enable_static_typechecking(typecheck)
+        return e.eval_module(am, g)
+
+    assert run("add(2)") == 3
+    assert run("joined(['a', 'b'], sep='-')") == "a-b"
+    # the declared types are visible to the static typechecker
+    with pytest.raises(xingque.StarlarkError, match="Expected type `int`"):
+        run("def h():\n    return add('2')\n", typecheck=True)
+
+
+def test_starlark_function_decorator():
+    @xingque.starlark_function
+    def f(
+        a: typing.Tuple[int, ...],
+        b: typing.Tuple[int, str],
+        c: typing.Dict[str, object],
+        d: typing.Callable[[int], int],
+        e: typing.Optional[int],
+        g: object,
+    ) -> None:
+        pass
+
+    assert f.parameter_types == {
+        "a": "tuple[int, ...]",
+        "b": "(int, str,)",
+        "c": "dict[str, typing.Any]",
+        "d": "typing.Callable",
+        "e": "int | None",
+    }
+    assert f.return_type == "None"
+
+    @xingque.starlark_function(docstring="Doubles.")
+    def double(x: int) -> int:
+        return x * 2
+
+    def build(sb):
+        sb.set("f", f)
+        sb.set("double", double)
+
+    gb = xingque.GlobalsBuilder()
+    gb.struct("s", build)
+    gb.set("d", double)
+    g = gb.build()
+
+    am = xingque.AstModule.parse("test.star", "s.double(d(2))")
+    assert xingque.Evaluator().eval_module(am, g) == 8
+
+
+def test_starlark_function_method():
+    class Counter:
+        def __init__(self) -> None:
+            self.n = 0
+
+        @xingque.starlark_function
+        def bump(self, by: int) -> int:
+            self.n += by
+            return self.n
+
+        @staticmethod
+        @xingque.starlark_function
+        def zero() -> int:
+            return 0
+
+    c = Counter()
+    # bound like a plain method
+    assert c.bump(2) == 2
+    assert c.bump(by=3) == 5
+    assert c.zero() == 0
+    # the decorated function is still reachable from the class
+    assert isinstance(Counter.bump, xingque.StarlarkFunction)
+    assert Counter.bump.parameter_types == {"by": "int"}
+
+    gb = xingque.GlobalsBuilder()
+    gb.set("bump", c.bump)
+    am = xingque.AstModule.parse("test.star", "bump(4)")
+    assert xingque.Evaluator().eval_module(am, gb.build()) == 9
+
+
+def test_sub_globals_builder_outlived():
+    kept = []
+
+    def build(sb):
+        @sb.function
+        def f() -> int:
+            return 1
+
+        sb.with_struct("inner", lambda isb: kept.append(isb))
+        kept.append(sb)
+
+    gb = xingque.GlobalsBuilder()
+    gb.struct("s", build)
+    gb.with_(kept.append)
+    g = gb.build()
+
+    am = xingque.AstModule.parse("test.star", "s.f()")
+    assert xingque.Evaluator().eval_module(am, g) == 1
+
+    # the builders lent to the callbacks are gone once they return
+    assert len(kept) == 3
+    for sb in kept:
+        with pytest.raises(RuntimeError):
+            sb.set("x", 1)
+        with pytest.raises(RuntimeError):
+            sb.struct("t", lambda _: None)
+
+
+def test_globals_documentation():
+    def f(a, b=2, *, c):
+        """Adds things."""
+
+    gb = xingque.GlobalsBuilder()
+    gb.set_function("f", f, parameter_types={"a": "int"}, return_type="int")
+    gb.set("x", 1)
+    docs = gb.build().documentation()
+
+    assert list(docs.members) == ["f", "x"]
+    f_docs = docs.members["f"]
+    assert isinstance(f_docs, xingque.DocFunction)
+    assert f_docs.docs.summary == "Adds things."
+    assert [(x.kind, x.name, x.type) for x in f_docs.params] == [
+        (xingque.DocParamKind.ARG, "a", "int"),
+        (xingque.DocParamKind.ARG, "b", "typing.Any"),
+        (xingque.DocParamKind.NO_ARGS, None, None),
+        (xingque.DocParamKind.ARG, "c", "typing.Any"),
+    ]
+    assert f_docs.params[1].default_value == "2"
+    assert f_docs.ret.type == "int"
+    assert isinstance(docs.members["x"], xingque.DocProperty)
+    assert json.loads(docs.to_json())["members"]["f"]["ret"]["type"] == "int"
+
+
+def test_frozen_module_documentation():
+    text = '''
+"""The module."""
+
+def f(x, *args):
+    """Does f."""
+    return x
+
+_hidden = 1
+'''
+    m = xingque.Module()
+    am = xingque.AstModule.parse("test.star", text)
+    xingque.Evaluator(m).eval_module(am, xingque.Globals.standard())
+    docs = m.freeze().documentation()
+
+    assert docs.docs.summary == "The module."
+    assert list(docs.members) == ["f"]
+    assert docs.members["f"].docs.summary == "Does f."
+    assert [x.name for x in docs.members["f"].params] == ["x", "args"]
+
+
+def test_globals_render_docs():
+    def f(x):
+        """Uses `g`."""
+
+    gb = xingque.GlobalsBuilder()
+    gb.set_function("f", f)
+    gb.set_function("g", f)
+    g = gb.build()
+
+    md = g.render_docs()
+    assert "* [`f`](#f): Uses [`g`](#g).\n" in md
+    assert "<a href=\"#g\"><code>g</code></a>" in g.render_docs("html")
@@ -0,0 +1,235 @@
+import pytest
+import xingque
+
+
+def test_parse_error():
+    with pytest.raises(xingque.StarlarkParseError) as excinfo:
+        xingque.AstModule.parse("test.star", "def f(:\n    pass\n")
+
+    e = excinfo.value
+    assert isinstance(e, xingque.StarlarkError)
+    assert e.message
+    assert e.span is not None
+    assert e.span.filename == "test.star"
+    assert e.span.resolve().begin_file_line().line == 0
+
+
+def test_fail_error():
+    text = """
+def g():
+    fail("oops")
+
+def f():
+    g()
+
+f()
+"""
+    am = xingque.AstModule.parse("test.star", text)
+    e = xingque.Evaluator()
+    with pytest.raises(xingque.StarlarkFailError) as excinfo:
+        e.eval_module(am, xingque.Globals.standard())
+
+    err = excinfo.value
+    assert "oops" in err.message
+    assert err.span.resolve().begin_file_line().line == 2
+    # starlark-rust keeps the call stack of its own errors to itself, so it's
+    # only available in the exception's string form
+    assert err.call_stack is None
+    assert "Traceback" in str(err)
+    assert "test.star:8, in <module>" in str(err)
+
+
+@pytest.mark.parametrize(
+    "text,exc",
+    [
+        ("x = undefined_variable", xingque.StarlarkScopeError),
+        ("x = 1 + 'a'", xingque.StarlarkValueError),
+        ("def f(a): pass\nf(1, 2)", xingque.StarlarkFunctionError),
+    ],
+)
+def test_error_kinds(text: str, exc: type) -> None:
+    am = xingque.AstModule.parse("test.star", text)
+    e = xingque.Evaluator()
+    with pytest.raises(exc):
+        e.eval_module(am, xingque.Globals.standard())
+
+
+def test_static_typecheck_error_on_eval():
+    am = xingque.AstModule.parse(
+        "test.star", "def f(a: int) -> str:\n    return a\n", xingque.Dialect.EXTENDED
+    )
+    e = xingque.Evaluator()
+    e.enable_static_typechecking(True)
+    # starlark-rust doesn't tell these apart from errors of native code
+    with pytest.raises(xingque.StarlarkNativeError) as excinfo:
+        e.eval_module(am, xingque.Globals.standard())
+    assert "Expected type `str` but got `int`" in excinfo.value.message
+
+
+def test_python_exception_propagation():
+    class PermissionDenied(Exception):
+        pass
+
+    def check_access(path: str) -> None:
+        raise PermissionDenied(path)
+
+    text = """
+def f():
+    check_access("/secret")
+
+f()
+"""
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set("check_access", check_access)
+    am = xingque.AstModule.parse("test.star", text)
+    e = xingque.Evaluator()
+    with pytest.raises(PermissionDenied) as excinfo:
+        e.eval_module(am, gb.build())
+
+    err = excinfo.value
+    assert err.args == ("/secret",)
+
+    # the original Python traceback is kept
+    tb = err.__traceback__
+    frame_names = []
+    while tb is not None:
+        frame_names.append(tb.tb_frame.f_code.co_name)
+        tb = tb.tb_next
+    assert "check_access" in frame_names
+
+    # the Starlark side of the story is attached as a note
+    assert len(err.__notes__) == 1
+    assert "test.star:3" in err.__notes__[0]
+
+    # and recorded as the call stack at the time of the call
+    assert [f.name for f in err.call_stack][:1] == ["f"]
+    assert "check_access" in err.call_stack[1].name
+    assert [f.location.resolve().begin_file_line().line for f in err.call_stack] == [
+        4,
+        2,
+    ]
+
+
+def test_diagnostic_from_error():
+    text = """def g():
+    fail("oops")
+
+def f():
+    g()
+
+f()
+"""
+    am = xingque.AstModule.parse("test.star", text)
+    with pytest.raises(xingque.StarlarkFailError) as excinfo:
+        xingque.Evaluator().eval_module(am, xingque.Globals.standard())
+
+    d = xingque.Diagnostic.from_error(excinfo.value)
+    assert d.severity == xingque.EvalSeverity.ERROR
+    assert "oops" in d.message
+    # the calls leading to `fail` aren't known, see test_fail_error
+    assert d.secondary_labels == []
+    assert d.render() == """\
+error: fail: oops
+ --> test.star:2:5
+  |
+2 |     fail("oops")
+  |     ^^^^^^^^^^^^
+  |
+"""
+    assert str(d) == d.render()
+    assert "\x1b[1;31m^^^^^^^^^^^^\x1b[0m" in d.render(color=True)
+
+    other = xingque.Diagnostic.from_error(ValueError("boom"))
+    assert other.span is None
+    assert other.render() == "error: boom\n"
+
+
+def test_diagnostic_from_error_call_stack():
+    def boom() -> None:
+        raise ValueError("boom")
+
+    text = """def g():
+    boom()
+
+def f():
+    g()
+
+f()
+"""
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set_function("boom", boom)
+    am = xingque.AstModule.parse("test.star", text)
+    with pytest.raises(ValueError) as excinfo:
+        xingque.Evaluator().eval_module(am, gb.build())
+
+    d = xingque.Diagnostic.from_error(excinfo.value)
+    assert [label for _, label in d.secondary_labels] == [
+        "in call to `f`",
+        "in call to `g`",
+    ]
+    assert d.render() == """\
+error: boom
+ --> test.star:2:5
+  |
+2 |     boom()
+  |     ^^^^^^
+...
+5 |     g()
+  |     --- in call to `g`
+6 |
+7 | f()
+  | --- in call to `f`
+  |
+"""
+
+
+def test_diagnostic_from_lint():
+    am = xingque.AstModule.parse("test.star", "x = y\ny = 1\n")
+    (lint,) = [
+        x for x in am.lint() if x.severity == xingque.EvalSeverity.WARNING
+    ]
+    d = xingque.Diagnostic.from_lint(lint)
+    assert d.name == "using-unassigned"
+    assert d.render() == """\
+warning[using-unassigned]: Use of unassigned variable `y`
+ --> test.star:1:5
+  |
+1 | x = y
+  |     ^
+  |
+"""
+
+
+def test_diagnostic_render():
+    main = xingque.CodeMap("main.star", "a = [\n\t1,\n]\nb = a\n")
+    lib = xingque.CodeMap("lib.star", "x = 1\n")
+    d = xingque.Diagnostic(
+        "something is off",
+        main.file_span(main.line_span(1)),
+        severity=xingque.EvalSeverity.ADVICE,
+        label="indented with a tab",
+        secondary_labels=[
+            (main.file_span(main.full_span()), "in this file"),
+            (lib.file_span(lib.line_span(0)), "defined here"),
+        ],
+        notes=["tabs are shown as spaces"],
+    )
+    assert d.render() == """\
+advice: something is off
+ --> main.star:2:1
+  |
+1 | a = [
+  | -----
+2 |     1,
+  | ^^^^^^ indented with a tab
+3 | ]
+4 | b = a
+  | ----- in this file
+  |
+ ::: lib.star:1:1
+  |
+1 | x = 1
+  | ----- defined here
+  |
+  = note: tabs are shown as spaces
+"""
@@ -0,0 +1,333 @@
+import threading
+
+import pytest
+import xingque
+
+
+def test_call_stack_from_builtin():
+    text = """
+def inner():
+    return where()
+
+def outer():
+    return inner()
+
+outer()
+"""
+    e = xingque.Evaluator()
+    seen = []
+
+    def where():
+        stack = e.call_stack()
+        seen.append(
+            (
+                [f.name for f in stack],
+                [
+                    f.location.resolve().begin_file_line().line if f.location else None
+                    for f in stack
+                ],
+                e.call_stack_top_frame().name,
+                e.call_stack_count(),
+            )
+        )
+        return len(stack)
+
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set_function("where", where)
+    g = gb.build()
+
+    am = xingque.AstModule.parse("test.star", text)
+    assert e.eval_module(am, g) == 3
+    assert seen == [
+        (["outer", "inner", "where"], [7, 5, 2], "where", 3),
+    ]
+
+    # nothing is on the stack outside of evaluation
+    assert e.call_stack() == []
+    assert e.call_stack_top_frame() is None
+
+
+def test_print_handler():
+    lines = []
+    e = xingque.Evaluator()
+    e.set_print_handler(lines.append)
+
+    g = xingque.Globals.extended_by(
+        [xingque.LibraryExtension.PRINT, xingque.LibraryExtension.PPRINT]
+    )
+    am = xingque.AstModule.parse("test.star", "print('hello', 1)\npprint([2])\n")
+    e.eval_module(am, g)
+    assert lines == ["hello 1", "[ 2 ]"]
+
+
+def test_print_handler_call_stack():
+    text = """
+def f():
+    print("in f")
+
+f()
+pprint("top")
+"""
+    e = xingque.Evaluator()
+    seen = []
+
+    def handler(text):
+        top = e.call_stack_top_frame()
+        line = top.location.resolve().begin_file_line().line
+        seen.append((text, top.name, line, [f.name for f in e.call_stack()]))
+
+    e.set_print_handler(handler)
+    g = xingque.Globals.extended_by(
+        [xingque.LibraryExtension.PRINT, xingque.LibraryExtension.PPRINT]
+    )
+    am = xingque.AstModule.parse("test.star", text)
+    e.eval_module(am, g)
+    assert seen == [
+        ("in f", "print", 2, ["f", "print"]),
+        ('"top"', "pprint", 5, ["pprint"]),
+    ]
+
+
+def test_print_handler_error():
+    class Stop(Exception):
+        pass
+
+    def handler(text):
+        raise Stop(text)
+
+    e = xingque.Evaluator()
+    e.set_print_handler(handler)
+    g = xingque.Globals.extended_by([xingque.LibraryExtension.PRINT])
+    am = xingque.AstModule.parse("test.star", "print('x')")
+    with pytest.raises(Stop):
+        e.eval_module(am, g)
+
+
+PROFILED_TEXT = """
+def f(x):
+    return [x] * 3
+
+def g():
+    return [f(i) for i in range(10)]
+
+g()
+"""
+
+
+@pytest.mark.parametrize(
+    "mode",
+    [
+        xingque.ProfileMode.HEAP_SUMMARY_ALLOCATED,
+        xingque.ProfileMode.STATEMENT,
+        xingque.ProfileMode.BYTECODE,
+        xingque.ProfileMode.BYTECODE_PAIRS,
+        xingque.ProfileMode.TYPECHECK,
+    ],
+)
+def test_profile_csv(mode):
+    e = xingque.Evaluator()
+    e.enable_profile(mode)
+    am = xingque.AstModule.parse("test.star", PROFILED_TEXT)
+    e.eval_module(am, xingque.Globals.standard())
+
+    data = e.gen_profile()
+    assert data.profile_mode == mode
+    text = data.gen()
+    rows = data.rows()
+    assert len(rows) == len(text.splitlines()) - 1
+    assert list(rows[0].keys()) == text.splitlines()[0].split(",")
+
+    # profile data can only be collected once
+    with pytest.raises(RuntimeError):
+        e.gen_profile()
+
+
+def test_profile_flame(tmp_path):
+    e = xingque.Evaluator()
+    e.enable_profile(xingque.ProfileMode.HEAP_FLAME_ALLOCATED)
+    am = xingque.AstModule.parse("test.star", PROFILED_TEXT)
+    e.eval_module(am, xingque.Globals.standard())
+
+    data = e.gen_profile()
+    rows = data.rows()
+    # the byte counts depend on the platform and starlark's value layout
+    allocated = dict((tuple(stack), n) for stack, n in rows)
+    assert allocated[("module", "test.star.g", "test.star.f", "list")] > 0
+
+    path = tmp_path / "profile.txt"
+    data.write(path)
+    assert path.read_text() == data.gen()
+
+    merged = xingque.ProfileData.merge([data, data])
+    assert merged.profile_mode == xingque.ProfileMode.HEAP_FLAME_ALLOCATED
+
+
+def test_coverage():
+    text = """
+def f(x):
+    if x:
+        return 1
+    return 2
+
+f(True)
+"""
+    report = xingque.CoverageReport()
+    am = xingque.AstModule.parse("test.star", text)
+    report.add_module(am)
+
+    e = xingque.Evaluator()
+    e.enable_profile(xingque.ProfileMode.COVERAGE)
+    e.eval_module(am, xingque.Globals.standard())
+    report.add_coverage(e.coverage())
+
+    assert report.files == ["test.star"]
+    assert report.lines("test.star") == [1, 2, 3, 4, 6]
+    assert report.executed_lines("test.star") == [1, 2, 3, 6]
+    assert all(x.file == "test.star" for x in report.executed_spans("test.star"))
+
+    lcov = report.to_lcov()
+    assert "SF:test.star\n" in lcov
+    assert "DA:4,1\nDA:5,0\n" in lcov
+    assert "LF:5\nLH:4\nend_of_record\n" in lcov
+
+    import xml.etree.ElementTree as ET
+
+    root = ET.fromstring(report.to_cobertura())
+    assert root.get("lines-covered") == "4"
+    assert root.get("lines-valid") == "5"
+    lines = root.findall(".//class[@filename='test.star']/lines/line")
+    assert [(x.get("number"), x.get("hits")) for x in lines] == [
+        ("2", "1"),
+        ("3", "1"),
+        ("4", "1"),
+        ("5", "0"),
+        ("7", "1"),
+    ]
+
+
+def test_filesystem_loader(tmp_path):
+    evaluations = []
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set_function("evaluated", evaluations.append)
+    g = gb.build()
+
+    (tmp_path / "pkg" / "sub").mkdir(parents=True)
+    (tmp_path / "ext").mkdir()
+    (tmp_path / "pkg" / "a.star").write_text(
+        'evaluated("a")\n'
+        'load(":b.star", "b")\n'
+        'load("sub/c.star", "c")\n'
+        'load("@ext//:d.star", "d")\n'
+        "a = b + c + d\n"
+    )
+    (tmp_path / "pkg" / "b.star").write_text('evaluated("b")\nb = 1\n')
+    (tmp_path / "pkg" / "sub" / "c.star").write_text(
+        'evaluated("c")\nload("../b.star", "b")\nc = b * 10\n'
+    )
+    (tmp_path / "ext" / "d.star").write_text('evaluated("d")\nd = 100\n')
+
+    loader = xingque.FilesystemLoader(tmp_path, g, prefixes={"@ext": tmp_path / "ext"})
+    assert loader.root == str(tmp_path)
+    assert loader.resolve("//pkg:a.star") == str(tmp_path / "pkg" / "a.star")
+    assert loader.resolve("../b.star", tmp_path / "pkg" / "sub" / "c.star") == str(
+        tmp_path / "pkg" / "b.star"
+    )
+    with pytest.raises(Exception, match="unknown load prefix `@nope`"):
+        loader.resolve("@nope//x.star")
+
+    def run() -> object:
+        am = xingque.AstModule.parse("main.star", 'load("//pkg:a.star", "a")\na')
+        e = xingque.Evaluator()
+        e.set_loader(loader)
+        return e.eval_module(am, g)
+
+    assert run() == 111
+    assert sorted(evaluations) == ["a", "b", "c", "d"]
+
+    # everything is cached
+    evaluations.clear()
+    assert run() == 111
+    assert loader.load("pkg/sub/c.star").get("c") == 10
+    assert evaluations == []
+
+    # changing a file invalidates the files depending on it
+    (tmp_path / "pkg" / "b.star").write_text('evaluated("b")\nb = 2\n')
+    assert run() == 122
+    assert sorted(evaluations) == ["a", "b", "c"]
+
+    evaluations.clear()
+    loader.clear_cache()
+    assert run() == 122
+    assert sorted(evaluations) == ["a", "b", "c", "d"]
+
+
+def test_filesystem_loader_errors(tmp_path):
+    (tmp_path / "a.star").write_text('load("b.star", "b")\na = 1\n')
+    (tmp_path / "b.star").write_text('load("c.star", "c")\nb = 1\n')
+    (tmp_path / "c.star").write_text('load("b.star", "b")\nc = 1\n')
+    loader = xingque.FilesystemLoader(str(tmp_path))
+
+    with pytest.raises(xingque.StarlarkError) as excinfo:
+        loader.load("a.star")
+    chain = " -> ".join(f"`{tmp_path / x}`" for x in ["b.star", "c.star", "b.star"])
+    assert f"load cycle detected: {chain}" in str(excinfo.value)
+
+    with pytest.raises(xingque.StarlarkError, match="cannot read `.*missing.star`"):
+        loader.load("missing.star")
+
+
+def test_filesystem_loader_error_in_loaded_file(tmp_path):
+    (tmp_path / "main.star").write_text('load("lib.star", "x")\n')
+    (tmp_path / "lib.star").write_text('def f():\n    fail("boom")\n\nx = f()\n')
+    loader = xingque.FilesystemLoader(tmp_path)
+
+    with pytest.raises(xingque.StarlarkFailError) as excinfo:
+        loader.load("main.star")
+    err = excinfo.value
+    assert type(err) is xingque.StarlarkFailError
+    assert err.message == "fail: boom"
+    assert err.span.filename == str(tmp_path / "lib.star")
+    assert err.span.source_span == 'fail("boom")'
+    assert str(err).count("error: fail: boom") == 1
+    assert err.__notes__ == [
+        f"while loading `{tmp_path / 'lib.star'}` at {tmp_path / 'main.star'}:1:1-22"
+    ]
+
+    # the same through an evaluator
+    e = xingque.Evaluator()
+    e.set_loader(loader)
+    am = xingque.AstModule.parse("top.star", 'load("main.star", "x")\n')
+    with pytest.raises(xingque.StarlarkFailError) as excinfo:
+        e.eval_module(am, xingque.Globals.standard())
+    assert excinfo.value.span.filename == str(tmp_path / "lib.star")
+
+
+def test_filesystem_loader_concurrent(tmp_path):
+    # a load blocked in another thread must not look like a cycle here
+    started = threading.Event()
+    release = threading.Event()
+
+    def gate() -> None:
+        if not started.is_set():
+            started.set()
+            release.wait(10)
+
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set_function("gate", gate)
+    g = gb.build()
+
+    (tmp_path / "w.star").write_text("gate()\nw = 1\n")
+    (tmp_path / "x.star").write_text('load("w.star", "w")\nx = w + 1\n')
+    (tmp_path / "y.star").write_text('load("x.star", "x")\ny = x + 1\n')
+    loader = xingque.FilesystemLoader(tmp_path, g)
+
+    results = {}
+    t = threading.Thread(target=lambda: results.update(x=loader.load("x.star")))
+    t.start()
+    try:
+        assert started.wait(10)
+        assert loader.load("y.star").get("y") == 3
+    finally:
+        release.set()
+        t.join()
+    assert results["x"].get("x") == 2
@@ -0,0 +1,184 @@
+import io
+import json
+from typing import Dict, List, Optional
+
+import xingque
+import xingque.lsp
+
+LIB_TEXT = '''
+def greet(name):
+    """Says hello to someone."""
+    return "hello " + name
+'''
+
+MAIN_TEXT = """load("lib.star", "greet")
+
+def main(who):
+    x = greet(who)
+    return len(x)
+
+main(later)
+later = "world"
+"""
+
+
+class Context:
+    dialect = xingque.Dialect.EXTENDED
+
+    def resolve_load(self, module: str, current_file: str) -> str:
+        return "/project/" + module
+
+    def get_load_contents(self, path: str) -> Optional[str]:
+        return LIB_TEXT if path == "/project/lib.star" else None
+
+
+def encode(messages: List[Dict]) -> bytes:
+    result = b""
+    for msg in messages:
+        body = json.dumps({"jsonrpc": "2.0", **msg}).encode("utf-8")
+        result += b"Content-Length: %d\r\n\r\n" % len(body) + body
+    return result
+
+
+def decode(data: bytes) -> List[Dict]:
+    result = []
+    while data:
+        header, _, data = data.partition(b"\r\n\r\n")
+        length = int(header.split(b":")[1])
+        result.append(json.loads(data[:length]))
+        data = data[length:]
+    return result
+
+
+def position(uri: str, line: int, character: int) -> Dict:
+    return {
+        "textDocument": {"uri": uri},
+        "position": {"line": line, "character": character},
+    }
+
+
+def test_serve():
+    uri = "file:///project/main.star"
+    stdin = io.BytesIO(
+        encode(
+            [
+                {"id": 1, "method": "initialize", "params": {"capabilities": {}}},
+                {"method": "initialized", "params": {}},
+                {
+                    "method": "textDocument/didOpen",
+                    "params": {
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": "starlark",
+                            "version": 1,
+                            "text": MAIN_TEXT,
+                        }
+                    },
+                },
+                # `greet` in `x = greet(who)`
+                {"id": 2, "method": "textDocument/hover", "params": position(uri, 3, 9)},
+                # `len`
+                {"id": 3, "method": "textDocument/hover", "params": position(uri, 4, 12)},
+                # `x` in `len(x)`
+                {
+                    "id": 4,
+                    "method": "textDocument/definition",
+                    "params": position(uri, 4, 15),
+                },
+                {
+                    "id": 5,
+                    "method": "textDocument/definition",
+                    "params": position(uri, 3, 9),
+                },
+                # `len` again
+                {
+                    "id": 6,
+                    "method": "textDocument/completion",
+                    "params": position(uri, 4, 12),
+                },
+                {"id": 7, "method": "nonexistent"},
+                {"id": 8, "method": "shutdown"},
+                {"id": 9, "method": "textDocument/hover", "params": position(uri, 3, 9)},
+                {"method": "exit"},
+                {"id": 10, "method": "shutdown"},
+            ]
+        )
+    )
+    stdout = io.BytesIO()
+    xingque.lsp.serve(stdin, stdout, Context())
+
+    msgs = decode(stdout.getvalue())
+    responses = {m["id"]: m for m in msgs if "id" in m}
+    notifications = [m for m in msgs if "id" not in m]
+
+    capabilities = responses[1]["result"]["capabilities"]
+    assert capabilities["hoverProvider"] is True
+    assert capabilities["definitionProvider"] is not None
+    assert capabilities["completionProvider"] is not None
+
+    (diags,) = [
+        n for n in notifications if n["method"] == "textDocument/publishDiagnostics"
+    ]
+    assert diags["params"]["uri"] == uri
+    assert any(
+        d["code"] == "using-unassigned" and d["range"]["start"]["line"] == 6
+        for d in diags["params"]["diagnostics"]
+    )
+
+    assert "Says hello to someone." in responses[2]["result"]["contents"][0]
+    assert "len" in responses[3]["result"]["contents"][0]
+
+    (link,) = responses[4]["result"]
+    assert link["targetUri"] == uri
+    assert link["targetRange"] == {
+        "start": {"line": 3, "character": 4},
+        "end": {"line": 3, "character": 5},
+    }
+    (link,) = responses[5]["result"]
+    assert link["targetUri"] == "file:///project/lib.star"
+    assert link["targetRange"]["start"] == {"line": 1, "character": 4}
+
+    labels = {item["label"] for item in responses[6]["result"]}
+    assert {"greet", "main", "x", "len"} <= labels
+
+    assert responses[7]["error"]["code"] == -32601
+    assert responses[8]["result"] is None
+    # requests between `shutdown` and `exit` are refused
+    assert responses[9]["error"]["code"] == -32600
+    # nothing is read after `exit`
+    assert 10 not in responses
+
+
+def test_serve_parse_error():
+    uri = "file:///project/bad.star"
+    stdin = io.BytesIO(
+        encode(
+            [
+                {"id": 1, "method": "initialize", "params": {"capabilities": {}}},
+                {"method": "initialized", "params": {}},
+                {
+                    "method": "textDocument/didOpen",
+                    "params": {
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": "starlark",
+                            "version": 1,
+                            "text": "def f(:\n",
+                        }
+                    },
+                },
+            ]
+        )
+    )
+    stdout = io.BytesIO()
+    # the server also stops when the input ends
+    xingque.lsp.serve(stdin, stdout, Context())
+
+    (msg,) = [
+        m
+        for m in decode(stdout.getvalue())
+        if m.get("method") == "textDocument/publishDiagnostics"
+    ]
+    (diag,) = msg["params"]["diagnostics"]
+    assert diag["severity"] == 1
+    assert diag["range"]["start"]["line"] == 0
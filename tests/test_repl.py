@@ -0,0 +1,83 @@
+import io
+
+import xingque
+from xingque.repl import Repl, is_unfinished, strip_comment
+
+
+def make_repl() -> "tuple[Repl, io.StringIO, io.StringIO]":
+    stdout = io.StringIO()
+    stderr = io.StringIO()
+    globals = xingque.Globals.extended_by([xingque.LibraryExtension.STRUCT_TYPE])
+    return Repl(globals, stdout=stdout, stderr=stderr), stdout, stderr
+
+
+def test_repl_keeps_module():
+    repl, stdout, stderr = make_repl()
+    assert not repl.push("a = 1")
+    assert not repl.push("a + 1")
+    assert not repl.push("None")
+    assert stdout.getvalue() == "2\n"
+    assert stderr.getvalue() == ""
+    assert repl.module.get("a") == 1
+
+
+def test_repl_multiline():
+    repl, stdout, _ = make_repl()
+    assert repl.push("def f(x):")
+    assert repl.push("    return x * 2")
+    assert not repl.push("")
+    assert repl.push("xs = [")
+    assert repl.push("  f(1),")
+    assert not repl.push("]")
+    assert not repl.push("xs")
+    assert stdout.getvalue() == "[2]\n"
+
+
+def test_repl_block_with_comment():
+    repl, stdout, stderr = make_repl()
+    assert repl.push("if True:  # always")
+    assert repl.push("    a = 1")
+    assert repl.push("    b = 2")
+    assert not repl.push("")
+    assert stderr.getvalue() == ""
+    assert repl.module.get("b") == 2
+
+    assert repl.push("def f(x): # doubles x")
+    assert repl.push("    return x * 2")
+    assert not repl.push("")
+    assert not repl.push("f(2)")
+    assert stdout.getvalue() == "4\n"
+
+
+def test_strip_comment():
+    assert strip_comment("if x:  # comment") == "if x:  "
+    assert strip_comment("x = '#' # c") == "x = '#' "
+    assert strip_comment('x = "a\\"#" # c') == 'x = "a\\"#" '
+    assert strip_comment("x = 1") == "x = 1"
+
+
+def test_repl_errors():
+    repl, stdout, stderr = make_repl()
+    assert not repl.push("undefined_name")
+    assert "Variable `undefined_name` not found" in stderr.getvalue()
+    assert not repl.push("1 +* 2")
+    assert "Parse error" in stderr.getvalue()
+    # the session is still usable
+    assert not repl.push("3")
+    assert stdout.getvalue() == "3\n"
+
+
+def test_repl_complete():
+    repl, _, _ = make_repl()
+    repl.push("my_var = 1")
+    assert repl.complete("my_") == ["my_var"]
+    assert "struct" in repl.complete("st")
+    assert "def" in repl.complete("de")
+
+
+def test_is_unfinished():
+    assert is_unfinished("foo(1,")
+    assert is_unfinished('x = """abc')
+    assert is_unfinished("x = 1 + \\")
+    assert not is_unfinished("foo(1)")
+    assert not is_unfinished("x = '(' # [")
@@ -1,5 +1,8 @@
+import datetime
+import enum
 import functools
 
+import pytest
 import xingque
 
 
@@ -271,3 +274,387 @@ bitnot = ~foo
     assert m.get("bitor") == "or:123"
     assert m.get("bitxor") == "xor:123"
     assert m.get("bitnot") == "invert"
+
+
+def test_item_forwarding():
+    class Foo:
+        def __init__(self) -> None:
+            self.items: "dict[object, object]" = {}
+
+        def __getitem__(self, key: object) -> object:
+            if isinstance(key, slice):
+                return f"slice:{key.start}:{key.stop}:{key.step}"
+            if key == "missing":
+                raise KeyError(key)
+            return f"item:{key}"
+
+        def __setitem__(self, key: object, value: object) -> None:
+            self.items[key] = value
+
+    text = """
+at_int = foo[0]
+at_str = foo["key"]
+slice_full = foo[1:3]
+slice_stride = foo[::2]
+foo["x"] = [1, 2]
+foo[3] = None
+"""
+
+    recorder = Foo()
+
+    am = xingque.AstModule.parse("test.star", text)
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set("foo", recorder)
+    g = gb.build()
+    m = xingque.Module()
+    e = xingque.Evaluator(m)
+    e.eval_module(am, g)
+
+    assert m.get("at_int") == "item:0"
+    assert m.get("at_str") == "item:key"
+    assert m.get("slice_full") == "slice:1:3:None"
+    assert m.get("slice_stride") == "slice:None:None:2"
+    assert recorder.items == {"x": [1, 2], 3: None}
+
+    am = xingque.AstModule.parse("test.star", 'foo["missing"]')
+    with pytest.raises(KeyError):
+        xingque.Evaluator().eval_module(am, g)
+
+
+def test_iteration_forwarding():
+    fetched = []
+
+    def numbers():
+        for i in range(5):
+            fetched.append(i)
+            yield i
+
+    class Foo:
+        def __iter__(self):
+            return iter(["a", "b"])
+
+    text = """
+def first_even(xs):
+    for x in xs:
+        if x > 0 and x % 2 == 0:
+            return x
+
+first = first_even(gen)
+squares = [x * x for x in foo_set]
+letters = {x: True for x in foo}
+"""
+
+    am = xingque.AstModule.parse("test.star", text)
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set("gen", numbers())
+    gb.set("foo_set", {3})
+    gb.set("foo", Foo())
+    g = gb.build()
+    m = xingque.Module()
+    e = xingque.Evaluator(m)
+    e.eval_module(am, g)
+
+    assert m.get("first") == 2
+    # the generator is consumed lazily
+    assert fetched == [0, 1, 2]
+    assert m.get("squares") == [9]
+    assert m.get("letters") == {"a": True, "b": True}
+
+    am = xingque.AstModule.parse("test.star", "[x for x in obj]")
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set("obj", object())
+    with pytest.raises(TypeError):
+        xingque.Evaluator().eval_module(am, gb.build())
+
+
+def test_iteration_error():
+    def numbers():
+        yield 1
+        raise ValueError("broken")
+
+    am = xingque.AstModule.parse("test.star", "[x for x in gen]")
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set("gen", numbers())
+    with pytest.raises(ValueError, match="broken"):
+        xingque.Evaluator().eval_module(am, gb.build())
+
+
+def test_reflected_operator_forwarding():
+    class Foo:
+        def __init__(self, value: int) -> None:
+            self.value = value
+
+        def __radd__(self, lhs: object) -> str:
+            return f"radd:{lhs}"
+
+        def __rmul__(self, lhs: object) -> str:
+            return f"rmul:{lhs}"
+
+        def __eq__(self, other: object) -> bool:
+            return isinstance(other, Foo) and other.value == self.value
+
+        def __lt__(self, other: "Foo") -> bool:
+            return self.value < other.value
+
+        def __gt__(self, other: "Foo") -> bool:
+            return self.value > other.value
+
+        __hash__ = object.__hash__
+
+    text = """
+radd_int = 1 + small
+radd_str = "x" + small
+rmul_int = 2 * small
+rmul_str = "x" * small
+lt = small < big
+ordered = sorted([big, small]) == [small, big]
+maximum = max(small, big) == big
+contained = small in [big, small]
+"""
+
+    am = xingque.AstModule.parse("test.star", text)
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set("small", Foo(1))
+    gb.set("big", Foo(2))
+    gb.set("other", object())
+    gb.set("another", object())
+    g = gb.build()
+    m = xingque.Module()
+    e = xingque.Evaluator(m)
+    e.eval_module(am, g)
+
+    assert m.get("radd_int") == "radd:1"
+    assert m.get("radd_str") == "radd:x"
+    assert m.get("rmul_int") == "rmul:2"
+    assert m.get("rmul_str") == "rmul:x"
+    assert m.get("lt")
+    assert m.get("ordered")
+    assert m.get("maximum")
+    assert m.get("contained")
+
+    for text in ["1 + other", '"x" * other', "other < another"]:
+        am = xingque.AstModule.parse("test.star", text)
+        with pytest.raises(TypeError):
+            xingque.Evaluator().eval_module(am, g)
+
+
+def test_register_type():
+    @xingque.register_type
+    class Target:
+        name: str
+        deps: "list[str]"
+
+        def __init__(self, name: str) -> None:
+            self.name = name
+            self.deps: "list[str]" = []
+            self.internal = True
+
+    class Rule:
+        def __init__(self, kind: str) -> None:
+            self.kind = kind
+            self.internal = True
+
+        def describe(self) -> str:
+            return f"rule {self.kind}"
+
+    assert xingque.register_type(
+        Rule, name="BuildRule", attrs={"kind": str, "describe": "typing.Callable"}
+    ) is Rule
+
+    text = """
+def make(name: str) -> Target:
+    t = Target(name)
+    t.deps = ["//base"]
+    return t
+
+t = make("app")
+target_dir = dir(t)
+has_internal = hasattr(t, "internal")
+rule_dir = dir(rule)
+description = rule.describe()
+"""
+
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set("Target", Target)
+    gb.set("rule", Rule("cc_binary"))
+    g = gb.build()
+    am = xingque.AstModule.parse("test.star", text, xingque.Dialect.EXTENDED)
+    m = xingque.Module()
+    xingque.Evaluator(m).eval_module(am, g)
+
+    t = m.get("t")
+    assert isinstance(t, Target)
+    assert t.deps == ["//base"]
+    assert m.get("target_dir") == ["deps", "name"]
+    assert not m.get("has_internal")
+    assert m.get("rule_dir") == ["describe", "kind"]
+    assert m.get("description") == "rule cc_binary"
+
+    for text in ["rule.internal", "rule.internal = 1"]:
+        am = xingque.AstModule.parse("test.star", text)
+        with pytest.raises(xingque.StarlarkValueError):
+            xingque.Evaluator().eval_module(am, g)
+
+    # instances only match the annotation of their own registered class
+    am = xingque.AstModule.parse(
+        "test.star",
+        "def f(t: Target):\n    pass\nf(rule)",
+        xingque.Dialect.EXTENDED,
+    )
+    with pytest.raises(xingque.StarlarkError) as excinfo:
+        xingque.Evaluator().eval_module(am, g)
+    assert "does not match the type annotation `Target`" in excinfo.value.message
+
+    text = """
+def f(t: Target) -> int:
+    return t.name
+
+def g() -> str:
+    return rule.nope
+"""
+    am = xingque.AstModule.parse("test.star", text, xingque.Dialect.EXTENDED)
+    errors, _ = am.typecheck(g)
+    messages = sorted(e.message for e in errors)
+    assert len(messages) == 2
+    assert "Expected type `int` but got `str`" in messages[0]
+    assert "The attribute `nope` is not available on the type `BuildRule`" in messages[1]
+
+
+def test_registered_class_object():
+    @xingque.register_type
+    class Rule:
+        KIND = "rule"
+        kind: str
+
+        def __init__(self, kind: str) -> None:
+            self.kind = kind
+
+        @staticmethod
+        def make(kind: str) -> "Rule":
+            return Rule(kind)
+
+    text = """
+kind = Rule.KIND
+made = Rule.make("a").kind
+has_make = hasattr(Rule, "make")
+in_dir = "KIND" in dir(Rule)
+by_class = {Rule: 1}[Rule]
+truthy = bool(Rule)
+"""
+    gb = xingque.GlobalsBuilder.standard()
+    gb.set("Rule", Rule)
+    am = xingque.AstModule.parse("test.star", text)
+    m = xingque.Module()
+    xingque.Evaluator(m).eval_module(am, gb.build())
+
+    assert m.get("kind") == "rule"
+    assert m.get("made") == "a"
+    assert m.get("has_make")
+    assert m.get("in_dir")
+    assert m.get("by_class") == 1
+    assert m.get("truthy")
+
+
+def test_stdlib_types_round_trip():
+    values = {
+        "s": {1, 2},
+        "fs": frozenset({3}),
+        "b": b"abc",
+        "d": datetime.date(2024, 6, 25),
+        "td": datetime.timedelta(days=1),
+    }
+
+    text = """
+set_len = len(s)
+set_has = 2 in s
+set_sorted = sorted(s)
+set_union = s | fs
+by_frozenset = {fs: "x"}
+bytes_item = b[0]
+bytes_slice = b[1:]
+bytes_list = list(b)
+date_year = d.year
+date_next = d + td
+date_ordered = d < d + td
+echo = [s, fs, b, d]
+"""
+
+    gb = xingque.GlobalsBuilder.standard()
+    for k, v in values.items():
+        gb.set(k, v)
+    am = xingque.AstModule.parse("test.star", text)
+    m = xingque.Module()
+    xingque.Evaluator(m).eval_module(am, gb.build())
+
+    assert m.get("set_len") == 2
+    assert m.get("set_has")
+    assert m.get("set_sorted") == [1, 2]
+    assert m.get("set_union") == {1, 2, 3}
+    assert m.get("by_frozenset") == {frozenset({3}): "x"}
+    assert m.get("bytes_item") == 97
+    assert m.get("bytes_slice") == b"bc"
+    assert m.get("bytes_list") == [97, 98, 99]
+    assert m.get("date_year") == 2024
+    assert m.get("date_next") == datetime.date(2024, 6, 26)
+    assert m.get("date_ordered")
+
+    # the very same objects come back
+    echo = m.get("echo")
+    for got, want in zip(echo, [values["s"], values["fs"], values["b"], values["d"]]):
+        assert got is want
+
+
+def test_register_converter():
+    class Color(enum.Enum):
+        RED = 1
+        GREEN = 2
+
+    class Loop:
+        pass
+
+    xingque.register_converter(set, sorted)
+    xingque.register_converter(Color, lambda x: x.name.lower())
+    xingque.register_converter(Loop, lambda x: Loop())
+    try:
+        text = """
+items = s
+nested = {"color": c, "sets": [s]}
+color_type = type(c)
+loop_type = type(loop)
+made = make()
+"""
+        gb = xingque.GlobalsBuilder.standard()
+        gb.set("s", {3, 1, 2})
+        gb.set("c", Color.GREEN)
+        gb.set("loop", Loop())
+        gb.set_function("make", lambda: {5, 4})
+        am = xingque.AstModule.parse("test.star", text)
+        m = xingque.Module()
+        xingque.Evaluator(m).eval_module(am, gb.build())
+
+        assert m.get("items") == [1, 2, 3]
+        assert m.get("nested") == {"color": "green", "sets": [[1, 2, 3]]}
+        assert m.get("color_type") == "string"
+        # converting to the same kind of value again would never end
+        assert m.get("loop_type") == "pyobject"
+        # results of Python calls are converted too
+        assert m.get("made") == [4, 5]
+
+        # frozenset isn't a subclass of set, so it stays as is
+        m.set("fs", frozenset({1}))
+        assert m.get("fs") == frozenset({1})
+
+        def broken(x: object) -> object:
+            raise KeyError("no")
+
+        xingque.register_converter(Color, broken)
+        with pytest.raises(KeyError):
+            m.set("c", Color.RED)
+    finally:
+        xingque.register_converter(set, None)
+        xingque.register_converter(Color, None)
+        xingque.register_converter(Loop, None)
+
+    m = xingque.Module()
+    m.set("s", {1})
+    assert m.get("s") == {1}
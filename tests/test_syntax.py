@@ -0,0 +1,415 @@
+import json
+
+import pytest
+
+import xingque
+
+
+LINTED_TEXT = """
+load("a.star", "unused")
+
+def f(x):
+    if x:
+        return 1
+
+def g():
+    return undefined_name
+"""
+
+
+def test_lint():
+    am = xingque.AstModule.parse("test.star", LINTED_TEXT)
+    lints = am.lint()
+    names = {x.short_name for x in lints}
+    assert "missing-return" in names
+    assert "unused-load" in names
+    assert "using-undefined" not in names
+
+    (lint,) = [x for x in lints if x.short_name == "missing-return"]
+    assert isinstance(lint.severity, xingque.EvalSeverity)
+    assert lint.location.filename == "test.star"
+    assert lint.location.resolve().span.begin.line == 3
+    assert lint.original.startswith("def f(x):")
+    assert lint.problem
+    assert str(lint).startswith("test.star:4:")
+
+
+def test_lint_with_globals():
+    am = xingque.AstModule.parse("test.star", LINTED_TEXT)
+    for globals in (xingque.Globals.standard(), ["len", "str"]):
+        lints = am.lint(globals)
+        names = {x.short_name for x in lints}
+        assert "using-undefined" in names
+
+
+def test_typecheck():
+    globals = xingque.Globals.standard()
+    lib = xingque.AstModule.parse(
+        "lib.star",
+        "def f(x: int) -> str:\n    return str(x)\n\nN = 1\n",
+        xingque.Dialect.EXTENDED,
+    )
+    errors, iface = lib.typecheck(globals)
+    assert errors == []
+    assert isinstance(iface, xingque.Interface)
+    assert iface.names() == ["f", "N"]
+    assert "f" in iface
+    assert iface.get("nonexistent") is None
+    assert set(iface.types) == {"f", "N"}
+
+    am = xingque.AstModule.parse(
+        "test.star",
+        'load("lib.star", "f")\n\ndef g():\n    return f("x")\n',
+        xingque.Dialect.EXTENDED,
+    )
+    errors, _ = am.typecheck(globals, {"lib.star": iface})
+    (err,) = errors
+    assert isinstance(err, xingque.StarlarkTypeCheckError)
+    assert err.message == "Expected type `int` but got `str`"
+    assert err.span.resolve().span.begin.line == 3
+
+    # the module is not consumed
+    assert len(am.loads) == 1
+
+    # edits to the module are typechecked
+    am = xingque.AstModule.parse(
+        "test.star",
+        "def plus(x: int, y: int) -> int:\n    return x\n\n"
+        'def g():\n    return "a" + "b"\n',
+        xingque.Dialect.EXTENDED,
+    )
+    assert am.typecheck(globals)[0] == []
+    am.replace_binary_operators({"+": "plus"})
+    (err,) = am.typecheck(globals)[0]
+    assert err.message == "Expected type `int` but got `str`"
+
+
+AST_TEXT = """
+load("//tools:defs.bzl", "cc_library", lib = "library")
+
+def f(x, *, y: int = 1):
+    if x:
+        return [i * 2 for i in x if i]
+    else:
+        pass
+
+cc_library(name = "foo", srcs = ["a.c"] + glob(["*.h"]), **kwargs)
+"""
+
+
+def test_statements():
+    am = xingque.AstModule.parse("BUILD", AST_TEXT, xingque.Dialect.EXTENDED)
+    load, def_, expr = am.statements
+
+    assert isinstance(load, xingque.Load)
+    assert load.module == "//tools:defs.bzl"
+    assert [(x.local, x.their) for x in load.args] == [
+        ("cc_library", "cc_library"),
+        ("lib", "library"),
+    ]
+
+    assert isinstance(def_, xingque.Def)
+    assert def_.name == "f"
+    assert [(x.kind, x.name) for x in def_.params] == [
+        (xingque.ParameterKind.NORMAL, "x"),
+        (xingque.ParameterKind.NO_ARGS, None),
+        (xingque.ParameterKind.WITH_DEFAULT_VALUE, "y"),
+    ]
+    assert def_.params[2].annotation.name == "int"
+    assert def_.params[2].default.value == 1
+    (if_,) = def_.body
+    assert isinstance(if_, xingque.If)
+    assert isinstance(if_.body[0].value, xingque.ListComprehension)
+    assert [type(x) for x in if_.body[0].value.clauses] == [
+        xingque.ForClause,
+        xingque.IfClause,
+    ]
+    assert isinstance(if_.orelse[0], xingque.Pass)
+
+    call = expr.value
+    assert isinstance(call, xingque.Call)
+    assert call.func.name == "cc_library"
+    assert [(x.kind, x.name) for x in call.args] == [
+        (xingque.ArgumentKind.NAMED, "name"),
+        (xingque.ArgumentKind.NAMED, "srcs"),
+        (xingque.ArgumentKind.KW_ARGS, None),
+    ]
+    assert call.args[1].value.op == "+"
+    assert call.children() == [call.func] + call.args
+
+    span = am.file_span(call.span).resolve().span
+    assert (span.begin.line, span.begin.column) == (9, 0)
+    assert repr(call.args[0]) == (
+        "Argument(kind=ArgumentKind.NAMED, name='name', value=Literal(value='foo'))"
+    )
+
+
+def test_unparse_roundtrip():
+    am = xingque.AstModule.parse("BUILD", AST_TEXT, xingque.Dialect.EXTENDED)
+    text = am.unparse()
+    assert text == AST_TEXT.lstrip()
+    am2 = xingque.AstModule.parse("BUILD", text, xingque.Dialect.EXTENDED)
+    assert repr(am2.statements) == repr(am.statements)
+
+    expr = xingque.Op(
+        xingque.Op(xingque.Identifier("a"), "+", xingque.Identifier("b")),
+        "*",
+        xingque.Minus(xingque.Literal(1)),
+    )
+    assert xingque.unparse(expr) == "(a + b) * -1"
+    assert xingque.unparse(xingque.Literal('say "hi"\n')) == '"say \\"hi\\"\\n"'
+
+
+def test_unparse_literals():
+    def roundtrip(node: xingque.AstNode) -> str:
+        text = xingque.unparse(node)
+        (stmt,) = xingque.AstModule.parse("test.star", text).statements
+        assert xingque.unparse(stmt.value) == text
+        return text
+
+    assert roundtrip(xingque.Literal(1.5e100)) == "1.5e+100"
+    assert roundtrip(xingque.Literal(-2)) == "-2"
+    assert roundtrip(xingque.Dot(xingque.Literal(1), "real")) == "(1).real"
+    assert roundtrip(xingque.Dot(xingque.Literal(0.5), "x")) == "(0.5).x"
+    index = xingque.Index(xingque.Literal(-1), xingque.Literal(0))
+    assert roundtrip(index) == "(-1)[0]"
+    call = xingque.Call(xingque.Literal(-1), [])
+    assert roundtrip(call) == "(-1)()"
+
+    with pytest.raises(TypeError, match="cannot write a list as a Starlark literal"):
+        xingque.unparse(xingque.Literal([1]))
+    with pytest.raises(ValueError, match="cannot write nan as a Starlark literal"):
+        xingque.unparse(xingque.Literal(float("nan")))
+    with pytest.raises(ValueError, match="cannot write -inf as a Starlark literal"):
+        xingque.unparse(xingque.Literal(float("-inf")))
+
+
+def test_node_visitor():
+    class CallCollector(xingque.NodeVisitor):
+        def __init__(self):
+            self.names = []
+
+        def visit_Call(self, node):
+            self.names.append(node.func.name)
+            self.generic_visit(node)
+
+    am = xingque.AstModule.parse("BUILD", AST_TEXT, xingque.Dialect.EXTENDED)
+    v = CallCollector()
+    v.visit(am.statements)
+    assert v.names == ["cc_library", "glob"]
+
+
+def test_node_transformer():
+    class Rewriter(xingque.NodeTransformer):
+        def visit_Call(self, node):
+            self.generic_visit(node)
+            if node.func.name == "cc_library":
+                node.func = xingque.Identifier("cc_lib")
+                node.args.insert(
+                    2,
+                    xingque.Argument(
+                        xingque.ArgumentKind.NAMED,
+                        "visibility",
+                        xingque.List([xingque.Literal("//visibility:public")]),
+                    ),
+                )
+            return node
+
+        def visit_Def(self, node):
+            # drop all defs
+            return None
+
+    am = xingque.AstModule.parse("BUILD", AST_TEXT, xingque.Dialect.EXTENDED)
+    statements = Rewriter().visit(am.statements)
+    assert [type(x) for x in statements] == [xingque.Load, xingque.Expression]
+    assert am.unparse(statements) == (
+        'load("//tools:defs.bzl", "cc_library", lib = "library")\n'
+        "\n"
+        "cc_lib(\n"
+        '    name = "foo",\n'
+        '    srcs = ["a.c"] + glob(["*.h"]),\n'
+        '    visibility = ["//visibility:public"],\n'
+        "    **kwargs,\n"
+        ")\n"
+    )
+
+
+FORMAT_TEXT = """
+# leading comment
+load("//a:b.bzl",   "x")  # trailing
+
+def f(a,b=1):  # on the header
+    # in the body
+    return  [a, b]
+
+
+
+cc_library(name = "foo", srcs = ["a.cc", "b.cc", "c.cc"], deps = [":bar", ":baz"])
+cc_binary(name = "x", deps = [
+    "a",  # why a
+])
+"""
+
+
+def test_format():
+    expected = """\
+# leading comment
+load("//a:b.bzl", "x")  # trailing
+
+def f(a, b = 1):  # on the header
+    # in the body
+    return [a, b]
+
+cc_library(
+    name = "foo",
+    srcs = ["a.cc", "b.cc", "c.cc"],
+    deps = [":bar", ":baz"],
+)
+cc_binary(
+    name = "x",
+    deps = [
+        "a",  # why a
+    ],
+)
+"""
+    am = xingque.AstModule.parse("BUILD", FORMAT_TEXT)
+    assert am.format() == expected
+    assert xingque.format_source(FORMAT_TEXT) == expected
+    assert xingque.format_source(expected) == expected
+
+
+def test_format_operator_comments():
+    text = """\
+x = (a  # first
+     + b)  # second
+y = (
+    a
+    # standalone
+    or b
+)
+"""
+    expected = """\
+x = (
+    a  # first
+    + b  # second
+)
+y = (
+    a
+    # standalone
+    or b
+)
+"""
+    assert xingque.format_source(text) == expected
+    assert xingque.format_source(expected) == expected
+
+
+def test_format_tuples_and_unary():
+    text = """\
+a, b = 1, 2
+(c, d) = (3, 4)
+x = 1,
+y = (1,)
+
+def f():
+    for i in (1, 2):
+        pass
+    return a, b
+
+def g():
+    return (a, b)
+
+z = -(-1)
+w = ~-x
+"""
+    assert xingque.format_source(text) == text
+    assert xingque.format_source("x = - -1\n") == "x = - -1\n"
+    assert xingque.format_source("x = --1\n") == "x = - -1\n"
+    nested = xingque.Minus(xingque.Minus(xingque.Literal(1)))
+    assert xingque.unparse(nested) == "-(-1)"
+    assert xingque.unparse(xingque.Minus(xingque.Literal(-1))) == "-(-1)"
+
+    # tuples that no longer fit on a line are parenthesized to be broken
+    long = "x = " + ", ".join(f"item{i}" for i in range(30)) + "\n"
+    assert xingque.format_source(long).startswith("x = (\n    item0,\n")
+
+
+def test_format_large():
+    text = "\n".join(
+        f"x{i} = f([{i}, {i + 1}], {{'k': {i}}}, name = 'n{i}')  # c{i}"
+        for i in range(3000)
+    )
+    formatted = xingque.format_source(text)
+    assert formatted.count("\n") == 3000
+    assert formatted.startswith('x0 = f([0, 1], {"k": 0}, name = "n0")  # c0\n')
+    assert xingque.format_source(formatted) == formatted
+
+
+DOC_TEXT = '''
+"""C++ rules.
+
+Details here.
+"""
+
+def cc_library(name: str, srcs: list[str] = [], **kwargs) -> None:
+    """Defines a C++ library.
+
+    Args:
+        name: The name of the target.
+
+    Returns:
+        Nothing.
+    """
+    pass
+
+def _helper():
+    pass
+
+VERSION = "1.0"
+'''
+
+
+def test_documentation():
+    am = xingque.AstModule.parse("defs.bzl", DOC_TEXT, xingque.Dialect.EXTENDED)
+    docs = am.documentation()
+    assert docs.docs.summary == "C++ rules."
+    assert docs.docs.details == "Details here."
+    assert list(docs.members) == ["cc_library", "VERSION"]
+
+    f = docs.members["cc_library"]
+    assert f.docs.summary == "Defines a C++ library."
+    assert [(x.kind, x.name, x.type, x.default_value) for x in f.params] == [
+        (xingque.DocParamKind.ARG, "name", "str", None),
+        (xingque.DocParamKind.ARG, "srcs", "list[str]", "[]"),
+        (xingque.DocParamKind.KWARGS, "kwargs", "typing.Any", None),
+    ]
+    assert f.params[0].docs.summary == "The name of the target."
+    assert f.ret.type == "None"
+    assert f.ret.docs.summary == "Nothing."
+
+    assert isinstance(docs.members["VERSION"], xingque.DocProperty)
+    assert json.loads(f.to_json())["kind"] == "function"
+
+
+def test_documentation_render():
+    am = xingque.AstModule.parse("defs.bzl", DOC_TEXT, xingque.Dialect.EXTENDED)
+    docs = am.documentation()
+
+    md = docs.render(title="C++", links={"str": "https://example.com/str"})
+    assert md.startswith("# C++\n\nC++ rules.\n\nDetails here.\n")
+    assert "* [`cc_library`](#cc_library): Defines a C++ library.\n" in md
+    assert '<a id="cc_library"></a>\n\n## `cc_library`\n' in md
+    assert (
+        "```python\n"
+        "def cc_library(name: str, srcs: list[str] = [], **kwargs) -> None\n"
+        "```\n"
+    ) in md
+    assert "* `name` ([`str`](https://example.com/str)): The name" in md
+    assert "* `srcs` (`list[str]`, default `[]`)\n" in md
+
+    html = docs.render("html", title="C++ <rules>")
+    assert html.startswith("<!DOCTYPE html>")
+    assert "<h1>C++ &lt;rules&gt;</h1>" in html
+    assert '<h2 id="VERSION"><code>VERSION</code></h2>' in html
+
+    with pytest.raises(ValueError):
+        docs.render("pdf")